@@ -14,7 +14,7 @@ use epd_waveshare::{color::Color, epd7in5_v2::*, graphics::DisplayRotation, prel
 use linux_embedded_hal::{
     gpio_cdev::{Chip, LineRequestFlags},
     spidev::{SpiModeFlags, SpidevOptions},
-    CdevPin, Delay, SpidevDevice,
+    CdevPin, Delay, SPIError, SpidevDevice,
 };
 
 // GPIO pin definitions (BCM numbering - no offset needed for cdev)
@@ -42,7 +42,8 @@ fn main() -> Result<(), Box<dyn Error>> {
     let busy_pin = CdevPin::new(busy_handle)?;
 
     let pwr_line = chip.get_line(EPD_PWR_PIN)?;
-    let _ = pwr_line.request(LineRequestFlags::OUTPUT, 1, "epd-pwr")?;
+    let pwr_handle = pwr_line.request(LineRequestFlags::OUTPUT, 0, "epd-pwr")?;
+    let pwr_pin = CdevPin::new(pwr_handle)?;
 
     // Initialize SPI
     let mut spi = SpidevDevice::open("/dev/spidev0.0")?;
@@ -55,9 +56,13 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let mut delay = Delay {};
 
-    let mut epd7in5 =
-        Epd7in5::new(&mut spi, busy_pin, dc_pin, rst_pin, &mut delay, None).expect("epd new");
-    epd7in5.set_lut(&mut spi, &mut delay, Some(RefreshLut::Quick))?;
+    let mut epd7in5 = Epd7in5::new_with_power(
+        &mut spi, busy_pin, dc_pin, rst_pin, pwr_pin, &mut delay, None,
+    )
+    .expect("epd new");
+    epd7in5
+        .set_lut(&mut spi, &mut delay, Some(RefreshLut::Quick))
+        .map_err(epd_error)?;
     let mut display = Display7in5::default();
     display.clear(Color::White);
     println!("Device successfully initialized!");
@@ -78,7 +83,9 @@ fn main() -> Result<(), Box<dyn Error>> {
     display.set_rotation(DisplayRotation::Rotate270);
     draw_text(&mut display, "Rotate 270!", 5, 50);
 
-    epd7in5.update_and_display_frame(&mut spi, display.buffer(), &mut delay)?;
+    epd7in5
+        .update_and_display_frame(&mut spi, display.buffer(), &mut delay)
+        .map_err(epd_error)?;
     delay.delay_ms(5000);
 
     // Draw an analog clock
@@ -98,7 +105,9 @@ fn main() -> Result<(), Box<dyn Error>> {
     let _ = Line::new(Point::new(64, 64), Point::new(80, 80))
         .into_styled(style)
         .draw(&mut display);
-    epd7in5.update_and_display_frame(&mut spi, display.buffer(), &mut delay)?;
+    epd7in5
+        .update_and_display_frame(&mut spi, display.buffer(), &mut delay)
+        .map_err(epd_error)?;
     delay.delay_ms(5000);
 
     // Draw some text
@@ -145,7 +154,9 @@ fn main() -> Result<(), Box<dyn Error>> {
         )
         .draw(&mut display);
     }
-    epd7in5.update_and_display_frame(&mut spi, display.buffer(), &mut delay)?;
+    epd7in5
+        .update_and_display_frame(&mut spi, display.buffer(), &mut delay)
+        .map_err(epd_error)?;
     delay.delay_ms(5000);
 
     // Draw an image
@@ -155,14 +166,20 @@ fn main() -> Result<(), Box<dyn Error>> {
     let raw_image = ImageRaw::<Color>::new(data, 460);
     let image = Image::new(&raw_image, Point::zero());
     image.draw(&mut display).unwrap();
-    epd7in5.update_and_display_frame(&mut spi, display.buffer(), &mut delay)?;
+    epd7in5
+        .update_and_display_frame(&mut spi, display.buffer(), &mut delay)
+        .map_err(epd_error)?;
 
     delay.delay_ms(5000);
 
     println!("Clock Demo (partial update)");
-    epd7in5.clear_frame(&mut spi, &mut delay)?;
+    epd7in5
+        .clear_frame(&mut spi, &mut delay)
+        .map_err(epd_error)?;
 
-    epd7in5.set_lut(&mut spi, &mut delay, Some(RefreshLut::PartialRefresh))?;
+    epd7in5
+        .set_lut(&mut spi, &mut delay, Some(RefreshLut::PartialRefresh))
+        .map_err(epd_error)?;
 
     // Clock parameters - using FONT_6X10 (6 pixels wide, 10 pixels tall)
     // "HH:MM:SS" = 8 characters
@@ -184,13 +201,15 @@ fn main() -> Result<(), Box<dyn Error>> {
             clock_buffer_height,
         )
     ];
-    let mut partial_display = display.get_partial_frame(
-        &mut partial_frame_buffer,
-        partial_frame_x,
-        partial_frame_y,
-        clock_buffer_width,
-        clock_buffer_height,
-    );
+    let mut partial_display = display
+        .get_partial_frame(
+            &mut partial_frame_buffer,
+            partial_frame_x,
+            partial_frame_y,
+            clock_buffer_width,
+            clock_buffer_height,
+        )
+        .expect("partial frame");
 
     // Time variables
     let mut hours = 12u8;
@@ -223,7 +242,9 @@ fn main() -> Result<(), Box<dyn Error>> {
             )
             .unwrap();
 
-        epd7in5.display_frame(&mut spi, &mut delay)?;
+        epd7in5
+            .display_frame(&mut spi, &mut delay)
+            .map_err(epd_error)?;
 
         // Increment time
         seconds += 1;
@@ -245,10 +266,14 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     // Clear and sleep
     println!("Clear the display");
-    epd7in5.set_lut(&mut spi, &mut delay, Some(RefreshLut::Full))?;
-    epd7in5.clear_frame(&mut spi, &mut delay)?;
-    println!("Finished tests - going to sleep");
-    epd7in5.sleep(&mut spi, &mut delay)?;
+    epd7in5
+        .set_lut(&mut spi, &mut delay, Some(RefreshLut::Full))
+        .map_err(epd_error)?;
+    epd7in5
+        .clear_frame(&mut spi, &mut delay)
+        .map_err(epd_error)?;
+    println!("Finished tests - powering off");
+    epd7in5.power_off(&mut spi, &mut delay).map_err(epd_error)?;
     Ok(())
 }
 
@@ -263,3 +288,8 @@ fn draw_text<D: DrawTarget<Color = Color>>(display: &mut D, text: &str, x: i32,
 
     let _ = Text::with_text_style(text, Point::new(x, y), style, text_style).draw(display);
 }
+
+// the driver error is no_std and thus does not implement std::error::Error
+fn epd_error(e: epd_waveshare::Error<SPIError>) -> String {
+    e.to_string()
+}
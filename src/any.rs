@@ -0,0 +1,522 @@
+//! One driver type for several black and white displays, chosen at runtime
+//!
+//! Needs the `any_epd` feature.
+//!
+//! Every driver has its own type, so a firmware supporting several displays can't easily hold
+//! "one of them". [`AnyEpd`] wraps the black and white drivers and forwards [`WaveshareDisplay`]
+//! to the display picked by a [`Model`], e.g. read from a strap pin or an EEPROM.
+//!
+//!```rust, no_run
+//!# use embedded_hal_mock::eh1::*;
+//!# fn main() -> Result<(), epd_waveshare::Error<embedded_hal::spi::ErrorKind>> {
+//!use embedded_graphics::{prelude::*, primitives::{Line, PrimitiveStyle}};
+//!use epd_waveshare::{any::*, prelude::*};
+//!#
+//!# let expectations = [];
+//!# let mut spi = spi::Mock::new(&expectations);
+//!# let expectations = [];
+//!# let busy_in = digital::Mock::new(&expectations);
+//!# let dc = digital::Mock::new(&expectations);
+//!# let rst = digital::Mock::new(&expectations);
+//!# let mut delay = delay::NoopDelay::new();
+//!# let strap_pin_high = false;
+//!
+//!let model = if strap_pin_high { Model::Epd4in2 } else { Model::Epd7in5V2 };
+//!let mut epd = AnyEpd::new(model, &mut spi, busy_in, dc, rst, &mut delay, None)?;
+//!
+//!// large enough for every model
+//!let mut buffer = [0u8; MAX_BUFFER_LEN];
+//!let mut display = model.display(&mut buffer).expect("buffer");
+//!let _ = Line::new(Point::new(0, 120), Point::new(0, 295))
+//!    .into_styled(PrimitiveStyle::with_stroke(Color::Black, 1))
+//!    .draw(&mut display);
+//!
+//!epd.update_and_display_frame(&mut spi, display.buffer(), &mut delay)?;
+//!epd.sleep(&mut spi, &mut delay)?;
+//!# Ok(())
+//!# }
+//!```
+
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{InputPin, OutputPin},
+    spi::SpiDevice,
+};
+
+use crate::buffer_len;
+use crate::color::Color;
+use crate::config::EpdConfig;
+use crate::error::Error;
+use crate::power::NoPowerPin;
+use crate::traits::{
+    BusyPolling, Capabilities, ColorKind, DeepSleepMode, RefreshLut, WaveshareDisplay,
+    WaveshareDisplayNew,
+};
+use crate::{
+    epd1in02, epd1in54, epd1in54_v2, epd2in13_v2, epd2in7, epd2in7_v2, epd2in9, epd2in9_v2,
+    epd2in9d, epd3in7, epd4in2, epd5in83_v2, epd7in5, epd7in5_hd, epd7in5_v2,
+};
+
+#[cfg(feature = "graphics")]
+use crate::graphics::{VarDisplay, VarDisplayError};
+
+/// Calls `$call` on the driver inside of `$epd`, whatever the variant
+macro_rules! dispatch {
+    ($epd:expr, $inner:ident => $call:expr) => {
+        match $epd {
+            AnyEpd::Epd1in02($inner) => $call,
+            AnyEpd::Epd1in54($inner) => $call,
+            AnyEpd::Epd1in54V2($inner) => $call,
+            AnyEpd::Epd2in13V2($inner) => $call,
+            AnyEpd::Epd2in7($inner) => $call,
+            AnyEpd::Epd2in7V2($inner) => $call,
+            AnyEpd::Epd2in9($inner) => $call,
+            AnyEpd::Epd2in9V2($inner) => $call,
+            AnyEpd::Epd2in9d($inner) => $call,
+            AnyEpd::Epd3in7($inner) => $call,
+            AnyEpd::Epd4in2($inner) => $call,
+            AnyEpd::Epd5in83V2($inner) => $call,
+            AnyEpd::Epd7in5($inner) => $call,
+            AnyEpd::Epd7in5Hd($inner) => $call,
+            AnyEpd::Epd7in5V2($inner) => $call,
+        }
+    };
+}
+
+/// The displays [`AnyEpd`] drives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    /// [`epd1in02`]
+    Epd1in02,
+    /// [`epd1in54`]
+    Epd1in54,
+    /// [`epd1in54_v2`]
+    Epd1in54V2,
+    /// [`epd2in13_v2`]
+    Epd2in13V2,
+    /// [`epd2in7`]
+    Epd2in7,
+    /// [`epd2in7_v2`]
+    Epd2in7V2,
+    /// [`epd2in9`]
+    Epd2in9,
+    /// [`epd2in9_v2`]
+    Epd2in9V2,
+    /// [`epd2in9d`]
+    Epd2in9d,
+    /// [`epd3in7`]
+    Epd3in7,
+    /// [`epd4in2`]
+    Epd4in2,
+    /// [`epd5in83_v2`]
+    Epd5in83V2,
+    /// [`epd7in5`]
+    Epd7in5,
+    /// [`epd7in5_hd`]
+    Epd7in5Hd,
+    /// [`epd7in5_v2`]
+    Epd7in5V2,
+}
+
+impl Model {
+    /// Every model
+    pub const ALL: [Model; 15] = [
+        Model::Epd1in02,
+        Model::Epd1in54,
+        Model::Epd1in54V2,
+        Model::Epd2in13V2,
+        Model::Epd2in7,
+        Model::Epd2in7V2,
+        Model::Epd2in9,
+        Model::Epd2in9V2,
+        Model::Epd2in9d,
+        Model::Epd3in7,
+        Model::Epd4in2,
+        Model::Epd5in83V2,
+        Model::Epd7in5,
+        Model::Epd7in5Hd,
+        Model::Epd7in5V2,
+    ];
+
+    /// Width of the display
+    pub const fn width(self) -> u32 {
+        match self {
+            Model::Epd1in02 => epd1in02::WIDTH,
+            Model::Epd1in54 => epd1in54::WIDTH,
+            Model::Epd1in54V2 => epd1in54_v2::WIDTH,
+            Model::Epd2in13V2 => epd2in13_v2::WIDTH,
+            Model::Epd2in7 => epd2in7::WIDTH,
+            Model::Epd2in7V2 => epd2in7_v2::WIDTH,
+            Model::Epd2in9 => epd2in9::WIDTH,
+            Model::Epd2in9V2 => epd2in9_v2::WIDTH,
+            Model::Epd2in9d => epd2in9d::WIDTH,
+            Model::Epd3in7 => epd3in7::WIDTH,
+            Model::Epd4in2 => epd4in2::WIDTH,
+            Model::Epd5in83V2 => epd5in83_v2::WIDTH,
+            Model::Epd7in5 => epd7in5::WIDTH,
+            Model::Epd7in5Hd => epd7in5_hd::WIDTH,
+            Model::Epd7in5V2 => epd7in5_v2::WIDTH,
+        }
+    }
+
+    /// Height of the display
+    pub const fn height(self) -> u32 {
+        match self {
+            Model::Epd1in02 => epd1in02::HEIGHT,
+            Model::Epd1in54 => epd1in54::HEIGHT,
+            Model::Epd1in54V2 => epd1in54_v2::HEIGHT,
+            Model::Epd2in13V2 => epd2in13_v2::HEIGHT,
+            Model::Epd2in7 => epd2in7::HEIGHT,
+            Model::Epd2in7V2 => epd2in7_v2::HEIGHT,
+            Model::Epd2in9 => epd2in9::HEIGHT,
+            Model::Epd2in9V2 => epd2in9_v2::HEIGHT,
+            Model::Epd2in9d => epd2in9d::HEIGHT,
+            Model::Epd3in7 => epd3in7::HEIGHT,
+            Model::Epd4in2 => epd4in2::HEIGHT,
+            Model::Epd5in83V2 => epd5in83_v2::HEIGHT,
+            Model::Epd7in5 => epd7in5::HEIGHT,
+            Model::Epd7in5Hd => epd7in5_hd::HEIGHT,
+            Model::Epd7in5V2 => epd7in5_v2::HEIGHT,
+        }
+    }
+
+    /// Length of a full frame buffer
+    pub const fn buffer_len(self) -> usize {
+        buffer_len(self.width() as usize, self.height() as usize)
+    }
+
+    /// Frame buffer of the display drawing into the first [`buffer_len`](Model::buffer_len)
+    /// bytes of `buffer`
+    #[cfg(feature = "graphics")]
+    pub fn display(self, buffer: &mut [u8]) -> Result<VarDisplay<'_, Color>, VarDisplayError> {
+        let buffer = buffer
+            .get_mut(..self.buffer_len())
+            .ok_or(VarDisplayError::BufferTooSmall)?;
+        VarDisplay::new(self.width(), self.height(), buffer, false)
+    }
+}
+
+/// Length of a buffer large enough for the frame of every [`Model`]
+pub const MAX_BUFFER_LEN: usize = {
+    let mut max = 0;
+    let mut i = 0;
+    while i < Model::ALL.len() {
+        let len = Model::ALL[i].buffer_len();
+        if len > max {
+            max = len;
+        }
+        i += 1;
+    }
+    max
+};
+
+/// Driver of one of the black and white displays, see [`Model`]
+///
+/// The variants are public, match on them to use what only a single driver offers, like
+/// [`QuickRefresh`](crate::prelude::QuickRefresh).
+#[allow(clippy::large_enum_variant)]
+pub enum AnyEpd<SPI, BUSY, DC, RST, DELAY, PWR = NoPowerPin> {
+    /// [`epd1in02::Epd1in02`]
+    Epd1in02(epd1in02::Epd1in02<SPI, BUSY, DC, RST, DELAY, PWR>),
+    /// [`epd1in54::Epd1in54`]
+    Epd1in54(epd1in54::Epd1in54<SPI, BUSY, DC, RST, DELAY, PWR>),
+    /// [`epd1in54_v2::Epd1in54`]
+    Epd1in54V2(epd1in54_v2::Epd1in54<SPI, BUSY, DC, RST, DELAY, PWR>),
+    /// [`epd2in13_v2::Epd2in13`]
+    Epd2in13V2(epd2in13_v2::Epd2in13<SPI, BUSY, DC, RST, DELAY, PWR>),
+    /// [`epd2in7::Epd2in7`]
+    Epd2in7(epd2in7::Epd2in7<SPI, BUSY, DC, RST, DELAY, PWR>),
+    /// [`epd2in7_v2::Epd2in7`]
+    Epd2in7V2(epd2in7_v2::Epd2in7<SPI, BUSY, DC, RST, DELAY, PWR>),
+    /// [`epd2in9::Epd2in9`]
+    Epd2in9(epd2in9::Epd2in9<SPI, BUSY, DC, RST, DELAY, PWR>),
+    /// [`epd2in9_v2::Epd2in9`]
+    Epd2in9V2(epd2in9_v2::Epd2in9<SPI, BUSY, DC, RST, DELAY, PWR>),
+    /// [`epd2in9d::Epd2in9d`]
+    Epd2in9d(epd2in9d::Epd2in9d<'static, SPI, BUSY, DC, RST, DELAY, PWR>),
+    /// [`epd3in7::EPD3in7`]
+    Epd3in7(epd3in7::EPD3in7<SPI, BUSY, DC, RST, DELAY, PWR>),
+    /// [`epd4in2::Epd4in2`]
+    Epd4in2(epd4in2::Epd4in2<SPI, BUSY, DC, RST, DELAY, PWR>),
+    /// [`epd5in83_v2::Epd5in83`]
+    Epd5in83V2(epd5in83_v2::Epd5in83<SPI, BUSY, DC, RST, DELAY, PWR>),
+    /// [`epd7in5::Epd7in5`]
+    Epd7in5(epd7in5::Epd7in5<SPI, BUSY, DC, RST, DELAY, PWR>),
+    /// [`epd7in5_hd::Epd7in5`]
+    Epd7in5Hd(epd7in5_hd::Epd7in5<SPI, BUSY, DC, RST, DELAY, PWR>),
+    /// [`epd7in5_v2::Epd7in5`]
+    Epd7in5V2(epd7in5_v2::Epd7in5<SPI, BUSY, DC, RST, DELAY, PWR>),
+}
+
+impl<SPI, BUSY, DC, RST, DELAY> AnyEpd<SPI, BUSY, DC, RST, DELAY>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+{
+    /// Creates and initialises the driver of `model`, see [`WaveshareDisplayNew::new`]
+    pub fn new(
+        model: Model,
+        spi: &mut SPI,
+        busy: BUSY,
+        dc: DC,
+        rst: RST,
+        delay: &mut DELAY,
+        delay_us: Option<u32>,
+    ) -> Result<Self, Error<SPI::Error>> {
+        let config = EpdConfig {
+            busy_poll_us: delay_us,
+            ..EpdConfig::new()
+        };
+        Self::with_config(model, spi, busy, dc, rst, delay, config)
+    }
+}
+
+impl<SPI, BUSY, DC, RST, DELAY, PWR> AnyEpd<SPI, BUSY, DC, RST, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    /// Creates and initialises the driver of `model` with the settings of `config`, see
+    /// [`WaveshareDisplayNew::with_config`]
+    ///
+    /// A power pin is given along the other settings, see [`EpdConfig::power_pin`].
+    pub fn with_config(
+        model: Model,
+        spi: &mut SPI,
+        busy: BUSY,
+        dc: DC,
+        rst: RST,
+        delay: &mut DELAY,
+        config: EpdConfig<PWR>,
+    ) -> Result<Self, Error<SPI::Error>> {
+        Ok(match model {
+            Model::Epd1in02 => AnyEpd::Epd1in02(WaveshareDisplayNew::with_config(
+                spi, busy, dc, rst, delay, config,
+            )?),
+            Model::Epd1in54 => AnyEpd::Epd1in54(WaveshareDisplayNew::with_config(
+                spi, busy, dc, rst, delay, config,
+            )?),
+            Model::Epd1in54V2 => AnyEpd::Epd1in54V2(WaveshareDisplayNew::with_config(
+                spi, busy, dc, rst, delay, config,
+            )?),
+            Model::Epd2in13V2 => AnyEpd::Epd2in13V2(WaveshareDisplayNew::with_config(
+                spi, busy, dc, rst, delay, config,
+            )?),
+            Model::Epd2in7 => AnyEpd::Epd2in7(WaveshareDisplayNew::with_config(
+                spi, busy, dc, rst, delay, config,
+            )?),
+            Model::Epd2in7V2 => AnyEpd::Epd2in7V2(WaveshareDisplayNew::with_config(
+                spi, busy, dc, rst, delay, config,
+            )?),
+            Model::Epd2in9 => AnyEpd::Epd2in9(WaveshareDisplayNew::with_config(
+                spi, busy, dc, rst, delay, config,
+            )?),
+            Model::Epd2in9V2 => AnyEpd::Epd2in9V2(WaveshareDisplayNew::with_config(
+                spi, busy, dc, rst, delay, config,
+            )?),
+            Model::Epd2in9d => AnyEpd::Epd2in9d(WaveshareDisplayNew::with_config(
+                spi, busy, dc, rst, delay, config,
+            )?),
+            Model::Epd3in7 => AnyEpd::Epd3in7(WaveshareDisplayNew::with_config(
+                spi, busy, dc, rst, delay, config,
+            )?),
+            Model::Epd4in2 => AnyEpd::Epd4in2(WaveshareDisplayNew::with_config(
+                spi, busy, dc, rst, delay, config,
+            )?),
+            Model::Epd5in83V2 => AnyEpd::Epd5in83V2(WaveshareDisplayNew::with_config(
+                spi, busy, dc, rst, delay, config,
+            )?),
+            Model::Epd7in5 => AnyEpd::Epd7in5(WaveshareDisplayNew::with_config(
+                spi, busy, dc, rst, delay, config,
+            )?),
+            Model::Epd7in5Hd => AnyEpd::Epd7in5Hd(WaveshareDisplayNew::with_config(
+                spi, busy, dc, rst, delay, config,
+            )?),
+            Model::Epd7in5V2 => AnyEpd::Epd7in5V2(WaveshareDisplayNew::with_config(
+                spi, busy, dc, rst, delay, config,
+            )?),
+        })
+    }
+
+    /// The display driven
+    pub fn model(&self) -> Model {
+        match self {
+            AnyEpd::Epd1in02(_) => Model::Epd1in02,
+            AnyEpd::Epd1in54(_) => Model::Epd1in54,
+            AnyEpd::Epd1in54V2(_) => Model::Epd1in54V2,
+            AnyEpd::Epd2in13V2(_) => Model::Epd2in13V2,
+            AnyEpd::Epd2in7(_) => Model::Epd2in7,
+            AnyEpd::Epd2in7V2(_) => Model::Epd2in7V2,
+            AnyEpd::Epd2in9(_) => Model::Epd2in9,
+            AnyEpd::Epd2in9V2(_) => Model::Epd2in9V2,
+            AnyEpd::Epd2in9d(_) => Model::Epd2in9d,
+            AnyEpd::Epd3in7(_) => Model::Epd3in7,
+            AnyEpd::Epd4in2(_) => Model::Epd4in2,
+            AnyEpd::Epd5in83V2(_) => Model::Epd5in83V2,
+            AnyEpd::Epd7in5(_) => Model::Epd7in5,
+            AnyEpd::Epd7in5Hd(_) => Model::Epd7in5Hd,
+            AnyEpd::Epd7in5V2(_) => Model::Epd7in5V2,
+        }
+    }
+}
+
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>
+    for AnyEpd<SPI, BUSY, DC, RST, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    type DisplayColor = Color;
+
+    /// What every model supports, [`capabilities`](WaveshareDisplay::capabilities) tells what
+    /// the model driven supports
+    const CAPABILITIES: Capabilities = Capabilities {
+        color: ColorKind::BlackWhite,
+        partial_update: false,
+        refresh_luts: &[RefreshLut::Full],
+        quick_refresh: false,
+        busy: BusyPolling::Command,
+    };
+
+    fn capabilities(&self) -> Capabilities {
+        dispatch!(self, epd => WaveshareDisplay::capabilities(epd))
+    }
+
+    fn deep_sleep_mode(&self) -> DeepSleepMode {
+        dispatch!(self, epd => WaveshareDisplay::deep_sleep_mode(epd))
+    }
+
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        dispatch!(self, epd => WaveshareDisplay::sleep(epd, spi, delay))
+    }
+
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        dispatch!(self, epd => WaveshareDisplay::wake_up(epd, spi, delay))
+    }
+
+    fn switch_power(&mut self, delay: &mut DELAY, on: bool) -> Result<(), Error<SPI::Error>> {
+        dispatch!(self, epd => WaveshareDisplay::switch_power(epd, delay, on))
+    }
+
+    fn set_background_color(&mut self, color: Color) {
+        dispatch!(self, epd => WaveshareDisplay::set_background_color(epd, color))
+    }
+
+    fn background_color(&self) -> &Color {
+        dispatch!(self, epd => WaveshareDisplay::background_color(epd))
+    }
+
+    fn set_busy_timeout(&mut self, timeout_us: Option<u32>) {
+        dispatch!(self, epd => WaveshareDisplay::set_busy_timeout(epd, timeout_us))
+    }
+
+    fn width(&self) -> u32 {
+        dispatch!(self, epd => WaveshareDisplay::width(epd))
+    }
+
+    fn height(&self) -> u32 {
+        dispatch!(self, epd => WaveshareDisplay::height(epd))
+    }
+
+    fn update_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        dispatch!(self, epd => WaveshareDisplay::update_frame(epd, spi, buffer, delay))
+    }
+
+    fn update_partial_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        dispatch!(self, epd => WaveshareDisplay::update_partial_frame(epd, spi, delay, buffer, x, y, width, height))
+    }
+
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        dispatch!(self, epd => WaveshareDisplay::display_frame(epd, spi, delay))
+    }
+
+    fn begin_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        dispatch!(self, epd => WaveshareDisplay::begin_display_frame(epd, spi, delay))
+    }
+
+    fn update_and_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        dispatch!(self, epd => WaveshareDisplay::update_and_display_frame(epd, spi, buffer, delay))
+    }
+
+    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        dispatch!(self, epd => WaveshareDisplay::clear_frame(epd, spi, delay))
+    }
+
+    fn set_lut(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        refresh_rate: Option<RefreshLut>,
+    ) -> Result<(), Error<SPI::Error>> {
+        dispatch!(self, epd => WaveshareDisplay::set_lut(epd, spi, delay, refresh_rate))
+    }
+
+    fn wait_until_idle(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        dispatch!(self, epd => WaveshareDisplay::wait_until_idle(epd, spi, delay))
+    }
+
+    fn is_busy(&mut self, spi: &mut SPI) -> Result<bool, Error<SPI::Error>> {
+        dispatch!(self, epd => WaveshareDisplay::is_busy(epd, spi))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffer_lengths() {
+        assert_eq!(Model::Epd2in9V2.buffer_len(), 128 / 8 * 296);
+        assert_eq!(MAX_BUFFER_LEN, Model::Epd7in5Hd.buffer_len());
+        assert!(Model::ALL
+            .iter()
+            .all(|model| model.buffer_len() <= MAX_BUFFER_LEN));
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn display() {
+        let mut buffer = [0u8; MAX_BUFFER_LEN];
+        let display = Model::Epd4in2.display(&mut buffer).unwrap();
+        assert_eq!(display.buffer().len(), Model::Epd4in2.buffer_len());
+        assert!(Model::Epd7in5V2.display(&mut buffer[..100]).is_err());
+    }
+}
@@ -8,6 +8,11 @@ use embedded_graphics_core::pixelcolor::BinaryColor;
 #[cfg(feature = "graphics")]
 use embedded_graphics_core::pixelcolor::PixelColor;
 
+mod palette;
+#[cfg(feature = "graphics")]
+pub(crate) use self::palette::{closest_entry, IDEAL_ENTRIES};
+pub use self::palette::{Lab, Palette, PaletteEntry};
+
 /// When trying to parse u8 to one of the color types
 #[derive(Debug, PartialEq, Eq)]
 pub struct OutOfColorRangeParseError(u8);
@@ -68,6 +73,23 @@ pub enum OctColor {
     HiZ = 0x07,
 }
 
+/// For the displays supporting 4 gray levels
+///
+/// The discriminant is the gray level as expected by the display, from black (`0b00`) to
+/// white (`0b11`).
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Gray2 {
+    /// Black color
+    Black = 0b00,
+    /// Dark gray color
+    DarkGray = 0b01,
+    /// Light gray color
+    LightGray = 0b10,
+    /// White color
+    #[default]
+    White = 0b11,
+}
+
 /// Color trait for use in `Display`s
 pub trait ColorType {
     /// Number of bit used to represent this color type in a single buffer.
@@ -87,7 +109,7 @@ pub trait ColorType {
     /// Return values are :
     /// * .0 is the mask used to exclude this pixel from the byte (eg: 0x7F in BiColor)
     /// * .1 are the bits used to set the color in the byte (eg: 0x80 in BiColor)
-    ///      this is u16 because we set 2 bytes in case of split buffer
+    ///   this is u16 because we set 2 bytes in case of split buffer
     fn bitmask(&self, bwrbit: bool, pos: u32) -> (u8, u16);
 }
 
@@ -133,6 +155,24 @@ impl ColorType for OctColor {
     }
 }
 
+/// The gray level is split over two buffers, like the two RAMs of the display:
+/// the first one holds the high bit of every pixel and the second one the low bit.
+impl ColorType for Gray2 {
+    const BITS_PER_PIXEL_PER_BUFFER: usize = 1;
+    const BUFFER_COUNT: usize = 2;
+    fn bitmask(&self, _bwrbit: bool, pos: u32) -> (u8, u16) {
+        let bit = 0x80 >> (pos % 8);
+        let level = self.get_level();
+        let high = if level & 0b10 != 0 { bit as u16 } else { 0 };
+        let low = if level & 0b01 != 0 {
+            (bit as u16) << 8
+        } else {
+            0
+        };
+        (!bit, high | low)
+    }
+}
+
 #[cfg(feature = "graphics")]
 impl From<BinaryColor> for OctColor {
     fn from(b: BinaryColor) -> OctColor {
@@ -155,34 +195,13 @@ impl From<OctColor> for embedded_graphics_core::pixelcolor::Rgb888 {
 impl From<embedded_graphics_core::pixelcolor::Rgb888> for OctColor {
     fn from(p: embedded_graphics_core::pixelcolor::Rgb888) -> OctColor {
         use embedded_graphics_core::prelude::RgbColor;
-        let colors = [
-            OctColor::Black,
-            OctColor::White,
-            OctColor::Green,
-            OctColor::Blue,
-            OctColor::Red,
-            OctColor::Yellow,
-            OctColor::Orange,
-            OctColor::HiZ,
-        ];
-        // if the user has already mapped to the right color space, it will just be in the list
-        if let Some(found) = colors.iter().find(|c| c.rgb() == (p.r(), p.g(), p.b())) {
-            return *found;
+        let rgb = (p.r(), p.g(), p.b());
+        // HiZ can't be displayed, so it's only picked if the user asked for it explicitly
+        if rgb == OctColor::HiZ.rgb() {
+            return OctColor::HiZ;
         }
 
-        // This is not ideal but just pick the nearest color
-        *colors
-            .iter()
-            .map(|c| (c, c.rgb()))
-            .map(|(c, (r, g, b))| {
-                let dist = (i32::from(r) - i32::from(p.r())).pow(2)
-                    + (i32::from(g) - i32::from(p.g())).pow(2)
-                    + (i32::from(b) - i32::from(p.b())).pow(2);
-                (c, dist)
-            })
-            .min_by_key(|(_c, dist)| *dist)
-            .map(|(c, _)| c)
-            .unwrap_or(&OctColor::White)
+        Palette::IDEAL.closest(rgb)
     }
 }
 
@@ -488,6 +507,86 @@ impl From<TriColor> for embedded_graphics_core::pixelcolor::Rgb888 {
     }
 }
 
+impl Gray2 {
+    /// Gets the gray level of the color, from 0 (black) to 3 (white)
+    pub fn get_level(self) -> u8 {
+        self as u8
+    }
+
+    /// Takes the lower 2 bits as gray level
+    pub fn from_level(level: u8) -> Gray2 {
+        match level & 0b11 {
+            0b00 => Gray2::Black,
+            0b01 => Gray2::DarkGray,
+            0b10 => Gray2::LightGray,
+            _ => Gray2::White,
+        }
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl From<embedded_graphics_core::pixelcolor::raw::RawU2> for Gray2 {
+    fn from(b: embedded_graphics_core::pixelcolor::raw::RawU2) -> Self {
+        use embedded_graphics_core::prelude::RawData;
+        Gray2::from_level(b.into_inner())
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl From<Gray2> for embedded_graphics_core::pixelcolor::raw::RawU2 {
+    fn from(color: Gray2) -> Self {
+        Self::new(color.get_level())
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl PixelColor for Gray2 {
+    type Raw = embedded_graphics_core::pixelcolor::raw::RawU2;
+}
+
+#[cfg(feature = "graphics")]
+impl From<BinaryColor> for Gray2 {
+    fn from(b: BinaryColor) -> Gray2 {
+        match b {
+            BinaryColor::On => Gray2::Black,
+            BinaryColor::Off => Gray2::White,
+        }
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl From<embedded_graphics_core::pixelcolor::Gray2> for Gray2 {
+    fn from(gray: embedded_graphics_core::pixelcolor::Gray2) -> Self {
+        use embedded_graphics_core::pixelcolor::GrayColor;
+        Gray2::from_level(gray.luma())
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl From<Gray2> for embedded_graphics_core::pixelcolor::Gray2 {
+    fn from(gray: Gray2) -> Self {
+        Self::new(gray.get_level())
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl From<embedded_graphics_core::pixelcolor::Rgb888> for Gray2 {
+    fn from(rgb: embedded_graphics_core::pixelcolor::Rgb888) -> Self {
+        use embedded_graphics_core::pixelcolor::RgbColor;
+        // ITU-R BT.601 luma, scaled down to the 4 available levels
+        let luma = (rgb.r() as u32 * 299 + rgb.g() as u32 * 587 + rgb.b() as u32 * 114) / 1000;
+        Gray2::from_level((luma >> 6) as u8)
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl From<Gray2> for embedded_graphics_core::pixelcolor::Rgb888 {
+    fn from(gray: Gray2) -> Self {
+        let luma = gray.get_level() * 0x55;
+        embedded_graphics_core::pixelcolor::Rgb888::new(luma, luma, luma)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -558,4 +657,21 @@ mod tests {
             (0b01111111, u16::from_le_bytes([0b00000000, 0b10000000]))
         );
     }
+
+    #[test]
+    fn gray2_levels() {
+        for level in 0..4 {
+            assert_eq!(Gray2::from_level(level).get_level(), level);
+        }
+        assert_eq!(Gray2::default(), Gray2::White);
+    }
+
+    #[test]
+    fn gray2_bitmask() {
+        // high bit in the first buffer, low bit in the second one
+        assert_eq!(Gray2::Black.bitmask(false, 0), (0x7F, 0x0000));
+        assert_eq!(Gray2::DarkGray.bitmask(false, 1), (0xBF, 0x4000));
+        assert_eq!(Gray2::LightGray.bitmask(false, 2), (0xDF, 0x0020));
+        assert_eq!(Gray2::White.bitmask(false, 7), (0xFE, 0x0101));
+    }
 }
@@ -0,0 +1,414 @@
+//! Calibrated palettes for color matching
+//!
+//! The pigments of the color displays don't look like the idealised colors returned by e.g.
+//! [`OctColor::rgb`]. A [`Palette`] holds the colors as they actually look on a panel, and
+//! picks the closest one in the CIE Lab color space, which matches the perception of the
+//! colors much better than the distance in RGB.
+
+use super::OctColor;
+
+/// Color in the CIE L\*a\*b\* color space (D65 white point)
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Lab {
+    /// Lightness, 0 to 100
+    pub l: f32,
+    /// Green (negative) to red (positive)
+    pub a: f32,
+    /// Blue (negative) to yellow (positive)
+    pub b: f32,
+}
+
+impl Lab {
+    /// Creates a color from its Lab components
+    pub const fn new(l: f32, a: f32, b: f32) -> Self {
+        Lab { l, a, b }
+    }
+
+    /// Converts a sRGB color
+    ///
+    /// Uses lookup tables and integer math up to the final scaling, this is called for every
+    /// pixel by [`Palette::closest`] and the dithering.
+    pub fn from_rgb((r, g, b): (u8, u8, u8)) -> Self {
+        let rgb = [linear(r), linear(g), linear(b)];
+
+        // sRGB to XYZ normalized to the white point, the rows sum up to 1 << 16
+        let xyz =
+            |row: [u32; 3]| (row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2] + (1 << 15)) >> 16;
+        let fx = lab_f(xyz([28435, 24656, 12445]));
+        let fy = lab_f(xyz([13933, 46871, 4732]));
+        let fz = lab_f(xyz([1162, 7173, 57201]));
+
+        const SCALE: f32 = 1.0 / u16::MAX as f32;
+        Lab {
+            l: 116.0 * SCALE * fy as f32 - 16.0,
+            a: 500.0 * SCALE * (fx - fy) as f32,
+            b: 200.0 * SCALE * (fy - fz) as f32,
+        }
+    }
+
+    /// Squared euclidean distance (CIE76 ΔE²) to another color
+    pub fn distance_squared(&self, other: &Lab) -> f32 {
+        let l = self.l - other.l;
+        let a = self.a - other.a;
+        let b = self.b - other.b;
+        l * l + a * a + b * b
+    }
+}
+
+/// A color of a display together with how it looks on the panel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PaletteEntry<C> {
+    /// Color of the display
+    pub color: C,
+    /// sRGB value of the color on the panel
+    pub rgb: (u8, u8, u8),
+    /// Lab value of the color on the panel, used to find the closest color
+    pub lab: Lab,
+}
+
+impl<C> PaletteEntry<C> {
+    /// Creates an entry from measured RGB and Lab values
+    pub const fn new(color: C, rgb: (u8, u8, u8), lab: Lab) -> Self {
+        PaletteEntry { color, rgb, lab }
+    }
+
+    /// Creates an entry from a measured RGB value, the Lab value is computed from it
+    pub fn from_rgb(color: C, rgb: (u8, u8, u8)) -> Self {
+        PaletteEntry {
+            color,
+            rgb,
+            lab: Lab::from_rgb(rgb),
+        }
+    }
+}
+
+/// Finds the entry closest to the given color, the entries being split into the first one
+/// and the rest so that there always is one
+pub(crate) fn closest_entry<'a, C>(
+    first: &'a PaletteEntry<C>,
+    rest: &'a [PaletteEntry<C>],
+    lab: &Lab,
+) -> &'a PaletteEntry<C> {
+    let mut best = first;
+    let mut best_distance = best.lab.distance_squared(lab);
+    for entry in rest {
+        let distance = entry.lab.distance_squared(lab);
+        if distance < best_distance {
+            best = entry;
+            best_distance = distance;
+        }
+    }
+    best
+}
+
+/// Entries of [`Palette::IDEAL`]
+pub(crate) const IDEAL_ENTRIES: [PaletteEntry<OctColor>; 7] = [
+    PaletteEntry::new(
+        OctColor::Black,
+        (0x00, 0x00, 0x00),
+        Lab::new(0.00, 0.00, 0.00),
+    ),
+    PaletteEntry::new(
+        OctColor::White,
+        (0xff, 0xff, 0xff),
+        Lab::new(100.00, 0.00, 0.00),
+    ),
+    PaletteEntry::new(
+        OctColor::Green,
+        (0x00, 0xff, 0x00),
+        Lab::new(87.74, -86.19, 83.19),
+    ),
+    PaletteEntry::new(
+        OctColor::Blue,
+        (0x00, 0x00, 0xff),
+        Lab::new(32.30, 79.19, -107.85),
+    ),
+    PaletteEntry::new(
+        OctColor::Red,
+        (0xff, 0x00, 0x00),
+        Lab::new(53.23, 80.11, 67.22),
+    ),
+    PaletteEntry::new(
+        OctColor::Yellow,
+        (0xff, 0xff, 0x00),
+        Lab::new(97.14, -21.56, 94.49),
+    ),
+    PaletteEntry::new(
+        OctColor::Orange,
+        (0xff, 0x80, 0x00),
+        Lab::new(67.05, 42.83, 74.03),
+    ),
+];
+
+/// Calibrated colors of a 7 color display
+///
+/// Every [`OctColor`] except [`OctColor::HiZ`] has exactly one entry, in the order of their
+/// nibble. [`epd5in65f::PALETTE`](crate::epd5in65f::PALETTE) and
+/// [`epd7in3f::PALETTE`](crate::epd7in3f::PALETTE) are approximations of the panels, the colors
+/// depend on the lighting and the individual panel, so you can load your own calibration:
+///
+/// ```rust
+/// # use epd_waveshare::color::{OctColor, Palette};
+/// // measured colors in the order of the nibbles: black, white, green, blue, red, yellow, orange
+/// let palette = Palette::from_rgb([
+///     (0x28, 0x24, 0x2c),
+///     (0xe0, 0xe0, 0xd8),
+///     (0x30, 0x66, 0x49),
+///     (0x2f, 0x3a, 0x74),
+///     (0xac, 0x36, 0x34),
+///     (0xde, 0xc9, 0x3a),
+///     (0xc4, 0x6c, 0x3a),
+/// ]);
+/// assert_eq!(palette.closest((0xa0, 0x60, 0x40)), OctColor::Orange);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    entries: [PaletteEntry<OctColor>; 7],
+}
+
+impl Palette {
+    /// Idealised colors, the same as [`OctColor::rgb`]
+    pub const IDEAL: Palette = Palette {
+        entries: IDEAL_ENTRIES,
+    };
+
+    /// Creates a palette from measured RGB and Lab values
+    ///
+    /// The entries are given in the order of their nibble, like for
+    /// [`from_rgb`](Self::from_rgb). Returns `None` if a color is missing or out of order.
+    pub const fn from_entries(entries: [PaletteEntry<OctColor>; 7]) -> Option<Self> {
+        let mut i = 0;
+        while i < entries.len() {
+            if entries[i].color as usize != i {
+                return None;
+            }
+            i += 1;
+        }
+        Some(Palette { entries })
+    }
+
+    /// Creates a palette from measured RGB values
+    ///
+    /// The colors are given in the order of their nibble: black, white, green, blue, red,
+    /// yellow and orange.
+    pub fn from_rgb(rgb: [(u8, u8, u8); 7]) -> Self {
+        let mut entries = IDEAL_ENTRIES;
+        for (entry, rgb) in entries.iter_mut().zip(rgb) {
+            *entry = PaletteEntry::from_rgb(entry.color, rgb);
+        }
+        Palette { entries }
+    }
+
+    /// All entries of the palette
+    pub fn entries(&self) -> &[PaletteEntry<OctColor>] {
+        &self.entries
+    }
+
+    /// The entry of a color, `None` for [`OctColor::HiZ`]
+    pub fn entry(&self, color: OctColor) -> Option<&PaletteEntry<OctColor>> {
+        self.entries.iter().find(|entry| entry.color == color)
+    }
+
+    /// Finds the display color which looks closest to a sRGB color
+    pub fn closest(&self, rgb: (u8, u8, u8)) -> OctColor {
+        let [first, rest @ ..] = &self.entries;
+        closest_entry(first, rest, &Lab::from_rgb(rgb)).color
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::IDEAL
+    }
+}
+
+/// sRGB to linear light, scaled to u16::MAX
+#[rustfmt::skip]
+const SRGB_TO_LINEAR: [u16; 256] = [
+    0, 20, 40, 60, 80, 99, 119, 139, 159, 179, 199, 219,
+    241, 264, 288, 313, 340, 367, 396, 427, 458, 491, 526, 562,
+    599, 637, 677, 718, 761, 805, 851, 898, 947, 997, 1048, 1101,
+    1156, 1212, 1270, 1330, 1391, 1453, 1517, 1583, 1651, 1720, 1790, 1863,
+    1937, 2013, 2090, 2170, 2250, 2333, 2418, 2504, 2592, 2681, 2773, 2866,
+    2961, 3058, 3157, 3258, 3360, 3464, 3570, 3678, 3788, 3900, 4014, 4129,
+    4247, 4366, 4488, 4611, 4736, 4864, 4993, 5124, 5257, 5392, 5530, 5669,
+    5810, 5953, 6099, 6246, 6395, 6547, 6700, 6856, 7014, 7174, 7335, 7500,
+    7666, 7834, 8004, 8177, 8352, 8528, 8708, 8889, 9072, 9258, 9445, 9635,
+    9828, 10022, 10219, 10417, 10619, 10822, 11028, 11235, 11446, 11658, 11873, 12090,
+    12309, 12530, 12754, 12980, 13209, 13440, 13673, 13909, 14146, 14387, 14629, 14874,
+    15122, 15371, 15623, 15878, 16135, 16394, 16656, 16920, 17187, 17456, 17727, 18001,
+    18277, 18556, 18837, 19121, 19407, 19696, 19987, 20281, 20577, 20876, 21177, 21481,
+    21787, 22096, 22407, 22721, 23038, 23357, 23678, 24002, 24329, 24658, 24990, 25325,
+    25662, 26001, 26344, 26688, 27036, 27386, 27739, 28094, 28452, 28813, 29176, 29542,
+    29911, 30282, 30656, 31033, 31412, 31794, 32179, 32567, 32957, 33350, 33745, 34143,
+    34544, 34948, 35355, 35764, 36176, 36591, 37008, 37429, 37852, 38278, 38706, 39138,
+    39572, 40009, 40449, 40891, 41337, 41785, 42236, 42690, 43147, 43606, 44069, 44534,
+    45002, 45473, 45947, 46423, 46903, 47385, 47871, 48359, 48850, 49344, 49841, 50341,
+    50844, 51349, 51858, 52369, 52884, 53401, 53921, 54445, 54971, 55500, 56032, 56567,
+    57105, 57646, 58190, 58737, 59287, 59840, 60396, 60955, 61517, 62082, 62650, 63221,
+    63795, 64372, 64952, 65535,
+];
+
+fn linear(channel: u8) -> u32 {
+    u32::from(SRGB_TO_LINEAR[usize::from(channel)])
+}
+
+/// `lab_f` of `t / u16::MAX` for `t` in steps of 16 up to 4096, scaled to u16::MAX
+#[rustfmt::skip]
+const LAB_F_LOW: [u16; 257] = [
+    9039, 9164, 9288, 9413, 9538, 9662, 9787, 9911, 10036, 10161, 10285, 10410,
+    10534, 10659, 10784, 10908, 11033, 11157, 11282, 11407, 11531, 11656, 11780, 11905,
+    12030, 12154, 12279, 12403, 12528, 12652, 12777, 12902, 13026, 13151, 13275, 13400,
+    13525, 13649, 13771, 13890, 14008, 14124, 14238, 14350, 14460, 14569, 14676, 14782,
+    14886, 14988, 15090, 15190, 15288, 15386, 15482, 15577, 15671, 15763, 15855, 15946,
+    16035, 16124, 16211, 16298, 16384, 16469, 16553, 16636, 16718, 16800, 16881, 16961,
+    17040, 17118, 17196, 17273, 17350, 17426, 17501, 17575, 17649, 17722, 17795, 17867,
+    17938, 18009, 18080, 18149, 18219, 18287, 18356, 18423, 18491, 18557, 18624, 18689,
+    18755, 18820, 18884, 18948, 19012, 19075, 19138, 19200, 19262, 19323, 19385, 19445,
+    19506, 19566, 19625, 19685, 19744, 19802, 19861, 19918, 19976, 20033, 20090, 20147,
+    20203, 20259, 20315, 20370, 20425, 20480, 20534, 20588, 20642, 20696, 20749, 20802,
+    20855, 20908, 20960, 21012, 21064, 21115, 21166, 21217, 21268, 21319, 21369, 21419,
+    21469, 21518, 21568, 21617, 21666, 21715, 21763, 21811, 21859, 21907, 21955, 22002,
+    22049, 22096, 22143, 22190, 22236, 22283, 22329, 22374, 22420, 22466, 22511, 22556,
+    22601, 22646, 22690, 22735, 22779, 22823, 22867, 22911, 22954, 22997, 23041, 23084,
+    23127, 23169, 23212, 23254, 23297, 23339, 23381, 23423, 23464, 23506, 23547, 23588,
+    23630, 23671, 23711, 23752, 23793, 23833, 23873, 23913, 23953, 23993, 24033, 24072,
+    24112, 24151, 24190, 24230, 24269, 24307, 24346, 24385, 24423, 24461, 24500, 24538,
+    24576, 24614, 24651, 24689, 24727, 24764, 24801, 24838, 24875, 24912, 24949, 24986,
+    25023, 25059, 25096, 25132, 25168, 25204, 25240, 25276, 25312, 25348, 25383, 25419,
+    25454, 25489, 25525, 25560, 25595, 25630, 25665, 25699, 25734, 25768, 25803, 25837,
+    25872, 25906, 25940, 25974, 26008,
+];
+
+/// `lab_f` of `t / u16::MAX` for `t` in steps of 256, scaled to u16::MAX
+#[rustfmt::skip]
+const LAB_F: [u16; 257] = [
+    9039, 11033, 13026, 14886, 16384, 17649, 18755, 19744, 20642, 21469, 22236, 22954,
+    23630, 24269, 24875, 25454, 26008, 26539, 27049, 27541, 28016, 28475, 28920, 29352,
+    29771, 30179, 30576, 30964, 31341, 31710, 32070, 32423, 32768, 33106, 33437, 33761,
+    34080, 34392, 34700, 35001, 35298, 35590, 35877, 36159, 36437, 36711, 36981, 37247,
+    37510, 37768, 38024, 38275, 38524, 38769, 39012, 39251, 39487, 39721, 39952, 40180,
+    40406, 40629, 40850, 41069, 41285, 41499, 41710, 41920, 42127, 42333, 42536, 42738,
+    42938, 43136, 43332, 43526, 43719, 43910, 44099, 44286, 44473, 44657, 44840, 45022,
+    45202, 45380, 45558, 45734, 45908, 46081, 46253, 46424, 46593, 46762, 46929, 47094,
+    47259, 47423, 47585, 47746, 47907, 48066, 48224, 48381, 48537, 48692, 48846, 48999,
+    49151, 49303, 49453, 49602, 49751, 49899, 50045, 50191, 50336, 50481, 50624, 50767,
+    50908, 51049, 51190, 51329, 51468, 51606, 51743, 51880, 52015, 52151, 52285, 52419,
+    52552, 52684, 52816, 52947, 53077, 53207, 53336, 53465, 53593, 53720, 53847, 53973,
+    54098, 54223, 54348, 54471, 54595, 54717, 54839, 54961, 55082, 55203, 55323, 55442,
+    55561, 55680, 55797, 55915, 56032, 56148, 56264, 56380, 56495, 56610, 56724, 56837,
+    56951, 57063, 57176, 57288, 57399, 57510, 57621, 57731, 57841, 57950, 58059, 58167,
+    58276, 58383, 58491, 58598, 58704, 58810, 58916, 59021, 59126, 59231, 59335, 59439,
+    59543, 59646, 59749, 59851, 59953, 60055, 60157, 60258, 60359, 60459, 60559, 60659,
+    60758, 60857, 60956, 61055, 61153, 61251, 61348, 61445, 61542, 61639, 61735, 61831,
+    61927, 62022, 62118, 62212, 62307, 62401, 62495, 62589, 62682, 62775, 62868, 62961,
+    63053, 63145, 63237, 63329, 63420, 63511, 63602, 63692, 63782, 63872, 63962, 64051,
+    64141, 64229, 64318, 64407, 64495, 64583, 64671, 64758, 64845, 64932, 65019, 65106,
+    65192, 65278, 65364, 65450, 65535,
+];
+
+/// `lab_f` of CIE Lab for `t / u16::MAX`, scaled to u16::MAX and interpolated from the tables
+fn lab_f(t: u32) -> i32 {
+    let (table, step) = if t < 4096 {
+        (&LAB_F_LOW, 16)
+    } else {
+        (&LAB_F, 256)
+    };
+    let (index, fraction) = ((t / step) as usize, (t % step) as i32);
+    let (low, high) = (i32::from(table[index]), i32::from(table[index + 1]));
+    low + (high - low) * fraction / step as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_lab_eq(left: Lab, right: Lab) {
+        assert!(
+            left.distance_squared(&right) < 0.01,
+            "{:?} != {:?}",
+            left,
+            right
+        );
+    }
+
+    #[test]
+    fn lab_values_match_rgb() {
+        let palettes = [
+            Palette::IDEAL,
+            crate::epd5in65f::PALETTE,
+            crate::epd7in3f::PALETTE,
+        ];
+        for palette in palettes {
+            for entry in palette.entries() {
+                assert_lab_eq(Lab::from_rgb(entry.rgb), entry.lab);
+            }
+        }
+    }
+
+    #[test]
+    fn ideal_colors_match_themselves() {
+        for entry in Palette::IDEAL.entries() {
+            assert_eq!(entry.rgb, entry.color.rgb());
+            assert_eq!(Palette::IDEAL.closest(entry.rgb), entry.color);
+        }
+        assert_eq!(Palette::IDEAL.entry(OctColor::HiZ), None);
+    }
+
+    #[test]
+    fn entries_in_nibble_order() {
+        let mut entries = IDEAL_ENTRIES;
+        assert_eq!(Palette::from_entries(entries), Some(Palette::IDEAL));
+        entries[2].color = OctColor::White;
+        assert_eq!(Palette::from_entries(entries), None);
+        entries.swap(1, 2);
+        entries[1].color = OctColor::White;
+        entries[2].color = OctColor::HiZ;
+        assert_eq!(Palette::from_entries(entries), None);
+    }
+
+    #[test]
+    fn calibrated_closest() {
+        // foliage, which is closest to black in idealised RGB
+        let foliage = (0x40, 0x60, 0x40);
+        assert_eq!(crate::epd5in65f::PALETTE.closest(foliage), OctColor::Green);
+    }
+
+    #[test]
+    fn lookup_tables_match_the_formula() {
+        extern crate std;
+        let reference = |(r, g, b): (u8, u8, u8)| {
+            let linear = |c: u8| {
+                let c = f64::from(c) / 255.0;
+                if c <= 0.04045 {
+                    c / 12.92
+                } else {
+                    ((c + 0.055) / 1.055).powf(2.4)
+                }
+            };
+            let (r, g, b) = (linear(r), linear(g), linear(b));
+            let f = |t: f64| {
+                if t > 0.008856 {
+                    t.cbrt()
+                } else {
+                    7.787 * t + 16.0 / 116.0
+                }
+            };
+            let fx = f((0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.9505);
+            let fy = f(0.2126 * r + 0.7152 * g + 0.0722 * b);
+            let fz = f((0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.089);
+            Lab::new(
+                (116.0 * fy - 16.0) as f32,
+                (500.0 * (fx - fy)) as f32,
+                (200.0 * (fy - fz)) as f32,
+            )
+        };
+        for r in (0..=255).step_by(5) {
+            for g in (0..=255).step_by(5) {
+                for b in (0..=255).step_by(5) {
+                    assert_lab_eq(Lab::from_rgb((r, g, b)), reference((r, g, b)));
+                }
+            }
+        }
+    }
+}
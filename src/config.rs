@@ -0,0 +1,201 @@
+//! Timings and settings of the connection to a display
+//!
+//! [`EpdConfig`] is passed to [`with_config`](crate::prelude::WaveshareDisplayNew::with_config) of
+//! every driver, e.g. to slow down the reset and busy handling for slow level shifters or long
+//! cables, or to hand over the pin switching the power rail of the panel. Settings left out keep
+//! the defaults of the driver.
+//!
+//!```rust, no_run
+//!# use embedded_hal_mock::eh1::*;
+//!# fn main() -> Result<(), epd_waveshare::Error<embedded_hal::spi::ErrorKind>> {
+//!use epd_waveshare::{config::EpdConfig, epd4in2::*, prelude::*};
+//!#
+//!# let mut spi = spi::Mock::new(&[]);
+//!# let pin = || digital::Mock::new(&[]);
+//!# let (busy, dc, rst) = (pin(), pin(), pin());
+//!# let mut delay = delay::NoopDelay::new();
+//!
+//!let config = EpdConfig::new()
+//!    .reset_pulse_us(20_000)
+//!    .reset_settle_us(300_000)
+//!    .busy_poll_us(5_000)
+//!    .busy_timeout_us(10_000_000);
+//!let mut epd = Epd4in2::with_config(&mut spi, busy, dc, rst, &mut delay, config)?;
+//!# Ok(())
+//!# }
+//!```
+
+use crate::color::{Color, TriColor};
+use crate::error::Error;
+use crate::power::NoPowerPin;
+
+/// Maximum number of bytes per SPI transfer when none is configured: 4096 on Linux, no limit
+/// (`0`) on the other targets
+///
+/// spidev refuses larger transfers with its default `bufsiz`, see
+/// <https://raspberrypi.stackexchange.com/questions/65595/spi-transfer-fails-with-buffer-size-greater-than-4096>
+pub const DEFAULT_SPI_CHUNK_SIZE: usize = if cfg!(target_os = "linux") { 4096 } else { 0 };
+
+/// Time the panel takes to settle after its power rail is switched on when none is configured,
+/// in us
+pub const DEFAULT_POWER_ON_DELAY_US: u32 = 10_000;
+
+/// Settings of a driver, built with the setters starting from [`EpdConfig::new`]
+///
+/// `PWR` is the type of the [power pin](EpdConfig::power_pin), [`NoPowerPin`] without one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EpdConfig<PWR = NoPowerPin> {
+    pub(crate) reset_pulse_us: Option<u32>,
+    pub(crate) reset_settle_us: Option<u32>,
+    pub(crate) busy_poll_us: Option<u32>,
+    pub(crate) busy_timeout_us: Option<u32>,
+    pub(crate) spi_chunk_size: Option<usize>,
+    pub(crate) border: Option<TriColor>,
+    pub(crate) power: Option<PWR>,
+    pub(crate) power_on_delay_us: Option<u32>,
+}
+
+impl EpdConfig {
+    /// Keeps all the defaults of the driver
+    pub const fn new() -> Self {
+        EpdConfig {
+            reset_pulse_us: None,
+            reset_settle_us: None,
+            busy_poll_us: None,
+            busy_timeout_us: None,
+            spi_chunk_size: None,
+            border: None,
+            power: None,
+            power_on_delay_us: None,
+        }
+    }
+}
+
+impl<PWR> EpdConfig<PWR> {
+    /// Time the reset pin is held low, in us
+    pub const fn reset_pulse_us(mut self, us: u32) -> Self {
+        self.reset_pulse_us = Some(us);
+        self
+    }
+
+    /// Time to wait after releasing the reset pin, in us (200ms by default)
+    pub const fn reset_settle_us(mut self, us: u32) -> Self {
+        self.reset_settle_us = Some(us);
+        self
+    }
+
+    /// Time to sleep between two probes of the busy pin, in us
+    ///
+    /// `0` busy waits. Same as the `delay_us` of [`new`](crate::prelude::WaveshareDisplayNew::new).
+    pub const fn busy_poll_us(mut self, us: u32) -> Self {
+        self.busy_poll_us = Some(us);
+        self
+    }
+
+    /// Maximum time to wait for the display to be idle, in us, see
+    /// [`set_busy_timeout`](crate::prelude::WaveshareDisplay::set_busy_timeout)
+    pub const fn busy_timeout_us(mut self, us: u32) -> Self {
+        self.busy_timeout_us = Some(us);
+        self
+    }
+
+    /// Maximum number of bytes sent in one SPI transfer, [`DEFAULT_SPI_CHUNK_SIZE`] by default
+    ///
+    /// `0` sends every block in one transfer, e.g. on Linux with a raised spidev `bufsiz`.
+    pub const fn spi_chunk_size(mut self, bytes: usize) -> Self {
+        self.spi_chunk_size = Some(bytes);
+        self
+    }
+
+    /// Color of the border around the active area
+    ///
+    /// The drivers of black and white displays return [`Error::Unsupported`] for
+    /// [`TriColor::Chromatic`], the 7 color displays show it red. Without it the drivers keep
+    /// their default border.
+    pub const fn border_color(mut self, color: TriColor) -> Self {
+        self.border = Some(color);
+        self
+    }
+
+    /// Pin switching the power rail of the panel, high when the panel is powered, e.g. the gate
+    /// of a load switch
+    ///
+    /// The driver switches the panel on before its first reset, and
+    /// [`power_off`](crate::prelude::WaveshareDisplay::power_off) and
+    /// [`power_on`](crate::prelude::WaveshareDisplay::power_on) switch it afterwards.
+    pub fn power_pin<P>(self, pin: P) -> EpdConfig<P> {
+        EpdConfig {
+            reset_pulse_us: self.reset_pulse_us,
+            reset_settle_us: self.reset_settle_us,
+            busy_poll_us: self.busy_poll_us,
+            busy_timeout_us: self.busy_timeout_us,
+            spi_chunk_size: self.spi_chunk_size,
+            border: self.border,
+            power: Some(pin),
+            power_on_delay_us: self.power_on_delay_us,
+        }
+    }
+
+    /// Time the panel takes to settle after its power rail is switched on, in us,
+    /// [`DEFAULT_POWER_ON_DELAY_US`] by default
+    pub const fn power_on_delay_us(mut self, us: u32) -> Self {
+        self.power_on_delay_us = Some(us);
+        self
+    }
+
+    /// The border color, `None` keeps the default of the driver
+    pub fn border(&self) -> Option<TriColor> {
+        self.border
+    }
+
+    /// The border color for the drivers of black and white displays, rejects
+    /// [`TriColor::Chromatic`]
+    pub(crate) fn bw_border<E>(&self) -> Result<Option<Color>, Error<E>> {
+        match self.border {
+            Some(TriColor::Black) => Ok(Some(Color::Black)),
+            Some(TriColor::White) => Ok(Some(Color::White)),
+            Some(TriColor::Chromatic) => Err(Error::Unsupported),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder() {
+        let config = EpdConfig::new()
+            .reset_pulse_us(1)
+            .reset_settle_us(2)
+            .busy_poll_us(3)
+            .busy_timeout_us(4)
+            .spi_chunk_size(5);
+        assert_eq!(config.reset_pulse_us, Some(1));
+        assert_eq!(config.reset_settle_us, Some(2));
+        assert_eq!(config.busy_poll_us, Some(3));
+        assert_eq!(config.busy_timeout_us, Some(4));
+        assert_eq!(config.spi_chunk_size, Some(5));
+        assert_eq!(config.bw_border::<()>(), Ok(None));
+
+        let config = config.border_color(TriColor::Black);
+        assert_eq!(config.border(), Some(TriColor::Black));
+        assert_eq!(config.bw_border::<()>(), Ok(Some(Color::Black)));
+        let config = config.border_color(TriColor::Chromatic);
+        assert_eq!(config.bw_border::<()>(), Err(Error::Unsupported));
+        assert_eq!(EpdConfig::new(), EpdConfig::default());
+    }
+
+    #[test]
+    fn power_pin() {
+        let config = EpdConfig::new()
+            .busy_poll_us(3)
+            .power_on_delay_us(4)
+            .power_pin(5u8);
+        assert_eq!(config.busy_poll_us, Some(3));
+        assert_eq!(config.power_on_delay_us, Some(4));
+        assert_eq!(config.power, Some(5));
+        assert_eq!(EpdConfig::new().power, None);
+    }
+}
@@ -0,0 +1,153 @@
+//! What the emulated panel shows, and its encoding as PPM or PNG
+
+use std::vec::Vec;
+
+/// RGB image of the panel
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<(u8, u8, u8)>,
+}
+
+impl Image {
+    /// Creates an image filled with one color
+    pub fn new(width: u32, height: u32, rgb: (u8, u8, u8)) -> Self {
+        Image {
+            width,
+            height,
+            pixels: std::vec![rgb; width as usize * height as usize],
+        }
+    }
+
+    /// Width in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Color of a pixel, `None` outside of the image
+    pub fn pixel(&self, x: u32, y: u32) -> Option<(u8, u8, u8)> {
+        if x < self.width && y < self.height {
+            Some(self.pixels[(y * self.width + x) as usize])
+        } else {
+            None
+        }
+    }
+
+    pub(super) fn set_pixel(&mut self, x: u32, y: u32, rgb: (u8, u8, u8)) {
+        self.pixels[(y * self.width + x) as usize] = rgb;
+    }
+
+    /// Encodes the image as binary PPM (P6)
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut ppm = std::format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for &(r, g, b) in &self.pixels {
+            ppm.extend_from_slice(&[r, g, b]);
+        }
+        ppm
+    }
+
+    /// Encodes the image as PNG
+    ///
+    /// The image data isn't compressed, the point is to be byte for byte reproducible for
+    /// golden tests rather than small.
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut png = Vec::from(&b"\x89PNG\r\n\x1a\n"[..]);
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&self.width.to_be_bytes());
+        header.extend_from_slice(&self.height.to_be_bytes());
+        // 8 bits per channel, RGB, deflate, adaptive filtering, no interlace
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+        write_chunk(&mut png, b"IHDR", &header);
+
+        // every line starts with its filter type, 0 = none
+        let mut raw = Vec::with_capacity(self.pixels.len() * 3 + self.height as usize);
+        for line in self.pixels.chunks(self.width.max(1) as usize) {
+            raw.push(0);
+            for &(r, g, b) in line {
+                raw.extend_from_slice(&[r, g, b]);
+            }
+        }
+        write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// zlib stream made of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const BLOCK: usize = 0xFFFF;
+    let mut zlib = Vec::with_capacity(data.len() + data.len() / BLOCK * 5 + 11);
+    // deflate with a 32K window, no dictionary, fastest
+    zlib.extend_from_slice(&[0x78, 0x01]);
+    let mut blocks = data.chunks(BLOCK).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        zlib.push(last as u8);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(data).to_be_bytes());
+    zlib
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn ppm_header() {
+        let image = Image::new(2, 1, (1, 2, 3));
+        assert_eq!(image.to_ppm(), b"P6\n2 1\n255\n\x01\x02\x03\x01\x02\x03");
+    }
+}
@@ -0,0 +1,905 @@
+//! Host side emulation of the display controllers (`emulator` feature, needs `std`)
+//!
+//! An [`Emulator`] hands out an SPI device and pins that can be passed to any driver of this
+//! crate instead of the real hardware. It decodes the command stream of the controller family
+//! like the panel would: RAM windows, address counters, data entry modes, the two RAMs and deep
+//! sleep. Every refresh updates the [`Image`] the panel shows, which can be compared to golden
+//! images as PNG or PPM:
+//!
+//! ```rust
+//! use embedded_graphics::{prelude::*, primitives::{Circle, PrimitiveStyle}};
+//! use epd_waveshare::{color::Color, emulator::*, epd2in9_v2::*, prelude::*};
+//! # fn main() -> Result<(), epd_waveshare::Error<core::convert::Infallible>> {
+//! let emulator = Emulator::new(Controller::Ssd16xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+//! let (mut spi, mut delay) = (emulator.spi(), Delay);
+//! let mut epd = Epd2in9::new(
+//!     &mut spi,
+//!     emulator.busy(),
+//!     emulator.dc(),
+//!     emulator.rst(),
+//!     &mut delay,
+//!     None,
+//! )?;
+//!
+//! let mut display = Display2in9::default();
+//! let _ = display.clear(Color::White);
+//! let _ = Circle::new(Point::new(14, 100), 100)
+//!     .into_styled(PrimitiveStyle::with_fill(Color::Black))
+//!     .draw(&mut display);
+//! epd.update_and_display_frame(&mut spi, display.buffer(), &mut delay)?;
+//!
+//! let image = emulator.image();
+//! assert_eq!(image.pixel(64, 150), Some((0, 0, 0)));
+//! assert_eq!(image.pixel(64, 10), Some((0xff, 0xff, 0xff)));
+//! // std::fs::write("screen.png", image.to_png())
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Pixels are decoded with the conventions of the color types of this crate
+//! ([`ColorType::bitmask`](crate::color::ColorType::bitmask)) rather than from the data polarity
+//! and LUT registers, and refreshes are instantaneous unless
+//! [`set_busy_reads`](Emulator::set_busy_reads) is used.
+
+mod image;
+mod panel;
+
+pub use self::image::Image;
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{ErrorType as PinErrorType, InputPin, OutputPin},
+    spi::{ErrorType as SpiErrorType, Operation, SpiDevice},
+};
+use std::rc::Rc;
+use std::vec::Vec;
+
+use self::panel::Panel;
+
+/// Controller families
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Controller {
+    /// Solomon SSD16xx, used by the [`type_a`](crate::epd2in9_v2) displays and most of the
+    /// small ones (busy while high)
+    Ssd16xx,
+    /// UltraChip UC8176/UC8179 with `DTM1`/`DTM2` and a partial window, used by e.g.
+    /// [`epd4in2`](crate::epd4in2) and [`epd7in5_v2`](crate::epd7in5_v2) (busy while low)
+    Uc81xx,
+    /// The controllers of the 7 color ACeP displays [`epd5in65f`](crate::epd5in65f) and
+    /// [`epd7in3f`](crate::epd7in3f), with 4 bits per pixel in `DTM1`
+    Acep,
+}
+
+/// How the panel shows the content of its RAMs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PanelColors {
+    /// [`Color`](crate::color::Color), from the first RAM of the SSD16xx and `DTM2` of the UC81xx
+    BlackWhite,
+    /// [`TriColor`](crate::color::TriColor) with a red chromatic color, black/white from the
+    /// first RAM and chromatic from the second one
+    TriColor {
+        /// Same as the `BWRBIT` of [`Display`](crate::graphics::Display)
+        bwrbit: bool,
+    },
+    /// [`OctColor`](crate::color::OctColor), for [`Controller::Acep`]
+    Octal,
+}
+
+/// Emulated panel, see the [module documentation](self)
+#[derive(Clone)]
+pub struct Emulator {
+    panel: Rc<RefCell<Panel>>,
+}
+
+impl Emulator {
+    /// Creates a panel of `width` x `height` pixels showing white
+    pub fn new(controller: Controller, width: u32, height: u32, colors: PanelColors) -> Self {
+        Emulator {
+            panel: Rc::new(RefCell::new(Panel::new(controller, width, height, colors))),
+        }
+    }
+
+    /// SPI device to pass to the driver
+    pub fn spi(&self) -> Spi {
+        Spi {
+            panel: self.panel.clone(),
+        }
+    }
+
+    /// Busy pin to pass to the driver
+    pub fn busy(&self) -> BusyPin {
+        BusyPin {
+            panel: self.panel.clone(),
+        }
+    }
+
+    /// Data/command pin to pass to the driver
+    pub fn dc(&self) -> DcPin {
+        DcPin {
+            panel: self.panel.clone(),
+        }
+    }
+
+    /// Reset pin to pass to the driver
+    pub fn rst(&self) -> RstPin {
+        RstPin {
+            panel: self.panel.clone(),
+            low: false,
+        }
+    }
+
+    /// Power pin switching the panel on while high, to pass to
+    /// [`new_with_power`](crate::traits::WaveshareDisplayNew::new_with_power) or
+    /// [`EpdConfig::power_pin`](crate::config::EpdConfig::power_pin)
+    ///
+    /// The panel is powered until the pin is set low.
+    pub fn power(&self) -> PowerPin {
+        PowerPin {
+            panel: self.panel.clone(),
+        }
+    }
+
+    /// What the panel shows since the last refresh
+    pub fn image(&self) -> Image {
+        self.panel.borrow().image.clone()
+    }
+
+    /// Number of refreshes so far
+    pub fn refreshes(&self) -> u32 {
+        self.panel.borrow().refreshes
+    }
+
+    /// Whether the controller is in deep sleep, waiting for a hardware reset
+    pub fn is_asleep(&self) -> bool {
+        self.panel.borrow().asleep
+    }
+
+    /// Whether the power rail of the panel is switched on
+    pub fn is_powered(&self) -> bool {
+        self.panel.borrow().powered
+    }
+
+    /// Content of a RAM, `0` for the bw/new RAM (`DTM1` on the UC81xx) and `1` for the
+    /// red/old one (`DTM2`)
+    ///
+    /// Panics if `index` is neither `0` nor `1`.
+    pub fn ram(&self, index: usize) -> Vec<u8> {
+        self.panel.borrow().ram[index].clone()
+    }
+
+    /// Last value written to the border register, `BorderWaveformControl` (0x3C) on the SSD16xx
+    /// and the first byte of `VcomAndDataIntervalSetting` (0x50) on the others
+    pub fn border(&self) -> Option<u8> {
+        self.panel.borrow().border
+    }
+
+    /// Makes the busy pin read busy that many times after every refresh, to exercise the
+    /// waiting code of a driver
+    pub fn set_busy_reads(&self, reads: u32) {
+        self.panel.borrow_mut().busy_reads_after_refresh = reads;
+    }
+
+    /// Sets the temperature the sensor of the controller reports, 20 °C by default
+    pub fn set_temperature(&self, celsius: i8) {
+        self.panel.borrow_mut().temperature = celsius;
+    }
+
+    /// Temperature written to the register of an SSD16xx (0x1A) with the external sensor
+    /// selected (0x18), which the waveforms are then chosen for
+    pub fn written_temperature(&self) -> Option<i8> {
+        let panel = self.panel.borrow();
+        panel.temperature_register.filter(|_| panel.external_sensor)
+    }
+}
+
+/// SPI device of an [`Emulator`], reads return zeros except after the temperature sensor command
+pub struct Spi {
+    panel: Rc<RefCell<Panel>>,
+}
+
+impl Spi {
+    fn operation(&mut self, operation: &mut Operation<'_, u8>) {
+        let mut panel = self.panel.borrow_mut();
+        match operation {
+            Operation::Write(bytes) => panel.write(bytes),
+            Operation::Transfer(read, write) => {
+                panel.write(write);
+                read.fill(0);
+            }
+            Operation::TransferInPlace(bytes) => {
+                panel.write(bytes);
+                bytes.fill(0);
+            }
+            Operation::Read(bytes) => panel.read(bytes),
+            Operation::DelayNs(_) => {}
+        }
+    }
+}
+
+impl SpiErrorType for Spi {
+    type Error = Infallible;
+}
+
+impl SpiDevice for Spi {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Infallible> {
+        for operation in operations {
+            self.operation(operation);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::spi::SpiDevice for Spi {
+    async fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, u8>],
+    ) -> Result<(), Infallible> {
+        SpiDevice::transaction(self, operations)
+    }
+}
+
+/// Busy pin of an [`Emulator`]
+pub struct BusyPin {
+    panel: Rc<RefCell<Panel>>,
+}
+
+impl PinErrorType for BusyPin {
+    type Error = Infallible;
+}
+
+impl InputPin for BusyPin {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(self.panel.borrow_mut().busy_level())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        Ok(!self.is_high()?)
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::digital::Wait for BusyPin {
+    async fn wait_for_high(&mut self) -> Result<(), Infallible> {
+        while !self.is_high()? {}
+        Ok(())
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Infallible> {
+        while !self.is_low()? {}
+        Ok(())
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Infallible> {
+        self.wait_for_low().await?;
+        self.wait_for_high().await
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Infallible> {
+        self.wait_for_high().await?;
+        self.wait_for_low().await
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Infallible> {
+        let level = self.is_high()?;
+        while self.is_high()? == level {}
+        Ok(())
+    }
+}
+
+/// Data/command pin of an [`Emulator`]
+pub struct DcPin {
+    panel: Rc<RefCell<Panel>>,
+}
+
+impl PinErrorType for DcPin {
+    type Error = Infallible;
+}
+
+impl OutputPin for DcPin {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.panel.borrow_mut().set_dc(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.panel.borrow_mut().set_dc(true);
+        Ok(())
+    }
+}
+
+/// Reset pin of an [`Emulator`], the controller resets on the rising edge
+pub struct RstPin {
+    panel: Rc<RefCell<Panel>>,
+    low: bool,
+}
+
+impl PinErrorType for RstPin {
+    type Error = Infallible;
+}
+
+impl OutputPin for RstPin {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.low = true;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        if self.low {
+            self.panel.borrow_mut().reset();
+        }
+        self.low = false;
+        Ok(())
+    }
+}
+
+/// Power pin of an [`Emulator`]
+pub struct PowerPin {
+    panel: Rc<RefCell<Panel>>,
+}
+
+impl PinErrorType for PowerPin {
+    type Error = Infallible;
+}
+
+impl OutputPin for PowerPin {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.panel.borrow_mut().set_powered(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.panel.borrow_mut().set_powered(true);
+        Ok(())
+    }
+}
+
+/// Delay of an [`Emulator`], returns immediately
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Delay;
+
+impl DelayNs for Delay {
+    fn delay_ns(&mut self, _ns: u32) {}
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::delay::DelayNs for Delay {
+    async fn delay_ns(&mut self, _ns: u32) {}
+}
+
+#[cfg(all(test, feature = "graphics"))]
+mod tests {
+    use super::*;
+    use crate::color::{Color, OctColor};
+    use crate::prelude::*;
+    use embedded_graphics::{
+        prelude::*,
+        primitives::{PrimitiveStyle, Rectangle},
+    };
+
+    const WHITE: Option<(u8, u8, u8)> = Some((0xff, 0xff, 0xff));
+    const BLACK: Option<(u8, u8, u8)> = Some((0, 0, 0));
+
+    #[test]
+    fn ssd16xx_frame_and_sleep() {
+        use crate::epd2in9_v2::*;
+        let emulator = Emulator::new(Controller::Ssd16xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+        let (mut spi, mut delay) = (emulator.spi(), Delay);
+        let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+        let mut epd = Epd2in9::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+
+        let mut display = Display2in9::default();
+        let _ = display.clear(Color::White);
+        let _ = Rectangle::new(Point::new(8, 10), Size::new(8, 2))
+            .into_styled(PrimitiveStyle::with_fill(Color::Black))
+            .draw(&mut display);
+        epd.update_and_display_frame(&mut spi, display.buffer(), &mut delay)
+            .unwrap();
+
+        let image = emulator.image();
+        assert_eq!(image.pixel(7, 10), WHITE);
+        assert_eq!(image.pixel(8, 10), BLACK);
+        assert_eq!(image.pixel(15, 11), BLACK);
+        assert_eq!(image.pixel(8, 12), WHITE);
+
+        // nothing happens until the next reset
+        epd.sleep(&mut spi, &mut delay).unwrap();
+        assert!(emulator.is_asleep());
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+        assert_eq!(emulator.refreshes(), 1);
+        epd.wake_up(&mut spi, &mut delay).unwrap();
+        assert!(!emulator.is_asleep());
+    }
+
+    #[test]
+    fn uc81xx_partial_window() {
+        use crate::epd7in5_v2::*;
+        let emulator = Emulator::new(Controller::Uc81xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+        emulator.set_busy_reads(3);
+        let (mut spi, mut delay) = (emulator.spi(), Delay);
+        let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+        let mut epd = Epd7in5::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        epd.update_partial_frame(&mut spi, &mut delay, &[0x00; 2 * 3], 16, 8, 16, 3)
+            .unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+
+        let image = emulator.image();
+        assert_eq!(image.pixel(15, 8), WHITE);
+        assert_eq!(image.pixel(16, 8), BLACK);
+        assert_eq!(image.pixel(31, 10), BLACK);
+        assert_eq!(image.pixel(32, 10), WHITE);
+        assert_eq!(image.pixel(16, 11), WHITE);
+        assert_eq!(emulator.refreshes(), 2);
+    }
+
+    #[test]
+    fn ssd16xx_partial_window_in_pixels() {
+        use crate::epd3in7::*;
+        let emulator = Emulator::new(Controller::Ssd16xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+        let (mut spi, mut delay) = (emulator.spi(), Delay);
+        let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+        let mut epd = EPD3in7::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+
+        epd.clear_frame(&mut spi, &mut delay).unwrap();
+        epd.update_partial_frame(&mut spi, &mut delay, &[0x00; 2 * 3], 264, 300, 16, 3)
+            .unwrap();
+        epd.display_frame(&mut spi, &mut delay).unwrap();
+
+        let image = emulator.image();
+        assert_eq!(image.pixel(263, 300), WHITE);
+        assert_eq!(image.pixel(264, 300), BLACK);
+        assert_eq!(image.pixel(279, 302), BLACK);
+        assert_eq!(image.pixel(264, 303), WHITE);
+
+        // the next full frame covers the whole panel again
+        let buffer = [0x00; WIDTH as usize / 8 * HEIGHT as usize];
+        epd.update_and_display_frame(&mut spi, &buffer, &mut delay)
+            .unwrap();
+        let image = emulator.image();
+        assert_eq!(image.pixel(0, 0), BLACK);
+        assert_eq!(image.pixel(WIDTH - 1, HEIGHT - 1), BLACK);
+    }
+
+    #[test]
+    fn gray2_planes() {
+        use crate::color::Gray2;
+        // low bits in the b/w RAM, high bits in the red RAM
+        let planes = |emulator: &Emulator| (emulator.ram(0)[0], emulator.ram(1)[0]);
+        {
+            use crate::epd3in7::*;
+            let emulator =
+                Emulator::new(Controller::Ssd16xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+            let (mut spi, mut delay) = (emulator.spi(), Delay);
+            let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+            let mut epd = EPD3in7::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+
+            let mut display = Display3in7Gray2::default();
+            let _ = display.clear(Gray2::LightGray);
+            epd.update_and_display_gray2_frame(&mut spi, display.buffer(), &mut delay)
+                .unwrap();
+            assert_eq!(planes(&emulator), (0x00, 0xFF));
+            let _ = display.clear(Gray2::DarkGray);
+            epd.update_and_display_gray2_frame(&mut spi, display.buffer(), &mut delay)
+                .unwrap();
+            assert_eq!(planes(&emulator), (0xFF, 0x00));
+        }
+        {
+            use crate::epd2in7_v2::*;
+            let emulator =
+                Emulator::new(Controller::Ssd16xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+            let (mut spi, mut delay) = (emulator.spi(), Delay);
+            let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+            let mut epd = Epd2in7::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+
+            let mut display = Display2in7Gray2::default();
+            let _ = display.clear(Gray2::LightGray);
+            epd.update_and_display_gray2_frame(&mut spi, display.buffer(), &mut delay)
+                .unwrap();
+            assert_eq!(planes(&emulator), (0x00, 0xFF));
+            let _ = display.clear(Gray2::DarkGray);
+            epd.update_and_display_gray2_frame(&mut spi, display.buffer(), &mut delay)
+                .unwrap();
+            assert_eq!(planes(&emulator), (0xFF, 0x00));
+        }
+    }
+
+    #[test]
+    fn capabilities_match_the_drivers() {
+        let emulator = Emulator::new(Controller::Uc81xx, 128, 296, PanelColors::BlackWhite);
+        let (mut spi, mut delay) = (emulator.spi(), Delay);
+        let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+        let mut epd =
+            crate::epd2in9bc::Epd2in9bc::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+        assert!(!epd.capabilities().partial_update);
+        assert_eq!(
+            epd.update_partial_frame(&mut spi, &mut delay, &[0xFF; 16], 0, 0, 8, 16),
+            Err(crate::Error::Unsupported)
+        );
+
+        // the quick refresh compares with the last frame, a window can't be written
+        let emulator = Emulator::new(Controller::Ssd16xx, 122, 250, PanelColors::BlackWhite);
+        let (mut spi, mut delay) = (emulator.spi(), Delay);
+        let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+        let mut epd =
+            crate::epd2in13_v2::Epd2in13::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+        epd.update_partial_frame(&mut spi, &mut delay, &[0xFF; 16], 0, 0, 8, 16)
+            .unwrap();
+        epd.set_refresh(&mut spi, &mut delay, RefreshLut::Quick)
+            .unwrap();
+        assert_eq!(
+            epd.update_partial_frame(&mut spi, &mut delay, &[0xFF; 16], 0, 0, 8, 16),
+            Err(crate::Error::Unsupported)
+        );
+
+        use crate::epd7in5_v2::*;
+        let emulator = Emulator::new(Controller::Uc81xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+        let (mut spi, mut delay) = (emulator.spi(), Delay);
+        let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+        let mut epd = Epd7in5::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+        let capabilities = epd.capabilities();
+        assert_eq!(capabilities.busy, BusyPolling::Command);
+        for &lut in capabilities.refresh_luts {
+            epd.set_lut(&mut spi, &mut delay, Some(lut)).unwrap();
+        }
+    }
+
+    #[cfg(feature = "any_epd")]
+    #[test]
+    fn any_epd_forwards_to_the_model() {
+        use crate::any::*;
+        let model = Model::Epd2in9V2;
+        let (width, height) = (model.width(), model.height());
+        let emulator = Emulator::new(Controller::Ssd16xx, width, height, PanelColors::BlackWhite);
+        let (mut spi, mut delay) = (emulator.spi(), Delay);
+        let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+        let mut epd = AnyEpd::new(model, &mut spi, busy, dc, rst, &mut delay, None).unwrap();
+        assert_eq!(epd.model(), model);
+        assert!(epd.capabilities().quick_refresh);
+
+        let mut buffer = [0u8; MAX_BUFFER_LEN];
+        let mut display = model.display(&mut buffer).unwrap();
+        let _ = display.clear(Color::White);
+        let _ = Rectangle::new(Point::new(8, 10), Size::new(8, 2))
+            .into_styled(PrimitiveStyle::with_fill(Color::Black))
+            .draw(&mut display);
+        epd.update_and_display_frame(&mut spi, display.buffer(), &mut delay)
+            .unwrap();
+
+        let image = emulator.image();
+        assert_eq!(image.pixel(8, 10), BLACK);
+        assert_eq!(image.pixel(8, 12), WHITE);
+    }
+
+    #[test]
+    fn custom_waveforms() {
+        use crate::waveform::{Ssd16xxWaveform, Uc81xxWaveform};
+        use crate::Error;
+        {
+            use crate::epd2in9_v2::*;
+            let emulator =
+                Emulator::new(Controller::Ssd16xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+            let (mut spi, mut delay) = (emulator.spi(), Delay);
+            let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+            let mut epd = Epd2in9::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+
+            let short = Ssd16xxWaveform {
+                lut: &[0; 30],
+                voltages: None,
+            };
+            assert_eq!(
+                epd.set_custom_lut(&mut spi, &mut delay, &short),
+                Err(Error::InvalidWaveform)
+            );
+            let table = [0; 159];
+            epd.set_custom_lut(&mut spi, &mut delay, &Ssd16xxWaveform::from_table(&table))
+                .unwrap();
+        }
+        {
+            use crate::epd2in9::*;
+            let emulator =
+                Emulator::new(Controller::Ssd16xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+            let (mut spi, mut delay) = (emulator.spi(), Delay);
+            let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+            let mut epd = Epd2in9::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+
+            // the SSD1608 takes no voltages along the waveform
+            let table = [0; 159];
+            let with_voltages = Ssd16xxWaveform {
+                lut: &table[..30],
+                ..Ssd16xxWaveform::from_table(&table)
+            };
+            assert_eq!(
+                epd.set_custom_lut(&mut spi, &mut delay, &with_voltages),
+                Err(Error::InvalidWaveform)
+            );
+        }
+        {
+            use crate::epd4in2::*;
+            let emulator =
+                Emulator::new(Controller::Uc81xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+            let (mut spi, mut delay) = (emulator.spi(), Delay);
+            let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+            let mut epd = Epd4in2::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+            let waveform = Uc81xxWaveform {
+                vcom: [0; 44],
+                ww: [0; 42],
+                bw: [0; 42],
+                wb: [0; 42],
+                bb: [0; 42],
+            };
+            epd.set_custom_lut(&mut spi, &mut delay, &waveform).unwrap();
+        }
+        {
+            use crate::epd3in7::*;
+            let emulator =
+                Emulator::new(Controller::Ssd16xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+            let (mut spi, mut delay) = (emulator.spi(), Delay);
+            let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+            let mut epd = EPD3in7::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+
+            let short = Ssd16xxWaveform {
+                lut: &[0; 70],
+                voltages: None,
+            };
+            assert_eq!(
+                epd.set_custom_lut(&mut spi, &mut delay, &short),
+                Err(Error::InvalidWaveform)
+            );
+            let waveform = Ssd16xxWaveform {
+                lut: &[0; 105],
+                voltages: None,
+            };
+            epd.set_custom_lut(&mut spi, &mut delay, &waveform).unwrap();
+        }
+    }
+
+    #[test]
+    fn temperature() {
+        use crate::waveform::{Ssd16xxWaveform, TemperatureBand, TemperatureBands};
+        use crate::Error;
+        {
+            use crate::epd2in9_v2::*;
+            let emulator =
+                Emulator::new(Controller::Ssd16xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+            let (mut spi, mut delay) = (emulator.spi(), Delay);
+            let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+            let mut epd = Epd2in9::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+
+            emulator.set_temperature(-12);
+            let refreshes = emulator.refreshes();
+            let celsius = epd.read_temperature(&mut spi, &mut delay).unwrap();
+            assert_eq!(celsius, -12);
+            // measuring doesn't refresh the panel
+            assert_eq!(emulator.refreshes(), refreshes);
+
+            let table = [0; 159];
+            let bands = [TemperatureBand {
+                up_to: 0,
+                waveform: Ssd16xxWaveform::from_table(&table),
+            }];
+            epd.set_lut_for_temperature(
+                &mut spi,
+                &mut delay,
+                &TemperatureBands::new(&bands),
+                celsius,
+            )
+            .unwrap();
+
+            // an external sensor's reading survives measuring and waking up
+            assert_eq!(emulator.written_temperature(), None);
+            epd.set_temperature(&mut spi, Some(35)).unwrap();
+            assert_eq!(emulator.written_temperature(), Some(35));
+            assert_eq!(epd.read_temperature(&mut spi, &mut delay), Ok(-12));
+            assert_eq!(emulator.written_temperature(), Some(35));
+            epd.sleep(&mut spi, &mut delay).unwrap();
+            epd.wake_up(&mut spi, &mut delay).unwrap();
+            assert_eq!(emulator.written_temperature(), Some(35));
+            epd.set_temperature(&mut spi, None).unwrap();
+            assert_eq!(emulator.written_temperature(), None);
+            assert_eq!(
+                epd.set_lut_for_temperature(
+                    &mut spi,
+                    &mut delay,
+                    &TemperatureBands::new(&[]),
+                    celsius
+                ),
+                Err(Error::InvalidWaveform)
+            );
+        }
+        {
+            use crate::epd7in5_v2::*;
+            let emulator =
+                Emulator::new(Controller::Uc81xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+            let (mut spi, mut delay) = (emulator.spi(), Delay);
+            let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+            let mut epd = Epd7in5::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+
+            emulator.set_temperature(31);
+            assert_eq!(epd.read_temperature(&mut spi, &mut delay), Ok(31));
+            epd.set_temperature(&mut spi, Some(-5)).unwrap();
+            epd.set_temperature(&mut spi, None).unwrap();
+        }
+    }
+
+    #[test]
+    fn power_states() {
+        use crate::epd2in13_v2::*;
+        use crate::power::Awake;
+        let emulator = Emulator::new(Controller::Ssd16xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+        let (mut spi, mut delay) = (emulator.spi(), Delay);
+        let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+        let mut epd = Awake::new(Epd2in13::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap());
+        epd.epd_mut().set_deep_sleep_mode(DeepSleepMode::Mode1);
+
+        let epd = epd.sleep(&mut spi, &mut delay).unwrap();
+        assert!(emulator.is_asleep());
+        assert_eq!(epd.deep_sleep_mode(), DeepSleepMode::Mode1);
+        assert!(epd.deep_sleep_mode().retains_ram());
+
+        let mut epd = epd.wake_up(&mut spi, &mut delay).unwrap();
+        assert!(!emulator.is_asleep());
+        let mut display = Display2in13::default();
+        let _ = display.clear(Color::Black);
+        epd.update_and_display_frame(&mut spi, display.buffer(), &mut delay)
+            .unwrap();
+        assert_eq!(emulator.image().pixel(0, 0), BLACK);
+    }
+
+    #[test]
+    fn power_rail() {
+        use crate::epd2in9_v2::*;
+        use crate::power::Awake;
+        let emulator = Emulator::new(Controller::Ssd16xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+        let (mut spi, mut delay, mut power) = (emulator.spi(), Delay, emulator.power());
+        let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+        power.set_low().unwrap();
+        let epd =
+            Epd2in9::new_with_power(&mut spi, busy, dc, rst, power, &mut delay, None).unwrap();
+        assert!(emulator.is_powered());
+        let mut epd = Awake::new(epd);
+        let mut display = Display2in9::default();
+        let _ = display.clear(Color::White);
+        epd.update_and_display_frame(&mut spi, display.buffer(), &mut delay)
+            .unwrap();
+        assert!(emulator.ram(0).iter().all(|&byte| byte == 0xff));
+
+        let epd = epd.power_off(&mut spi, &mut delay).unwrap();
+        assert!(!emulator.is_powered());
+        assert!(emulator.ram(0).iter().all(|&byte| byte == 0));
+        // e-paper keeps its image without power
+        assert_eq!(emulator.image().pixel(0, 0), WHITE);
+
+        let mut epd = epd.power_on(&mut spi, &mut delay).unwrap();
+        assert!(emulator.is_powered());
+        let _ = display.clear(Color::Black);
+        epd.update_and_display_frame(&mut spi, display.buffer(), &mut delay)
+            .unwrap();
+        assert_eq!(emulator.image().pixel(0, 0), BLACK);
+
+        // without a power pin the display only goes to sleep
+        let emulator = Emulator::new(Controller::Ssd16xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+        let mut spi = emulator.spi();
+        let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+        let mut epd = Epd2in9::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+        assert!(matches!(
+            epd.power_off(&mut spi, &mut delay),
+            Err(crate::Error::Unsupported)
+        ));
+        assert!(emulator.is_asleep());
+        assert!(emulator.is_powered());
+    }
+
+    #[test]
+    fn config() {
+        use crate::epd2in9_v2::*;
+        let emulator = Emulator::new(Controller::Ssd16xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+        let (mut spi, mut delay) = (emulator.spi(), Delay);
+        let pins = || (emulator.busy(), emulator.dc(), emulator.rst());
+
+        let (busy, dc, rst) = pins();
+        let config = EpdConfig::new().border_color(TriColor::Chromatic);
+        let result = Epd2in9::with_config(&mut spi, busy, dc, rst, &mut delay, config);
+        assert!(matches!(result, Err(crate::Error::Unsupported)));
+        assert_eq!(emulator.border(), None);
+
+        let (busy, dc, rst) = pins();
+        let config = EpdConfig::new().border_color(TriColor::Black);
+        Epd2in9::with_config(&mut spi, busy, dc, rst, &mut delay, config).unwrap();
+        assert_eq!(emulator.border(), Some(0x04));
+
+        let (busy, dc, rst) = pins();
+        let config = EpdConfig::new()
+            .reset_pulse_us(20_000)
+            .reset_settle_us(10_000)
+            .busy_poll_us(0)
+            .busy_timeout_us(1_000)
+            .spi_chunk_size(7);
+        let mut epd = Epd2in9::with_config(&mut spi, busy, dc, rst, &mut delay, config).unwrap();
+        let mut display = Display2in9::default();
+        let _ = display.clear(Color::Black);
+        epd.update_and_display_frame(&mut spi, display.buffer(), &mut delay)
+            .unwrap();
+        assert_eq!(emulator.image().pixel(0, 0), BLACK);
+
+        emulator.set_busy_reads(u32::MAX);
+        let result = epd.update_and_display_frame(&mut spi, display.buffer(), &mut delay);
+        assert_eq!(result, Err(crate::Error::BusyTimeout));
+
+        use crate::epd4in2::{self, Epd4in2};
+        let emulator = Emulator::new(
+            Controller::Uc81xx,
+            epd4in2::WIDTH,
+            epd4in2::HEIGHT,
+            PanelColors::BlackWhite,
+        );
+        let mut spi = emulator.spi();
+        let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+        Epd4in2::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+        assert_eq!(emulator.border(), Some(0x97));
+        let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+        let config = EpdConfig::new().border_color(TriColor::Black);
+        Epd4in2::with_config(&mut spi, busy, dc, rst, &mut delay, config).unwrap();
+        assert_eq!(emulator.border(), Some(0x57));
+    }
+
+    #[test]
+    fn deep_sleep_modes() {
+        {
+            use crate::epd3in7::*;
+            let emulator =
+                Emulator::new(Controller::Ssd16xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+            let (mut spi, mut delay) = (emulator.spi(), Delay);
+            let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+            let mut epd = EPD3in7::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+            assert_eq!(epd.deep_sleep_mode(), DeepSleepMode::Mode1);
+            epd.set_deep_sleep_mode(DeepSleepMode::Mode2);
+            epd.sleep(&mut spi, &mut delay).unwrap();
+            assert!(emulator.is_asleep());
+            assert!(!epd.deep_sleep_mode().retains_ram());
+        }
+        {
+            use crate::epd2in9::*;
+            let emulator =
+                Emulator::new(Controller::Ssd16xx, WIDTH, HEIGHT, PanelColors::BlackWhite);
+            let (mut spi, mut delay) = (emulator.spi(), Delay);
+            let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+            let mut epd = Epd2in9::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+            epd.sleep(&mut spi, &mut delay).unwrap();
+            assert!(!emulator.is_asleep());
+
+            // the SSD1608 only has mode 1
+            epd.wake_up(&mut spi, &mut delay).unwrap();
+            epd.set_deep_sleep_mode(DeepSleepMode::Mode2);
+            assert_eq!(epd.deep_sleep_mode(), DeepSleepMode::Mode1);
+            epd.sleep(&mut spi, &mut delay).unwrap();
+            assert!(emulator.is_asleep());
+        }
+    }
+
+    #[test]
+    fn acep_colors() {
+        use crate::epd5in65f::*;
+        let emulator = Emulator::new(Controller::Acep, WIDTH, HEIGHT, PanelColors::Octal);
+        let (mut spi, mut delay) = (emulator.spi(), Delay);
+        let (busy, dc, rst) = (emulator.busy(), emulator.dc(), emulator.rst());
+        let mut epd = Epd5in65f::new(&mut spi, busy, dc, rst, &mut delay, None).unwrap();
+
+        let mut display = Display5in65f::default();
+        let _ = display.clear(OctColor::White);
+        let _ = Rectangle::new(Point::new(3, 0), Size::new(2, 1))
+            .into_styled(PrimitiveStyle::with_fill(OctColor::Orange))
+            .draw(&mut display);
+        epd.update_and_display_frame(&mut spi, display.buffer(), &mut delay)
+            .unwrap();
+
+        let image = emulator.image();
+        assert_eq!(image.pixel(2, 0), WHITE);
+        assert_eq!(image.pixel(3, 0), Some(OctColor::Orange.rgb()));
+        assert_eq!(image.pixel(4, 0), Some(OctColor::Orange.rgb()));
+        assert!(image.to_png().starts_with(b"\x89PNG"));
+    }
+}
@@ -0,0 +1,438 @@
+//! Decoding of the command stream of the controller families
+
+use std::vec::Vec;
+
+use super::image::Image;
+use super::{Controller, PanelColors};
+use crate::color::{Color, ColorType, OctColor, TriColor};
+
+/// RAM area and address counter, in pixels
+#[derive(Clone, Copy)]
+struct Window {
+    x_start: u32,
+    x_end: u32,
+    y_start: u32,
+    y_end: u32,
+}
+
+impl Window {
+    fn full(width: u32, height: u32) -> Self {
+        Window {
+            x_start: 0,
+            x_end: width - 1,
+            y_start: 0,
+            y_end: height - 1,
+        }
+    }
+}
+
+pub(super) struct Panel {
+    controller: Controller,
+    colors: PanelColors,
+    width: u32,
+    height: u32,
+    /// Line length of a RAM, in bytes
+    line_bytes: usize,
+    /// The two RAMs: bw/new and red/old for SSD16xx, DTM1 and DTM2 for the UC81xx
+    pub(super) ram: [Vec<u8>; 2],
+    pub(super) image: Image,
+    pub(super) refreshes: u32,
+    pub(super) asleep: bool,
+    /// false while the power rail is switched off
+    pub(super) powered: bool,
+    /// Reading of the temperature sensor, in °C
+    pub(super) temperature: i8,
+    /// SSD16xx: the external sensor is selected, the temperature register keeps written values
+    pub(super) external_sensor: bool,
+    /// SSD16xx: whole °C last written to the temperature register
+    pub(super) temperature_register: Option<i8>,
+    /// Last value of the border register
+    pub(super) border: Option<u8>,
+
+    /// false while the dc pin is low
+    data: bool,
+    command: Option<u8>,
+    params: Vec<u8>,
+    /// RAM written by the data of the current command
+    target: Option<usize>,
+
+    window: Window,
+    x: u32,
+    y: u32,
+    /// SSD16xx data entry mode: bit 0 x increments, bit 1 y increments, bit 2 y first
+    entry_mode: u8,
+    /// SSD16xx display update sequence, the panel only refreshes with bit 2 set
+    update_sequence: u8,
+    /// UC81xx partial mode
+    partial: bool,
+    partial_window: Window,
+
+    busy_reads: u32,
+    pub(super) busy_reads_after_refresh: u32,
+    /// ACeP: the busy pin reads low once after powering off
+    power_off_pulse: bool,
+}
+
+impl Panel {
+    pub(super) fn new(
+        controller: Controller,
+        width: u32,
+        height: u32,
+        colors: PanelColors,
+    ) -> Self {
+        let bits = match colors {
+            PanelColors::Octal => 4,
+            _ => 1,
+        };
+        let line_bytes = (width as usize * bits + 7) / 8;
+        let full = Window::full(width, height);
+        let mut panel = Panel {
+            controller,
+            colors,
+            width,
+            height,
+            line_bytes,
+            ram: [
+                std::vec![0; line_bytes * height as usize],
+                std::vec![0; line_bytes * height as usize],
+            ],
+            image: Image::new(width, height, (0xff, 0xff, 0xff)),
+            refreshes: 0,
+            asleep: false,
+            powered: true,
+            temperature: 20,
+            external_sensor: true,
+            temperature_register: None,
+            border: None,
+            data: false,
+            command: None,
+            params: Vec::new(),
+            target: None,
+            window: full,
+            x: 0,
+            y: 0,
+            entry_mode: 0,
+            update_sequence: 0,
+            partial: false,
+            partial_window: full,
+            busy_reads: 0,
+            busy_reads_after_refresh: 0,
+            power_off_pulse: false,
+        };
+        panel.reset();
+        panel
+    }
+
+    /// Hardware reset, the RAM keeps its content
+    pub(super) fn reset(&mut self) {
+        self.asleep = false;
+        self.command = None;
+        self.target = None;
+        self.soft_reset();
+    }
+
+    /// Switches the power rail, the RAM is lost while off but the image stays
+    pub(super) fn set_powered(&mut self, powered: bool) {
+        if self.powered && !powered {
+            self.ram.iter_mut().for_each(|ram| ram.fill(0));
+            self.busy_reads = 0;
+            self.power_off_pulse = false;
+        }
+        self.powered = powered;
+        self.reset();
+    }
+
+    fn soft_reset(&mut self) {
+        self.window = Window::full(self.width, self.height);
+        self.x = 0;
+        self.y = 0;
+        self.entry_mode = 0b011;
+        self.update_sequence = 0xFF;
+        self.partial = false;
+        self.external_sensor = true;
+        self.temperature_register = None;
+    }
+
+    pub(super) fn set_dc(&mut self, data: bool) {
+        self.data = data;
+    }
+
+    /// Level of the busy pin
+    pub(super) fn busy_level(&mut self) -> bool {
+        // SSD16xx are busy while high, the others while low
+        let idle = self.controller != Controller::Ssd16xx;
+        if self.power_off_pulse {
+            self.power_off_pulse = false;
+            return false;
+        }
+        if self.busy_reads > 0 {
+            self.busy_reads -= 1;
+            return !idle;
+        }
+        idle
+    }
+
+    pub(super) fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if self.asleep || !self.powered {
+                // only a hardware reset wakes the controller up
+                continue;
+            }
+            if self.data {
+                self.data_byte(byte);
+            } else {
+                self.command_byte(byte);
+            }
+        }
+    }
+
+    /// Answers the temperature sensor command of the family, zeros otherwise
+    pub(super) fn read(&mut self, bytes: &mut [u8]) {
+        bytes.fill(0);
+        let sensor = match self.controller {
+            Controller::Ssd16xx => 0x1B,
+            Controller::Uc81xx | Controller::Acep => 0x40,
+        };
+        if self.data && self.powered && !self.asleep && self.command == Some(sensor) {
+            if let Some(first) = bytes.first_mut() {
+                *first = self.temperature as u8;
+            }
+        }
+    }
+
+    fn command_byte(&mut self, command: u8) {
+        self.command = Some(command);
+        self.params.clear();
+        self.target = None;
+        match self.controller {
+            Controller::Ssd16xx => self.ssd16xx_command(command),
+            Controller::Uc81xx | Controller::Acep => self.uc81xx_command(command),
+        }
+    }
+
+    fn data_byte(&mut self, byte: u8) {
+        if let Some(ram) = self.target {
+            self.write_ram(ram, byte);
+            return;
+        }
+        if let Some(command) = self.command {
+            self.params.push(byte);
+            match self.controller {
+                Controller::Ssd16xx => self.ssd16xx_params(command),
+                Controller::Uc81xx | Controller::Acep => self.uc81xx_params(command),
+            }
+        }
+    }
+
+    fn ssd16xx_command(&mut self, command: u8) {
+        match command {
+            0x12 => self.soft_reset(),
+            0x20 if self.update_sequence & 0b100 != 0 => self.refresh(),
+            0x24 => self.target = Some(0),
+            0x26 => self.target = Some(1),
+            _ => {}
+        }
+    }
+
+    fn ssd16xx_params(&mut self, command: u8) {
+        let p = &self.params;
+        let word = |i: usize| u32::from(p[i]) | u32::from(p[i + 1]) << 8;
+        match (command, p.len()) {
+            (0x10, 1) => self.asleep = p[0] & 0b11 != 0,
+            (0x11, 1) => self.entry_mode = p[0] & 0b111,
+            (0x18, 1) => self.external_sensor = p[0] == 0x48,
+            (0x1A, 1) => self.temperature_register = Some(p[0] as i8),
+            (0x22, 1) => self.update_sequence = p[0],
+            (0x3C, 1) => self.border = Some(p[0]),
+            // x in bytes, or in pixels with two bytes per address on the larger controllers
+            (0x44, 2) => {
+                self.window.x_start = u32::from(p[0]) * 8;
+                self.window.x_end = u32::from(p[1]) * 8 + 7;
+            }
+            (0x44, 4) => {
+                self.window.x_start = word(0);
+                self.window.x_end = word(2);
+            }
+            (0x45, 2) => self.window.y_start = word(0),
+            (0x45, 4) => self.window.y_end = word(2),
+            (0x4E, 1) => self.x = u32::from(p[0]) * 8,
+            (0x4E, 2) => self.x = word(0),
+            (0x4F, 1) => self.y = u32::from(p[0]),
+            (0x4F, 2) => self.y = word(0),
+            _ => {}
+        }
+    }
+
+    fn uc81xx_command(&mut self, command: u8) {
+        match command {
+            0x02 if self.controller == Controller::Acep => self.power_off_pulse = true,
+            0x10 | 0x13 => {
+                self.target = Some(if command == 0x10 { 0 } else { 1 });
+                self.window = if self.partial {
+                    self.partial_window
+                } else {
+                    Window::full(self.width, self.height)
+                };
+                self.x = self.window.x_start;
+                self.y = self.window.y_start;
+            }
+            0x12 => self.refresh(),
+            0x91 => self.partial = true,
+            0x92 => self.partial = false,
+            _ => {}
+        }
+    }
+
+    fn uc81xx_params(&mut self, command: u8) {
+        let p = &self.params;
+        let word = |i: usize| u32::from(p[i]) << 8 | u32::from(p[i + 1]);
+        match (command, p.len()) {
+            (0x07, 1) => self.asleep = p[0] == 0xA5,
+            (0x50, 1) => self.border = Some(p[0]),
+            // HRST, HRED, VRST, VRED, the horizontal ones byte aligned
+            (0x90, 8) => {
+                self.partial_window = Window {
+                    x_start: word(0) & !0b111,
+                    x_end: word(2) | 0b111,
+                    y_start: word(4),
+                    y_end: word(6),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn write_ram(&mut self, ram: usize, byte: u8) {
+        let (x, y) = (self.x, self.y);
+        if x < self.width && y < self.height {
+            let index = y as usize * self.line_bytes + self.byte_column(x);
+            self.ram[ram][index] = byte;
+        }
+        self.advance();
+    }
+
+    /// Index of the byte holding `x` inside of a line
+    fn byte_column(&self, x: u32) -> usize {
+        match self.colors {
+            PanelColors::Octal => x as usize / 2,
+            _ => x as usize / 8,
+        }
+    }
+
+    /// Moves the address counter to the next byte
+    fn advance(&mut self) {
+        let step = match self.colors {
+            PanelColors::Octal => 2,
+            _ => 8,
+        };
+        let w = self.window;
+        let (x_min, x_max) = (w.x_start.min(w.x_end), w.x_start.max(w.x_end));
+        let (y_min, y_max) = (w.y_start.min(w.y_end), w.y_start.max(w.y_end));
+        let (x_inc, y_inc, y_first) = match self.controller {
+            Controller::Ssd16xx => (
+                self.entry_mode & 0b001 != 0,
+                self.entry_mode & 0b010 != 0,
+                self.entry_mode & 0b100 != 0,
+            ),
+            Controller::Uc81xx | Controller::Acep => (true, true, false),
+        };
+        // start of the window in the direction of the counter
+        let x_first = if x_inc { x_min } else { x_max - x_max % step };
+        let y_origin = if y_inc { y_min } else { y_max };
+
+        let next_x = |x: u32| {
+            if x_inc {
+                Some(x + step).filter(|&x| x <= x_max)
+            } else {
+                x.checked_sub(step).filter(|&x| x >= x_min - x_min % step)
+            }
+        };
+        let next_y = |y: u32| {
+            if y_inc {
+                Some(y + 1).filter(|&y| y <= y_max)
+            } else {
+                y.checked_sub(1).filter(|&y| y >= y_min)
+            }
+        };
+
+        if y_first {
+            match next_y(self.y) {
+                Some(y) => self.y = y,
+                None => {
+                    self.y = y_origin;
+                    self.x = next_x(self.x).unwrap_or(x_first);
+                }
+            }
+        } else {
+            match next_x(self.x) {
+                Some(x) => self.x = x,
+                None => {
+                    self.x = x_first;
+                    self.y = next_y(self.y).unwrap_or(y_origin);
+                }
+            }
+        }
+    }
+
+    fn refresh(&mut self) {
+        self.refreshes += 1;
+        self.busy_reads = self.busy_reads_after_refresh;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let index = y as usize * self.line_bytes + self.byte_column(x);
+                let planes = (self.ram[0][index], self.ram[1][index]);
+                let rgb = match self.colors {
+                    PanelColors::BlackWhite => {
+                        // the UC81xx show the new data of DTM2
+                        let byte = match self.controller {
+                            Controller::Ssd16xx => planes.0,
+                            _ => planes.1,
+                        };
+                        match decode(&[Color::Black, Color::White], false, x, (byte, 0)) {
+                            Color::Black => (0x00, 0x00, 0x00),
+                            Color::White => (0xff, 0xff, 0xff),
+                        }
+                    }
+                    PanelColors::TriColor { bwrbit } => {
+                        let colors = [TriColor::Black, TriColor::White, TriColor::Chromatic];
+                        match decode(&colors, bwrbit, x, planes) {
+                            TriColor::Black => (0x00, 0x00, 0x00),
+                            TriColor::White => (0xff, 0xff, 0xff),
+                            TriColor::Chromatic => (0xff, 0x00, 0x00),
+                        }
+                    }
+                    PanelColors::Octal => {
+                        let nibble = if x % 2 == 0 {
+                            planes.0 >> 4
+                        } else {
+                            planes.0 & 0x0F
+                        };
+                        // HiZ leaves the pixel as it was
+                        match OctColor::from_nibble(nibble) {
+                            Ok(OctColor::HiZ) | Err(_) => continue,
+                            Ok(color) => color.rgb(),
+                        }
+                    }
+                };
+                self.image.set_pixel(x, y, rgb);
+            }
+        }
+    }
+}
+
+/// Finds the color whose bits match the RAM content, inverse of [`ColorType::bitmask`]
+///
+/// Combinations no color writes fall back to the color matching the first plane only.
+fn decode<C: ColorType + Copy>(colors: &[C], bwrbit: bool, x: u32, (first, second): (u8, u8)) -> C {
+    let matches = |color: &C, both: bool| {
+        let (mask, bits) = color.bitmask(bwrbit, x);
+        first & !mask == (bits & 0xFF) as u8
+            && (!both || C::BUFFER_COUNT == 1 || second & !mask == (bits >> 8) as u8)
+    };
+    colors
+        .iter()
+        .find(|color| matches(color, true))
+        .or_else(|| colors.iter().find(|color| matches(color, false)))
+        .copied()
+        .unwrap_or(colors[0])
+}
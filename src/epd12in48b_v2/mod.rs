@@ -7,19 +7,83 @@
 //! - [Wiki](https://www.waveshare.com/wiki/12.48inch_e-Paper_Module_(B))
 //! - [Waveshare C drivers](https://github.com/waveshareteam/12.48inch-e-paper/)
 //!
+//! [`Epd12in48b`] implements [`WaveshareDisplay`] and [`WaveshareThreeColorDisplay`], while
+//! [`EpdDriver`] gives low-level access to the sub-displays, lookup tables and status.
+//!
+//! A full [`Display12in48b`] takes about 320 KB, so on smaller targets the frame is rendered and
+//! transmitted in horizontal bands with [`BandRenderer`](crate::graphics::band::BandRenderer),
+//! before refreshing the whole display once:
+//!
+//! ```rust, no_run
+//! # use embedded_hal_mock::eh1::*;
+//! # fn main() -> Result<(), epd_waveshare::Error<embedded_hal::spi::ErrorKind>> {
+//! use embedded_graphics::{prelude::*, primitives::{Circle, PrimitiveStyle}};
+//! use epd_waveshare::{color::TriColor, epd12in48b_v2::*, graphics::band::BandRenderer, prelude::*};
+//! # let mut spi = spi::Mock::new(&[]);
+//! # let pin = || digital::Mock::new(&[]);
+//! # let mut delay = delay::NoopDelay::new();
+//! # let (m1_busy, s1_busy, m2_busy, s2_busy) = (pin(), pin(), pin(), pin());
+//! # let (m1_cs, s1_cs, m2_cs, s2_cs, m1s1_dc, m2s2_dc) = (pin(), pin(), pin(), pin(), pin(), pin());
+//! # let (m1s1_rst, m2s2_rst) = (pin(), pin());
+//!
+//! // the spi device must not have a chip select, they are part of the control pins
+//! let busy = BusyPins { m1: m1_busy, s1: s1_busy, m2: m2_busy, s2: s2_busy };
+//! let control = ControlPins { m1_cs, s1_cs, m2_cs, s2_cs, m1s1_dc, m2s2_dc };
+//! let rst = ResetPins { m1s1: m1s1_rst, m2s2: m2s2_rst };
+//! let mut epd = Epd12in48b::new(&mut spi, busy, control, rst, &mut delay, None)?;
+//!
+//! // 24 bands of 41 lines, 13 KB each
+//! let mut buffer = [0u8; 2 * WIDTH as usize / 8 * 41];
+//! let mut renderer = BandRenderer::<TriColor>::new(&mut buffer, WIDTH, HEIGHT, false).unwrap();
+//! renderer.update_frame(&mut epd, &mut spi, &mut delay, |band| {
+//!     let _ = band.clear(TriColor::White);
+//!     let _ = Circle::new(Point::new(500, 340), 300)
+//!         .into_styled(PrimitiveStyle::with_fill(TriColor::Chromatic))
+//!         .draw(band);
+//! })?;
+//! epd.display_frame(&mut spi, &mut delay)?;
+//! # Ok(())
+//! # }
+//! ```
 
 mod command;
 mod config;
+mod pins;
 
+use core::marker::PhantomData;
 use embedded_hal::{
     delay::DelayNs,
-    digital::{InputPin, OutputPin, PinState},
-    spi::SpiBus,
+    digital::{Error as _, InputPin, OutputPin, PinState},
+    spi::{SpiBus, SpiDevice},
 };
 
+use crate::buffer_len;
+use crate::color::TriColor;
+use crate::config::{EpdConfig, DEFAULT_POWER_ON_DELAY_US, DEFAULT_SPI_CHUNK_SIZE};
+use crate::error::Error;
+use crate::power::NoPowerPin;
 pub use crate::rect::Rect;
+use crate::traits::{
+    BusyPolling, Capabilities, ColorKind, InternalWiAdditions, RefreshLut, WaveshareDisplay,
+    WaveshareDisplayNew, WaveshareThreeColorDisplay,
+};
+use crate::window::{check_window, check_window_buffer};
 use command::Command;
 pub use config::*;
+pub use pins::*;
+
+/// Full size buffer for use with the 12in48b v2 EPD
+///
+/// It takes about 320 KB, which only fits in the RAM of bigger hosts (use `Box` to keep it
+/// off the stack). Otherwise render the frame in horizontal bands, see the module documentation.
+#[cfg(feature = "graphics")]
+pub type Display12in48b = crate::graphics::Display<
+    WIDTH,
+    HEIGHT,
+    false,
+    { buffer_len(WIDTH as usize, HEIGHT as usize * 2) },
+    TriColor,
+>;
 
 /// A collection of peripherals controlling the EPD
 ///
@@ -73,6 +137,11 @@ where
 pub const WIDTH: u32 = 1304;
 /// EPD height
 pub const HEIGHT: u32 = 984;
+/// Default Background Color
+pub const DEFAULT_BACKGROUND_COLOR: TriColor = TriColor::White;
+
+/// Number of bytes of one color plane
+const PLANE_SIZE: usize = buffer_len(WIDTH as usize, HEIGHT as usize);
 
 const S2_WIDTH: u32 = 648;
 const S2_HEIGHT: u32 = 492;
@@ -119,6 +188,8 @@ const CS_M2: CS = 0b0100;
 const CS_S2: CS = 0b1000;
 const CS_ALL: CS = CS_M1 | CS_S1 | CS_M2 | CS_S2;
 const CS_DATA: CS = 0b10000;
+// forces the pins to be set on the next selection
+const CS_UNKNOWN: CS = 0xFF;
 
 /// Waveshare 12.48"(B)
 pub struct EpdDriver<INPUT, OUTPUT, SPI, DELAY>
@@ -131,6 +202,8 @@ where
     peris: Peripherals<INPUT, OUTPUT, SPI>,
     delay: DELAY,
     control_state: CS,
+    /// maximum number of us to wait for the device to be idle, None waits forever
+    busy_timeout_us: Option<u32>,
 }
 
 impl<INPUT, OUTPUT, SPI, DELAY> EpdDriver<INPUT, OUTPUT, SPI, DELAY>
@@ -151,9 +224,18 @@ where
             peris,
             delay,
             control_state: 0,
+            busy_timeout_us: None,
         }
     }
 
+    /// Sets the maximum number of us to wait for the sub-displays to be idle
+    ///
+    /// When one of them is still busy after that time, [`Error::BusyTimeout`] is returned
+    /// instead of waiting forever. None (the default) waits forever.
+    pub fn set_busy_timeout(&mut self, timeout_us: Option<u32>) {
+        self.busy_timeout_us = timeout_us;
+    }
+
     /// Consumes EpdDriver, releasing peripherals to the caller.
     pub fn into_peripherals(self) -> Peripherals<INPUT, OUTPUT, SPI> {
         self.peris
@@ -161,46 +243,33 @@ where
 
     /// Reset the display, potentially waking it up from deep sleep.
     /// Normally should be followed by a call to [`init()`](EpdDriver::init).
-    pub fn reset(&mut self) -> Result<(), OUTPUT::Error> {
-        drop(self.peris.m1_cs.set_high());
-        drop(self.peris.s1_cs.set_high());
-        drop(self.peris.m2_cs.set_high());
-        drop(self.peris.s2_cs.set_high());
-        drop(self.peris.m1s1_dc.set_low());
-        drop(self.peris.m2s2_dc.set_low());
-        self.control_state = 0;
+    pub fn reset(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.deselect()?;
 
-        self.peris.m1s1_rst.set_high()?;
-        self.peris.m2s2_rst.set_high()?;
+        let (m1s1, m2s2) = (&mut self.peris.m1s1_rst, &mut self.peris.m2s2_rst);
+        m1s1.set_high().map_err(|e| Error::Pin(e.kind()))?;
+        m2s2.set_high().map_err(|e| Error::Pin(e.kind()))?;
         self.delay.delay_ms(1);
 
-        self.peris.m1s1_rst.set_low()?;
+        m1s1.set_low().map_err(|e| Error::Pin(e.kind()))?;
         self.delay.delay_us(100); // min RST low = 50us
-        self.peris.m1s1_rst.set_high()?;
+        m1s1.set_high().map_err(|e| Error::Pin(e.kind()))?;
         self.delay.delay_ms(100); // min wait after RST = 10ms
 
-        self.peris.m2s2_rst.set_low()?;
+        m2s2.set_low().map_err(|e| Error::Pin(e.kind()))?;
         self.delay.delay_us(100);
-        self.peris.m2s2_rst.set_high()?;
+        m2s2.set_high().map_err(|e| Error::Pin(e.kind()))?;
         self.delay.delay_ms(100);
 
         Ok(())
     }
 
     /// Initialize display registers.
-    pub fn init(&mut self, config: &Config) -> Result<(), SPI::Error> {
+    pub fn init(&mut self, config: &Config) -> Result<(), Error<SPI::Error>> {
         // booster soft start
         self.cmd_with_data(CS_ALL, Command::BoosterSoftStart, &[0x17, 0x17, 0x39, 0x17])?;
 
         // resolution setting
-        fn resolution_data(rect: Rect) -> [u8; 4] {
-            [
-                (rect.w / 256) as u8,
-                (rect.w % 256) as u8,
-                (rect.h / 256) as u8,
-                (rect.h % 256) as u8,
-            ]
-        }
         self.cmd_with_data(CS_M1, Command::TconResolution, &resolution_data(M1_RECT))?;
         self.cmd_with_data(CS_S1, Command::TconResolution, &resolution_data(S1_RECT))?;
         self.cmd_with_data(CS_M2, Command::TconResolution, &resolution_data(M2_RECT))?;
@@ -218,37 +287,14 @@ where
     }
 
     /// Set data "polarity", waveform lookup table mode, etc, without re-initializing anything else.
-    pub fn set_mode(&mut self, config: &Config) -> Result<(), SPI::Error> {
-        let ddx = match (config.inverted_r, config.inverted_kw) {
-            (false, true) => 0b00,
-            (false, false) => 0b01,
-            (true, true) => 0b10,
-            (true, false) => 0b11,
-        };
-        let ddx0 = ddx & 1 == 1;
-        let bdv = match (ddx0, config.border_lut) {
-            (false, BorderLUT::LUTBD) => 0b00,
-            (false, BorderLUT::LUTR) => 0b01,
-            (false, BorderLUT::LUTW) => 0b10,
-            (false, BorderLUT::LUTK) => 0b11,
-            (true, BorderLUT::LUTK) => 0b00,
-            (true, BorderLUT::LUTW) => 0b01,
-            (true, BorderLUT::LUTR) => 0b10,
-            (true, BorderLUT::LUTBD) => 0b11,
-        };
-
-        let reg = (config.external_lut as u8) << 5;
+    pub fn set_mode(&mut self, config: &Config) -> Result<(), Error<SPI::Error>> {
+        let (reg, vcom) = mode_data(config);
         self.cmd_with_data(CS_M1, Command::PanelSetting, &[reg | 0x0F])?;
         self.cmd_with_data(CS_S1, Command::PanelSetting, &[reg | 0x0F])?;
         self.cmd_with_data(CS_M2, Command::PanelSetting, &[reg | 0x03])?;
         self.cmd_with_data(CS_S2, Command::PanelSetting, &[reg | 0x03])?;
 
-        let bdv = bdv << 4;
-        self.cmd_with_data(
-            CS_ALL,
-            Command::VcomAndDataIntervalSetting,
-            &[bdv | ddx, 0x07],
-        )?;
+        self.cmd_with_data(CS_ALL, Command::VcomAndDataIntervalSetting, &[vcom, 0x07])?;
 
         self.flush()
     }
@@ -258,29 +304,42 @@ where
     /// - data2 contains the red/not red channel.
     ///
     /// `pixels` may contain a lesser number of rows than the window being written,
-    /// in which case it will be treated as circular.
-    pub fn write_data1(&mut self, pixels: &[u8]) -> Result<(), SPI::Error> {
+    /// in which case it will be treated as circular. Returns `Error::InvalidBuffer` when it
+    /// is empty or doesn't hold whole rows.
+    pub fn write_data1(&mut self, pixels: &[u8]) -> Result<(), Error<SPI::Error>> {
         self.write_window_data(Command::DataStartTransmission1, FULL_RECT, pixels)?;
         self.flush()
     }
 
     /// Fill data2 buffer with pixels.
     /// See also [`write_data1`](EpdDriver::write_data1).
-    pub fn write_data2(&mut self, pixels: &[u8]) -> Result<(), SPI::Error> {
+    pub fn write_data2(&mut self, pixels: &[u8]) -> Result<(), Error<SPI::Error>> {
         self.write_window_data(Command::DataStartTransmission2, FULL_RECT, pixels)?;
         self.flush()
     }
 
     /// Fill a window in the data1 buffer with pixels.
     /// See also [`write_data1`](EpdDriver::write_data1).
-    pub fn write_data1_partial(&mut self, window: Rect, pixels: &[u8]) -> Result<(), SPI::Error> {
+    ///
+    /// Returns `Error::InvalidWindow` when the window isn't 8-aligned horizontally or doesn't
+    /// lie within the display, and `Error::InvalidBuffer` when `pixels` doesn't hold exactly
+    /// the rows of the window.
+    pub fn write_data1_partial(
+        &mut self,
+        window: Rect,
+        pixels: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
         self.write_partial(Command::DataStartTransmission1, window, pixels)?;
         self.flush()
     }
 
     /// Fill a window in the data2 buffer with pixels.
-    /// See also [`write_data1`](EpdDriver::write_data1).
-    pub fn write_data2_partial(&mut self, window: Rect, pixels: &[u8]) -> Result<(), SPI::Error> {
+    /// See also [`write_data1_partial`](EpdDriver::write_data1_partial).
+    pub fn write_data2_partial(
+        &mut self,
+        window: Rect,
+        pixels: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
         self.write_partial(Command::DataStartTransmission2, window, pixels)?;
         self.flush()
     }
@@ -290,41 +349,46 @@ where
     /// If LUT data is shorter than expected, the rest is filled with zeroes.<br>
     /// Note that stored lookup tables need to be activated by setting
     /// [`Config::external_lut`](config::Config::external_lut)`=true`.
-    pub fn set_lutc(&mut self, data: &[u8]) -> Result<(), SPI::Error> {
+    pub fn set_lutc(&mut self, data: &[u8]) -> Result<(), Error<SPI::Error>> {
         self.set_lut(Command::LutC, data, 60)
     }
 
     /// Store White-to-White Look-Up Table.
     /// See also [`write_data1`](EpdDriver::set_lutc).
-    pub fn set_lutww(&mut self, data: &[u8]) -> Result<(), SPI::Error> {
+    pub fn set_lutww(&mut self, data: &[u8]) -> Result<(), Error<SPI::Error>> {
         self.set_lut(Command::LutWW, data, 42)
     }
 
     /// Store Black-to-White (KW mode) / Red (KWR mode) Look-Up Table.
     /// See also [`write_data1`](EpdDriver::set_lutc).
-    pub fn set_lutkw_lutr(&mut self, data: &[u8]) -> Result<(), SPI::Error> {
+    pub fn set_lutkw_lutr(&mut self, data: &[u8]) -> Result<(), Error<SPI::Error>> {
         self.set_lut(Command::LutKW_LutR, data, 60)
     }
 
     /// Store White-to-Black (KW mode) / White (KWR mode) Look-Up Table.
     /// See also [`write_data1`](EpdDriver::set_lutc).
-    pub fn set_lutwk_lutw(&mut self, data: &[u8]) -> Result<(), SPI::Error> {
+    pub fn set_lutwk_lutw(&mut self, data: &[u8]) -> Result<(), Error<SPI::Error>> {
         self.set_lut(Command::LutWK_LutW, data, 60)
     }
 
     /// Store Black-to-Black (KW mode) / Black (KWR mode) Look-Up Table.
     /// See also [`write_data1`](EpdDriver::set_lutc).
-    pub fn set_lutkk_lutk(&mut self, data: &[u8]) -> Result<(), SPI::Error> {
+    pub fn set_lutkk_lutk(&mut self, data: &[u8]) -> Result<(), Error<SPI::Error>> {
         self.set_lut(Command::LutKK_LutK, data, 60)
     }
 
     /// Store Border Look-Up Table.
     /// See also [`write_data1`](EpdDriver::set_lutc).
-    pub fn set_lutbd(&mut self, data: &[u8]) -> Result<(), SPI::Error> {
+    pub fn set_lutbd(&mut self, data: &[u8]) -> Result<(), Error<SPI::Error>> {
         self.set_lut(Command::LutBD, data, 42)
     }
 
-    fn set_lut(&mut self, cmd: Command, data: &[u8], reqd_len: usize) -> Result<(), SPI::Error> {
+    fn set_lut(
+        &mut self,
+        cmd: Command,
+        data: &[u8],
+        reqd_len: usize,
+    ) -> Result<(), Error<SPI::Error>> {
         self.cmd_with_data(CS_ALL, cmd, data)?;
         if data.len() < reqd_len {
             let zeroes = [0; 60];
@@ -334,17 +398,17 @@ where
     }
 
     /// Refresh the entire display.
-    pub fn refresh_display(&mut self) -> Result<(), SPI::Error> {
+    pub fn refresh_display(&mut self) -> Result<(), Error<SPI::Error>> {
         self.begin_refresh_display()?;
-        drop(self.wait_ready(CS_ALL));
+        self.wait_ready(CS_ALL)?;
         Ok(())
     }
 
     /// Asynchronous version of [`refresh_display`](EpdDriver::refresh_display).
     /// Use [`is_busy`](EpdDriver::is_busy) to poll for completion.
-    pub fn begin_refresh_display(&mut self) -> Result<(), SPI::Error> {
+    pub fn begin_refresh_display(&mut self) -> Result<(), Error<SPI::Error>> {
         self.cmd(CS_ALL, Command::PowerOn)?;
-        drop(self.wait_ready(CS_ALL));
+        self.wait_ready(CS_ALL)?;
         // Appears to be required to reliably trigger display refresh after a power-on.
         self.delay.delay_ms(100);
 
@@ -356,20 +420,20 @@ where
     /// Refresh the specified sub-window of the display.  
     ///
     /// Technically, this works, however, after 2+ partial updates, the rest of the displayed image becomes visibly degraded.
-    pub fn refresh_display_partial(&mut self, window: Rect) -> Result<(), SPI::Error> {
+    pub fn refresh_display_partial(&mut self, window: Rect) -> Result<(), Error<SPI::Error>> {
         self.begin_refresh_display_partial(window)?;
 
-        drop(self.wait_ready(CS_ALL));
+        self.wait_ready(CS_ALL)?;
         Ok(())
     }
 
     /// Asynchronous version of [`refresh_display_partial`](EpdDriver::refresh_display_partial).
     /// Use [`is_busy`](EpdDriver::is_busy) to poll for completion.
-    pub fn begin_refresh_display_partial(&mut self, window: Rect) -> Result<(), SPI::Error> {
+    pub fn begin_refresh_display_partial(&mut self, window: Rect) -> Result<(), Error<SPI::Error>> {
         self.setup_partial_windows(window)?;
 
         self.cmd(CS_ALL, Command::PowerOn)?;
-        drop(self.wait_ready(CS_ALL));
+        self.wait_ready(CS_ALL)?;
         self.delay.delay_ms(100);
 
         self.cmd(CS_ALL, Command::PartialIn)?;
@@ -381,55 +445,30 @@ where
 
     /// Turn off booster, controller, source driver, gate driver, VCOM, and temperature sensor.
     /// However, the contents of the data memory buffers will be retained.
-    pub fn power_off(&mut self) -> Result<(), SPI::Error> {
+    pub fn power_off(&mut self) -> Result<(), Error<SPI::Error>> {
         self.cmd(CS_ALL, Command::PowerOff)?;
-        drop(self.wait_ready(CS_ALL));
+        self.wait_ready(CS_ALL)?;
 
         self.flush()
     }
 
     /// Put display into deep sleep.  Only [`reset()`](EpdDriver::reset) can bring it out of this state.
     /// The contents of the data memory buffers will be lost.
-    pub fn hibernate(&mut self) -> Result<(), SPI::Error> {
+    pub fn hibernate(&mut self) -> Result<(), Error<SPI::Error>> {
         self.cmd(CS_ALL, Command::PowerOff)?;
-        drop(self.wait_ready(CS_ALL));
+        self.wait_ready(CS_ALL)?;
 
         self.cmd_with_data(CS_ALL, Command::DeepSleep, &[0xA5])?;
 
         self.flush()
     }
 
-    fn setup_partial_windows(&mut self, window: Rect) -> Result<(), SPI::Error> {
+    fn setup_partial_windows(&mut self, window: Rect) -> Result<(), Error<SPI::Error>> {
         let s2_part = window.intersect(S2_RECT).sub_offset(S2_RECT.x, S2_RECT.y);
         let m2_part = window.intersect(M2_RECT).sub_offset(M2_RECT.x, M2_RECT.y);
         let m1_part = window.intersect(M1_RECT).sub_offset(M1_RECT.x, M1_RECT.y);
         let s1_part = window.intersect(S1_RECT).sub_offset(S1_RECT.x, S1_RECT.y);
 
-        fn partial_window_data(window: Rect, reverse_scan: Option<u32>) -> [u8; 9] {
-            if window.is_empty() {
-                [0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00, 0xFF, 0xFF, 0x01]
-            } else {
-                let start_x = match reverse_scan {
-                    Some(width) => width - window.x - window.w,
-                    None => window.x,
-                };
-                let end_x = start_x + window.w - 1;
-                let start_y = window.y;
-                let end_y = start_y + window.h - 1;
-                [
-                    (start_x / 256) as u8,
-                    (start_x % 256) as u8,
-                    (end_x / 256) as u8,
-                    (end_x % 256) as u8,
-                    (start_y / 256) as u8,
-                    (start_y % 256) as u8,
-                    (end_y / 256) as u8,
-                    (end_y % 256) as u8,
-                    0x01,
-                ]
-            }
-        }
-
         self.cmd_with_data(
             CS_S2,
             Command::PartialWindow,
@@ -459,10 +498,9 @@ where
         transmission_cmd: Command,
         window: Rect,
         pixels: &[u8],
-    ) -> Result<(), SPI::Error> {
-        if window.x % 8 != 0 || window.w % 8 != 0 {
-            panic!("Window is not 8-aligned horizontally");
-        }
+    ) -> Result<(), Error<SPI::Error>> {
+        check_window(window.x, window.y, window.w, window.h, WIDTH, HEIGHT)?;
+        check_window_buffer(pixels, window.w, window.h, 1)?;
 
         self.cmd(CS_ALL, Command::PartialIn)?;
 
@@ -478,8 +516,10 @@ where
         transmission_cmd: Command,
         window: Rect,
         pixels: &[u8],
-    ) -> Result<(), SPI::Error> {
-        assert!(!pixels.is_empty());
+    ) -> Result<(), Error<SPI::Error>> {
+        if pixels.is_empty() || pixels.len() % (window.w / 8) as usize != 0 {
+            return Err(Error::InvalidBuffer);
+        }
 
         let s2_part = window.intersect(S2_RECT);
         let s1_part = window.intersect(S1_RECT);
@@ -542,7 +582,7 @@ where
         Ok(())
     }
 
-    fn cmd(&mut self, chips: CS, command: Command) -> Result<(), SPI::Error> {
+    fn cmd(&mut self, chips: CS, command: Command) -> Result<(), Error<SPI::Error>> {
         self.spi_write(chips, &[command as u8])
     }
 
@@ -551,93 +591,108 @@ where
         chips: CS,
         command: Command,
         data: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.spi_write(chips, &[command as u8])?;
         self.spi_write(chips | CS_DATA, data)
     }
 
     // Set control pins to the specified state, then send data via SPI.
-    fn spi_write(&mut self, control: CS, data: &[u8]) -> Result<(), SPI::Error> {
+    fn spi_write(&mut self, control: CS, data: &[u8]) -> Result<(), Error<SPI::Error>> {
         if self.control_state != control {
-            fn pin_state(high: bool) -> PinState {
-                if high {
-                    PinState::High
-                } else {
-                    PinState::Low
-                }
-            }
-
-            self.peris.spi.flush()?;
+            self.peris.spi.flush().map_err(Error::Spi)?;
             self.delay.delay_ns(100); // Tscc = 20ns, Tchw = 40ns
 
             // CS is active low
-            drop(self.peris.m1_cs.set_state(pin_state(control & CS_M1 == 0)));
-            drop(self.peris.s1_cs.set_state(pin_state(control & CS_S1 == 0)));
-            drop(self.peris.m2_cs.set_state(pin_state(control & CS_M2 == 0)));
-            drop(self.peris.s2_cs.set_state(pin_state(control & CS_S2 == 0)));
+            let peris = &mut self.peris;
+            for (pin, chip) in [
+                (&mut peris.m1_cs, CS_M1),
+                (&mut peris.s1_cs, CS_S1),
+                (&mut peris.m2_cs, CS_M2),
+                (&mut peris.s2_cs, CS_S2),
+            ] {
+                pin.set_state(PinState::from(control & chip == 0))
+                    .map_err(|e| Error::Pin(e.kind()))?;
+            }
 
             // DC is active high
-            let dc = pin_state(control & CS_DATA != 0);
-            drop(self.peris.m1s1_dc.set_state(dc));
-            drop(self.peris.m2s2_dc.set_state(dc));
+            let dc = PinState::from(control & CS_DATA != 0);
+            for pin in [&mut peris.m1s1_dc, &mut peris.m2s2_dc] {
+                pin.set_state(dc).map_err(|e| Error::Pin(e.kind()))?;
+            }
 
             self.delay.delay_ns(100); // Tcss = 60ns, Tsds = 30ns
             self.control_state = control;
         }
 
-        self.peris.spi.write(data)
+        self.peris.spi.write(data).map_err(Error::Spi)
     }
 
     // Flush SPI, reset control pins to the default state.
-    fn flush(&mut self) -> Result<(), SPI::Error> {
-        self.peris.spi.flush()?;
-        drop(self.peris.m1_cs.set_high());
-        drop(self.peris.s1_cs.set_high());
-        drop(self.peris.m2_cs.set_high());
-        drop(self.peris.s2_cs.set_high());
-        drop(self.peris.m1s1_dc.set_low());
-        drop(self.peris.m2s2_dc.set_low());
+    fn flush(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.peris.spi.flush().map_err(Error::Spi)?;
+        self.deselect()
+    }
+
+    // Reset control pins to the default state.
+    fn deselect(&mut self) -> Result<(), Error<SPI::Error>> {
+        let peris = &mut self.peris;
+        for pin in [
+            &mut peris.m1_cs,
+            &mut peris.s1_cs,
+            &mut peris.m2_cs,
+            &mut peris.s2_cs,
+        ] {
+            pin.set_high().map_err(|e| Error::Pin(e.kind()))?;
+        }
+        for pin in [&mut peris.m1s1_dc, &mut peris.m2s2_dc] {
+            pin.set_low().map_err(|e| Error::Pin(e.kind()))?;
+        }
         self.control_state = 0;
         Ok(())
     }
 
-    fn wait_ready(&mut self, chips: CS) -> Result<(), INPUT::Error> {
+    fn wait_ready(&mut self, chips: CS) -> Result<(), Error<SPI::Error>> {
+        const POLL_US: u32 = 200_000;
+        let mut waited_us: u32 = 0;
         while self.busy_chips(chips)? != 0 {
-            self.delay.delay_ms(200);
+            if matches!(self.busy_timeout_us, Some(timeout) if waited_us >= timeout) {
+                return Err(Error::BusyTimeout);
+            }
+            self.delay.delay_us(POLL_US);
+            waited_us = waited_us.saturating_add(POLL_US);
         }
         Ok(())
     }
 
-    fn busy_chips(&mut self, chips: CS) -> Result<CS, INPUT::Error> {
+    fn busy_chips(&mut self, chips: CS) -> Result<CS, Error<SPI::Error>> {
+        let peris = &mut self.peris;
         let mut busy = 0;
-        if chips & CS_M1 != 0 && self.peris.m1_busy.is_low()? {
-            busy |= CS_M1;
-        }
-        if chips & CS_S1 != 0 && self.peris.s1_busy.is_low()? {
-            busy |= CS_S1;
-        }
-        if chips & CS_M2 != 0 && self.peris.m2_busy.is_low()? {
-            busy |= CS_M2;
-        }
-        if chips & CS_S2 != 0 && self.peris.s2_busy.is_low()? {
-            busy |= CS_S2;
+        for (pin, chip) in [
+            (&mut peris.m1_busy, CS_M1),
+            (&mut peris.s1_busy, CS_S1),
+            (&mut peris.m2_busy, CS_M2),
+            (&mut peris.s2_busy, CS_S2),
+        ] {
+            if chips & chip != 0 && pin.is_low().map_err(|e| Error::Pin(e.kind()))? {
+                busy |= chip;
+            }
         }
         Ok(busy)
     }
 
     /// Poll readiness status of all sub-displays and return a bit mask of the busy ones.
-    pub fn get_busy(&mut self) -> u8 {
-        self.busy_chips(CS_ALL).unwrap()
+    pub fn get_busy(&mut self) -> Result<u8, Error<SPI::Error>> {
+        self.busy_chips(CS_ALL)
     }
 
     /// Check if any of the sub-displays is busy.
-    pub fn is_busy(&mut self) -> bool {
-        self.busy_chips(CS_ALL).unwrap() != 0
+    pub fn is_busy(&mut self) -> Result<bool, Error<SPI::Error>> {
+        Ok(self.busy_chips(CS_ALL)? != 0)
     }
 
     /// Query and return the status byte of each sub-display.
     /// Order: \[M1, S1, M2, S2\].
-    pub fn get_status(&mut self) -> Result<[u8; 4], SPI::Error> {
+    pub fn get_status(&mut self) -> Result<[u8; 4], Error<SPI::Error>> {
         self.control_state = 0xFF;
         let mut status = [0u8; 4];
         for i in 0..4 {
@@ -648,24 +703,844 @@ where
                 _ => (&mut self.peris.s2_cs, &mut self.peris.m2s2_dc),
             };
             // Request status
-            drop(cs.set_low());
-            drop(dc.set_low());
+            cs.set_low().map_err(|e| Error::Pin(e.kind()))?;
+            dc.set_low().map_err(|e| Error::Pin(e.kind()))?;
             self.delay.delay_ns(100); // Tcss = 60ns
-            self.peris.spi.write(&[Command::GetStatus as u8])?;
-            self.peris.spi.flush()?;
+            self.peris
+                .spi
+                .write(&[Command::GetStatus as u8])
+                .map_err(Error::Spi)?;
+            self.peris.spi.flush().map_err(Error::Spi)?;
             self.delay.delay_ns(100); // Tsds = 30ns
 
             // Read status
-            drop(dc.set_high());
+            dc.set_high().map_err(|e| Error::Pin(e.kind()))?;
             self.delay.delay_ns(100); // Tsdh = 30ns
-            self.peris.spi.read(&mut status[i..i + 1])?;
+            self.peris
+                .spi
+                .read(&mut status[i..i + 1])
+                .map_err(Error::Spi)?;
             self.delay.delay_ns(100); // Tscc = 20ns
-            drop(dc.set_low());
+            dc.set_low().map_err(|e| Error::Pin(e.kind()))?;
 
-            drop(cs.set_high());
+            cs.set_high().map_err(|e| Error::Pin(e.kind()))?;
             self.delay.delay_ns(100); // Tchw = 40ns
         }
         self.control_state = 0;
         Ok(status)
     }
 }
+
+/// Data of the [`Command::TconResolution`] command for a sub-display
+fn resolution_data(rect: Rect) -> [u8; 4] {
+    [
+        (rect.w / 256) as u8,
+        (rect.w % 256) as u8,
+        (rect.h / 256) as u8,
+        (rect.h % 256) as u8,
+    ]
+}
+
+/// Register bits of [`Command::PanelSetting`] and first byte of
+/// [`Command::VcomAndDataIntervalSetting`] for a configuration
+fn mode_data(config: &Config) -> (u8, u8) {
+    let ddx = match (config.inverted_r, config.inverted_kw) {
+        (false, true) => 0b00,
+        (false, false) => 0b01,
+        (true, true) => 0b10,
+        (true, false) => 0b11,
+    };
+    let ddx0 = ddx & 1 == 1;
+    let bdv = match (ddx0, config.border_lut) {
+        (false, BorderLUT::LUTBD) => 0b00,
+        (false, BorderLUT::LUTR) => 0b01,
+        (false, BorderLUT::LUTW) => 0b10,
+        (false, BorderLUT::LUTK) => 0b11,
+        (true, BorderLUT::LUTK) => 0b00,
+        (true, BorderLUT::LUTW) => 0b01,
+        (true, BorderLUT::LUTR) => 0b10,
+        (true, BorderLUT::LUTBD) => 0b11,
+    };
+
+    let reg = (config.external_lut as u8) << 5;
+    (reg, bdv << 4 | ddx)
+}
+
+/// Data of the [`Command::PartialWindow`] command for a sub-display
+fn partial_window_data(window: Rect, reverse_scan: Option<u32>) -> [u8; 9] {
+    if window.is_empty() {
+        [0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00, 0xFF, 0xFF, 0x01]
+    } else {
+        let start_x = match reverse_scan {
+            Some(width) => width - window.x - window.w,
+            None => window.x,
+        };
+        let end_x = start_x + window.w - 1;
+        let start_y = window.y;
+        let end_y = start_y + window.h - 1;
+        [
+            (start_x / 256) as u8,
+            (start_x % 256) as u8,
+            (end_x / 256) as u8,
+            (end_x % 256) as u8,
+            (start_y / 256) as u8,
+            (start_y % 256) as u8,
+            (end_y / 256) as u8,
+            (end_y % 256) as u8,
+            0x01,
+        ]
+    }
+}
+
+/// Waveshare 12.48"(B) driver implementing the common traits
+///
+/// Unlike [`EpdDriver`] it doesn't own the SPI bus: pass a [`SpiDevice`] without chip select
+/// (e.g. `ExclusiveDevice::new_no_delay(bus, NoCs)`), the chip selects of the sub-displays are
+/// part of the [`ControlPins`].
+pub struct Epd12in48b<SPI, INPUT, OUTPUT, DELAY, PWR = NoPowerPin> {
+    busy: BusyPins<INPUT>,
+    control: ControlPins<OUTPUT>,
+    rst: ResetPins<OUTPUT>,
+    control_state: CS,
+    config: Config,
+    color: TriColor,
+    /// number of us to sleep between two busy probes
+    delay_us: u32,
+    /// maximum number of us to wait for the device to be idle, None waits forever
+    busy_timeout_us: Option<u32>,
+    /// number of us the reset pins are held low
+    reset_pulse_us: u32,
+    /// number of us to wait after resetting a pair of sub-displays
+    reset_settle_us: u32,
+    /// maximum number of bytes per spi transfer, 0 for no limit
+    chunk_size: usize,
+    /// pin switching the power rail of the panel, high when powered
+    power: Option<PWR>,
+    /// whether the power rail was switched on
+    powered: bool,
+    /// number of us to wait after switching the power rail on
+    power_on_delay_us: u32,
+    _spi: PhantomData<SPI>,
+    _delay: PhantomData<DELAY>,
+}
+
+impl<SPI, INPUT, OUTPUT, DELAY, PWR>
+    InternalWiAdditions<SPI, BusyPins<INPUT>, ControlPins<OUTPUT>, ResetPins<OUTPUT>, DELAY>
+    for Epd12in48b<SPI, INPUT, OUTPUT, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    INPUT: InputPin,
+    OUTPUT: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.reset(delay)?;
+
+        // see EpdDriver::init
+        self.cmd_with_data(
+            spi,
+            delay,
+            CS_ALL,
+            Command::BoosterSoftStart,
+            &[0x17, 0x17, 0x39, 0x17],
+        )?;
+        for (chips, rect) in [
+            (CS_M1, M1_RECT),
+            (CS_S1, S1_RECT),
+            (CS_M2, M2_RECT),
+            (CS_S2, S2_RECT),
+        ] {
+            self.cmd_with_data(
+                spi,
+                delay,
+                chips,
+                Command::TconResolution,
+                &resolution_data(rect),
+            )?;
+        }
+        self.cmd_with_data(spi, delay, CS_ALL, Command::DualSPI, &[0x20])?;
+        self.cmd_with_data(spi, delay, CS_ALL, Command::TconSetting, &[0x22])?;
+        self.cmd_with_data(spi, delay, CS_ALL, Command::PowerSaving, &[0x00])?;
+        self.cmd_with_data(spi, delay, CS_ALL, Command::CascadeSetting, &[0x03])?;
+        self.cmd_with_data(spi, delay, CS_ALL, Command::ForceTemperature, &[25])?;
+
+        self.send_mode(spi, delay)?;
+        self.release(delay)
+    }
+}
+
+impl<SPI, INPUT, OUTPUT, DELAY, PWR>
+    WaveshareThreeColorDisplay<SPI, BusyPins<INPUT>, ControlPins<OUTPUT>, ResetPins<OUTPUT>, DELAY>
+    for Epd12in48b<SPI, INPUT, OUTPUT, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    INPUT: InputPin,
+    OUTPUT: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    fn update_color_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        black: &[u8],
+        chromatic: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.update_achromatic_frame(spi, delay, black)?;
+        self.update_chromatic_frame(spi, delay, chromatic)
+    }
+
+    fn update_achromatic_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        black: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        if black.len() != PLANE_SIZE {
+            return Err(Error::InvalidBuffer);
+        }
+        self.write_window_data(
+            spi,
+            delay,
+            Command::DataStartTransmission1,
+            FULL_RECT,
+            black,
+        )?;
+        self.release(delay)
+    }
+
+    fn update_chromatic_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        chromatic: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        if chromatic.len() != PLANE_SIZE {
+            return Err(Error::InvalidBuffer);
+        }
+        self.write_window_data(
+            spi,
+            delay,
+            Command::DataStartTransmission2,
+            FULL_RECT,
+            chromatic,
+        )?;
+        self.release(delay)
+    }
+}
+
+impl<SPI, INPUT, OUTPUT, DELAY, PWR>
+    WaveshareDisplayNew<SPI, BusyPins<INPUT>, ControlPins<OUTPUT>, ResetPins<OUTPUT>, DELAY, PWR>
+    for Epd12in48b<SPI, INPUT, OUTPUT, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    INPUT: InputPin,
+    OUTPUT: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    /// Creates the driver with the default [`Config`], the border color of `config` selects
+    /// its [`BorderLUT`]
+    ///
+    /// The busy poll defaults to 200ms, the reset pulse to 100us and the reset settle time to
+    /// 100ms per pair of sub-displays.
+    fn with_config(
+        spi: &mut SPI,
+        busy: BusyPins<INPUT>,
+        control: ControlPins<OUTPUT>,
+        rst: ResetPins<OUTPUT>,
+        delay: &mut DELAY,
+        config: EpdConfig<PWR>,
+    ) -> Result<Self, Error<SPI::Error>> {
+        let border_lut = match config.border() {
+            None => BorderLUT::LUTBD,
+            Some(TriColor::Black) => BorderLUT::LUTK,
+            Some(TriColor::White) => BorderLUT::LUTW,
+            Some(TriColor::Chromatic) => BorderLUT::LUTR,
+        };
+        let mut epd = Epd12in48b {
+            busy,
+            control,
+            rst,
+            control_state: CS_UNKNOWN,
+            config: Config {
+                border_lut,
+                ..Config::default()
+            },
+            color: DEFAULT_BACKGROUND_COLOR,
+            delay_us: config.busy_poll_us.unwrap_or(200_000),
+            busy_timeout_us: config.busy_timeout_us,
+            reset_pulse_us: config.reset_pulse_us.unwrap_or(100),
+            reset_settle_us: config.reset_settle_us.unwrap_or(100_000),
+            chunk_size: config.spi_chunk_size.unwrap_or(DEFAULT_SPI_CHUNK_SIZE),
+            power: config.power,
+            powered: false,
+            power_on_delay_us: config
+                .power_on_delay_us
+                .unwrap_or(DEFAULT_POWER_ON_DELAY_US),
+            _spi: PhantomData,
+            _delay: PhantomData,
+        };
+
+        epd.init(spi, delay)?;
+
+        Ok(epd)
+    }
+}
+
+impl<SPI, INPUT, OUTPUT, DELAY, PWR>
+    WaveshareDisplay<SPI, BusyPins<INPUT>, ControlPins<OUTPUT>, ResetPins<OUTPUT>, DELAY>
+    for Epd12in48b<SPI, INPUT, OUTPUT, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    INPUT: InputPin,
+    OUTPUT: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    type DisplayColor = TriColor;
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        color: ColorKind::TriColor,
+        partial_update: true,
+        refresh_luts: &[RefreshLut::Full],
+        quick_refresh: false,
+        busy: BusyPolling::Pin,
+    };
+
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.cmd(spi, delay, CS_ALL, Command::PowerOff)?;
+        self.wait_ready(delay)?;
+        self.cmd_with_data(spi, delay, CS_ALL, Command::DeepSleep, &[0xA5])?;
+        self.release(delay)
+    }
+
+    fn switch_power(&mut self, delay: &mut DELAY, on: bool) -> Result<(), Error<SPI::Error>> {
+        if self.power.is_none() {
+            return Err(Error::Unsupported);
+        }
+        if on {
+            return self.power_up(delay);
+        }
+        // no pin may power the panel once its rail is off
+        self.control_state = CS_UNKNOWN;
+        let pins = &mut self.control;
+        for pin in [
+            &mut pins.m1_cs,
+            &mut pins.s1_cs,
+            &mut pins.m2_cs,
+            &mut pins.s2_cs,
+        ] {
+            pin.set_low().map_err(|e| Error::Pin(e.kind()))?;
+        }
+        pins.set_low().map_err(|e| Error::Pin(e.kind()))?;
+        self.rst.set_low().map_err(|e| Error::Pin(e.kind()))?;
+        if let Some(power) = &mut self.power {
+            power.set_low().map_err(|e| Error::Pin(e.kind()))?;
+        }
+        self.powered = false;
+        Ok(())
+    }
+
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.init(spi, delay)
+    }
+
+    fn set_background_color(&mut self, color: TriColor) {
+        self.color = color;
+    }
+
+    fn background_color(&self) -> &TriColor {
+        &self.color
+    }
+
+    fn set_busy_timeout(&mut self, timeout_us: Option<u32>) {
+        self.busy_timeout_us = timeout_us;
+    }
+
+    fn width(&self) -> u32 {
+        WIDTH
+    }
+
+    fn height(&self) -> u32 {
+        HEIGHT
+    }
+
+    /// Transmits the black/white and the chromatic planes, as in the buffer of [`Display12in48b`]
+    fn update_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        if buffer.len() != 2 * PLANE_SIZE {
+            return Err(Error::InvalidBuffer);
+        }
+        let (black, chromatic) = buffer.split_at(PLANE_SIZE);
+        self.update_color_frame(spi, delay, black, chromatic)
+    }
+
+    /// Transmits the black/white and the chromatic planes of a window
+    ///
+    /// `x` and `width` must be multiples of 8, the planes follow each other in the buffer like
+    /// in a [`VarDisplay`](crate::graphics::VarDisplay) of that size.
+    fn update_partial_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        let window = Rect::new(x, y, width, height);
+        check_window(x, y, width, height, WIDTH, HEIGHT)?;
+        check_window_buffer(buffer, width, height, 2)?;
+        let (black, chromatic) = buffer.split_at(buffer.len() / 2);
+
+        self.cmd(spi, delay, CS_ALL, Command::PartialIn)?;
+        self.setup_partial_windows(spi, delay, window)?;
+        self.write_window_data(spi, delay, Command::DataStartTransmission1, window, black)?;
+        self.write_window_data(
+            spi,
+            delay,
+            Command::DataStartTransmission2,
+            window,
+            chromatic,
+        )?;
+        self.cmd(spi, delay, CS_ALL, Command::PartialOut)?;
+        self.release(delay)
+    }
+
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.begin_display_frame(spi, delay)?;
+        self.wait_ready(delay)
+    }
+
+    fn begin_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.cmd(spi, delay, CS_ALL, Command::PowerOn)?;
+        self.wait_ready(delay)?;
+        // see EpdDriver::begin_refresh_display
+        delay.delay_ms(100);
+
+        self.cmd(spi, delay, CS_ALL, Command::DisplayRefresh)?;
+        self.release(delay)
+    }
+
+    fn update_and_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.update_frame(spi, buffer, delay)?;
+        self.display_frame(spi, delay)
+    }
+
+    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        let (white, chromatic) = match self.color {
+            TriColor::Black => (false, false),
+            TriColor::White => (true, false),
+            TriColor::Chromatic => (false, true),
+        };
+        let black_byte = if white != self.config.inverted_kw {
+            0xFF
+        } else {
+            0x00
+        };
+        let chromatic_byte = if chromatic != self.config.inverted_r {
+            0xFF
+        } else {
+            0x00
+        };
+
+        // a single line is repeated for the whole window
+        let mut line = [black_byte; WIDTH as usize / 8];
+        self.write_window_data(
+            spi,
+            delay,
+            Command::DataStartTransmission1,
+            FULL_RECT,
+            &line,
+        )?;
+        line.fill(chromatic_byte);
+        self.write_window_data(
+            spi,
+            delay,
+            Command::DataStartTransmission2,
+            FULL_RECT,
+            &line,
+        )?;
+        self.release(delay)
+    }
+
+    /// Only the built-in lookup tables are supported, this does nothing
+    fn set_lut(
+        &mut self,
+        _spi: &mut SPI,
+        _delay: &mut DELAY,
+        _refresh_rate: Option<RefreshLut>,
+    ) -> Result<(), Error<SPI::Error>> {
+        Ok(())
+    }
+
+    fn wait_until_idle(
+        &mut self,
+        _spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.wait_ready(delay)
+    }
+
+    fn is_busy(&mut self, _spi: &mut SPI) -> Result<bool, Error<SPI::Error>> {
+        self.busy.is_low().map_err(|e| Error::Pin(e.kind()))
+    }
+}
+
+impl<SPI, INPUT, OUTPUT, DELAY, PWR> Epd12in48b<SPI, INPUT, OUTPUT, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    INPUT: InputPin,
+    OUTPUT: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    /// Set data "polarity", border and lookup table mode, see [`Config`]
+    pub fn set_config(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        config: Config,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.config = config;
+        self.send_mode(spi, delay)?;
+        self.release(delay)
+    }
+
+    /// Get the current configuration
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Consumes the driver, releasing the pins to the caller.
+    pub fn into_pins(self) -> (BusyPins<INPUT>, ControlPins<OUTPUT>, ResetPins<OUTPUT>) {
+        (self.busy, self.control, self.rst)
+    }
+
+    /// Switches the power rail on and waits for the panel to settle, unless it is already on
+    fn power_up(&mut self, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        match &mut self.power {
+            Some(power) if !self.powered => {
+                power.set_high().map_err(|e| Error::Pin(e.kind()))?;
+                delay.delay_us(self.power_on_delay_us);
+                self.powered = true;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn reset(&mut self, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.power_up(delay)?;
+        // see EpdDriver::reset
+        self.control_state = CS_UNKNOWN;
+        self.select(delay, 0)?;
+        self.rst.set_high().map_err(|e| Error::Pin(e.kind()))?;
+        delay.delay_ms(1);
+
+        self.rst.m1s1.set_low().map_err(|e| Error::Pin(e.kind()))?;
+        delay.delay_us(self.reset_pulse_us);
+        self.rst.m1s1.set_high().map_err(|e| Error::Pin(e.kind()))?;
+        delay.delay_us(self.reset_settle_us);
+
+        self.rst.m2s2.set_low().map_err(|e| Error::Pin(e.kind()))?;
+        delay.delay_us(self.reset_pulse_us);
+        self.rst.m2s2.set_high().map_err(|e| Error::Pin(e.kind()))?;
+        delay.delay_us(self.reset_settle_us);
+        Ok(())
+    }
+
+    fn send_mode(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        let (reg, vcom) = mode_data(&self.config);
+        self.cmd_with_data(
+            spi,
+            delay,
+            CS_M1 | CS_S1,
+            Command::PanelSetting,
+            &[reg | 0x0F],
+        )?;
+        self.cmd_with_data(
+            spi,
+            delay,
+            CS_M2 | CS_S2,
+            Command::PanelSetting,
+            &[reg | 0x03],
+        )?;
+        self.cmd_with_data(
+            spi,
+            delay,
+            CS_ALL,
+            Command::VcomAndDataIntervalSetting,
+            &[vcom, 0x07],
+        )
+    }
+
+    fn setup_partial_windows(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        window: Rect,
+    ) -> Result<(), Error<SPI::Error>> {
+        // the top sub-displays are scanned in reverse
+        for (chips, rect, reverse_scan) in [
+            (CS_S2, S2_RECT, Some(S2_RECT.w)),
+            (CS_M2, M2_RECT, Some(M2_RECT.w)),
+            (CS_M1, M1_RECT, None),
+            (CS_S1, S1_RECT, None),
+        ] {
+            let part = window.intersect(rect).sub_offset(rect.x, rect.y);
+            self.cmd_with_data(
+                spi,
+                delay,
+                chips,
+                Command::PartialWindow,
+                &partial_window_data(part, reverse_scan),
+            )?;
+        }
+        Ok(())
+    }
+
+    // Send data to each sub-display for the window area that overlaps with it.
+    // `pixels` may contain less rows than the window, it's repeated in that case.
+    fn write_window_data(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        transmission_cmd: Command,
+        window: Rect,
+        pixels: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        let row_bytes = (window.w / 8) as usize;
+        for (chips, rect) in [
+            (CS_S2, S2_RECT),
+            (CS_M2, M2_RECT),
+            (CS_M1, M1_RECT),
+            (CS_S1, S1_RECT),
+        ] {
+            let part = window.intersect(rect);
+            if part.is_empty() {
+                continue;
+            }
+            let skip = ((part.x - window.x) / 8) as usize;
+            let len = (part.w / 8) as usize;
+
+            self.cmd(spi, delay, chips, transmission_cmd)?;
+            for row in (part.y - window.y) as usize..(part.y - window.y + part.h) as usize {
+                let begin = (row * row_bytes) % pixels.len() + skip;
+                self.data(spi, delay, chips, &pixels[begin..begin + len])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn cmd(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        chips: CS,
+        command: Command,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.select(delay, chips)?;
+        spi.write(&[command as u8]).map_err(Error::Spi)
+    }
+
+    fn data(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        chips: CS,
+        data: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.select(delay, chips | CS_DATA)?;
+        if self.chunk_size == 0 {
+            return spi.write(data).map_err(Error::Spi);
+        }
+        for data_chunk in data.chunks(self.chunk_size) {
+            spi.write(data_chunk).map_err(Error::Spi)?;
+        }
+        Ok(())
+    }
+
+    fn cmd_with_data(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        chips: CS,
+        command: Command,
+        data: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.cmd(spi, delay, chips, command)?;
+        self.data(spi, delay, chips, data)
+    }
+
+    // Set chip select and dc pins to the specified state
+    fn select(&mut self, delay: &mut DELAY, control: CS) -> Result<(), Error<SPI::Error>> {
+        if self.control_state == control {
+            return Ok(());
+        }
+        delay.delay_ns(100); // Tscc = 20ns, Tchw = 40ns
+
+        // CS is active low
+        let pins = &mut self.control;
+        for (pin, chip) in [
+            (&mut pins.m1_cs, CS_M1),
+            (&mut pins.s1_cs, CS_S1),
+            (&mut pins.m2_cs, CS_M2),
+            (&mut pins.s2_cs, CS_S2),
+        ] {
+            pin.set_state(PinState::from(control & chip == 0))
+                .map_err(|e| Error::Pin(e.kind()))?;
+        }
+
+        // DC is active high
+        pins.set_state(PinState::from(control & CS_DATA != 0))
+            .map_err(|e| Error::Pin(e.kind()))?;
+
+        delay.delay_ns(100); // Tcss = 60ns, Tsds = 30ns
+        self.control_state = control;
+        Ok(())
+    }
+
+    // Deselect all sub-displays
+    fn release(&mut self, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.select(delay, 0)
+    }
+
+    fn wait_ready(&mut self, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        let mut waited_us: u32 = 0;
+        while self.busy.is_low().map_err(|e| Error::Pin(e.kind()))? {
+            let step = match self.busy_timeout_us {
+                Some(timeout) if waited_us >= timeout => return Err(Error::BusyTimeout),
+                Some(_) => self.delay_us.max(1),
+                None => self.delay_us,
+            };
+            if step > 0 {
+                delay.delay_us(step);
+            }
+            waited_us = waited_us.saturating_add(step);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn epd_size() {
+        assert_eq!(WIDTH, 1304);
+        assert_eq!(HEIGHT, 984);
+        assert_eq!(PLANE_SIZE, 160_392);
+        assert_eq!(DEFAULT_BACKGROUND_COLOR, TriColor::White);
+    }
+
+    use embedded_hal_mock::eh1::{
+        delay::NoopDelay,
+        digital::{Mock as PinMock, State, Transaction as PinTransaction},
+        spi::Mock as SpiMock,
+    };
+
+    type MockDriver = EpdDriver<PinMock, PinMock, SpiMock<u8>, NoopDelay>;
+
+    /// A driver whose sub-displays answer `busy` when their busy pin is read
+    fn mock_driver(busy: [&[PinTransaction]; 4]) -> MockDriver {
+        let pin = || PinMock::new(&[]);
+        let peris = Peripherals {
+            spi: SpiMock::new(&[]),
+            m1_cs: pin(),
+            s1_cs: pin(),
+            m2_cs: pin(),
+            s2_cs: pin(),
+            m1s1_dc: pin(),
+            m2s2_dc: pin(),
+            m1s1_rst: pin(),
+            m2s2_rst: pin(),
+            m1_busy: PinMock::new(busy[0]),
+            s1_busy: PinMock::new(busy[1]),
+            m2_busy: PinMock::new(busy[2]),
+            s2_busy: PinMock::new(busy[3]),
+        };
+        EpdDriver::new(peris, NoopDelay::new())
+    }
+
+    fn done(epd: MockDriver) {
+        let mut peris = epd.into_peripherals();
+        peris.spi.done();
+        for pin in [
+            &mut peris.m1_cs,
+            &mut peris.s1_cs,
+            &mut peris.m2_cs,
+            &mut peris.s2_cs,
+            &mut peris.m1s1_dc,
+            &mut peris.m2s2_dc,
+            &mut peris.m1s1_rst,
+            &mut peris.m2s2_rst,
+            &mut peris.m1_busy,
+            &mut peris.s1_busy,
+            &mut peris.m2_busy,
+            &mut peris.s2_busy,
+        ] {
+            pin.done();
+        }
+    }
+
+    #[test]
+    fn driver_busy_timeout() {
+        let busy = [
+            PinTransaction::get(State::Low),
+            PinTransaction::get(State::Low),
+        ];
+        let idle = [
+            PinTransaction::get(State::High),
+            PinTransaction::get(State::High),
+        ];
+        let mut epd = mock_driver([&busy, &idle, &idle, &idle]);
+        epd.set_busy_timeout(Some(0));
+        assert_eq!(epd.get_busy(), Ok(CS_M1));
+        assert_eq!(epd.wait_ready(CS_ALL), Err(Error::BusyTimeout));
+        done(epd);
+    }
+
+    #[test]
+    fn driver_checks_the_pixels() {
+        let mut epd = mock_driver([&[]; 4]);
+        let window = Rect::new(640, 488, 16, 8);
+        assert_eq!(
+            epd.write_data1_partial(window, &[]),
+            Err(Error::InvalidBuffer)
+        );
+        assert_eq!(
+            epd.write_data2_partial(window, &[0xFF; 2 * 8 - 1]),
+            Err(Error::InvalidBuffer)
+        );
+        assert_eq!(epd.write_data1(&[]), Err(Error::InvalidBuffer));
+        assert_eq!(
+            epd.write_data2(&[0xFF; WIDTH as usize / 8 + 1]),
+            Err(Error::InvalidBuffer)
+        );
+        done(epd);
+    }
+
+    #[test]
+    fn default_mode() {
+        // KWR mode with 1 for white and red, border follows LUTBD
+        assert_eq!(mode_data(&Config::default()), (0x00, 0x31));
+    }
+}
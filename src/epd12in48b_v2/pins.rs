@@ -0,0 +1,119 @@
+//! Pin groups of the four sub-displays, to pass them to [`WaveshareDisplay::new`](crate::prelude::WaveshareDisplayNew::new)
+//!
+//! The common traits expect a single busy, dc and reset pin. These groups bundle the pins of
+//! all the sub-displays and behave like one pin of each kind, [`Epd12in48b`](super::Epd12in48b)
+//! still drives them one by one.
+
+use embedded_hal::digital::{ErrorType, InputPin, OutputPin};
+
+/// "Busy" signals of the sub-displays
+///
+/// As an [`InputPin`] it is low as long as any of the sub-displays is busy.
+pub struct BusyPins<INPUT> {
+    /// "Busy" signal from `M1`.
+    pub m1: INPUT,
+    /// "Busy" signal from `S1`.
+    pub s1: INPUT,
+    /// "Busy" signal from `M2`.
+    pub m2: INPUT,
+    /// "Busy" signal from `S2`.
+    pub s2: INPUT,
+}
+
+impl<INPUT: InputPin> ErrorType for BusyPins<INPUT> {
+    type Error = INPUT::Error;
+}
+
+impl<INPUT: InputPin> InputPin for BusyPins<INPUT> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.is_low()?)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.m1.is_low()? || self.s1.is_low()? || self.m2.is_low()? || self.s2.is_low()?)
+    }
+}
+
+/// Chip select and "command/data" signals of the sub-displays
+///
+/// As an [`OutputPin`] it drives both "command/data" signals, the chip selects are only
+/// handled by the driver.
+pub struct ControlPins<OUTPUT> {
+    /// Chip select signal for `M1`.
+    pub m1_cs: OUTPUT,
+    /// Chip select signal for `S1`.
+    pub s1_cs: OUTPUT,
+    /// Chip select signal for `M2`.
+    pub m2_cs: OUTPUT,
+    /// Chip select signal for `S2`.
+    pub s2_cs: OUTPUT,
+    /// Shared "command/data" signal for `M1` and `S1`.
+    pub m1s1_dc: OUTPUT,
+    /// Shared "command/data" signal for `M2` and `S2`.
+    pub m2s2_dc: OUTPUT,
+}
+
+impl<OUTPUT: OutputPin> ErrorType for ControlPins<OUTPUT> {
+    type Error = OUTPUT::Error;
+}
+
+impl<OUTPUT: OutputPin> OutputPin for ControlPins<OUTPUT> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.m1s1_dc.set_low()?;
+        self.m2s2_dc.set_low()
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.m1s1_dc.set_high()?;
+        self.m2s2_dc.set_high()
+    }
+}
+
+/// Reset signals of the sub-displays
+///
+/// As an [`OutputPin`] it drives both reset signals at once.
+pub struct ResetPins<OUTPUT> {
+    /// Shared reset signal for `M1` and `S1`.
+    pub m1s1: OUTPUT,
+    /// Shared reset signal for `M2` and `S2`.
+    pub m2s2: OUTPUT,
+}
+
+impl<OUTPUT: OutputPin> ErrorType for ResetPins<OUTPUT> {
+    type Error = OUTPUT::Error;
+}
+
+impl<OUTPUT: OutputPin> OutputPin for ResetPins<OUTPUT> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.m1s1.set_low()?;
+        self.m2s2.set_low()
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.m1s1.set_high()?;
+        self.m2s2.set_high()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction as PinTransaction};
+
+    #[test]
+    fn busy_while_any_is_busy() {
+        let idle = || PinMock::new(&[PinTransaction::get(State::High)]);
+        let mut busy = BusyPins {
+            m1: idle(),
+            s1: idle(),
+            m2: PinMock::new(&[PinTransaction::get(State::Low)]),
+            s2: PinMock::new(&[]),
+        };
+        assert!(busy.is_low().unwrap());
+
+        busy.m1.done();
+        busy.s1.done();
+        busy.m2.done();
+        busy.s2.done();
+    }
+}
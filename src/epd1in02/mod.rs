@@ -11,9 +11,16 @@ use embedded_hal::{
 };
 
 use crate::color::Color;
+use crate::config::EpdConfig;
+use crate::error::Error;
 use crate::interface::DisplayInterface;
-use crate::prelude::WaveshareDisplay;
-use crate::traits::{InternalWiAdditions, QuickRefresh, RefreshLut};
+use crate::power::NoPowerPin;
+use crate::prelude::{WaveshareDisplay, WaveshareDisplayNew};
+use crate::traits::{
+    BusyPolling, Capabilities, ColorKind, InternalWiAdditions, QuickRefresh, RefreshLut,
+};
+use crate::waveform::Uc8175Waveform;
+use crate::window::{check_window, check_window_buffer};
 
 pub(crate) mod command;
 use self::command::Command;
@@ -49,35 +56,37 @@ const SINGLE_BYTE_WRITE: bool = true;
 
 /// Epd1in02 driver
 ///
-pub struct Epd1in02<SPI, BUSY, DC, RST, DELAY> {
+pub struct Epd1in02<SPI, BUSY, DC, RST, DELAY, PWR = NoPowerPin> {
     /// Connection Interface
-    interface: DisplayInterface<SPI, BUSY, DC, RST, DELAY, SINGLE_BYTE_WRITE>,
+    interface: DisplayInterface<SPI, BUSY, DC, RST, DELAY, PWR, SINGLE_BYTE_WRITE>,
     /// Background Color
     color: Color,
     is_turned_on: bool,
     refresh_mode: RefreshLut,
+    /// Color of the border, `None` follows the background color
+    border: Option<Color>,
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>
-    for Epd1in02<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplayNew<SPI, BUSY, DC, RST, DELAY, PWR>
+    for Epd1in02<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    type DisplayColor = Color;
-
-    fn new(
+    fn with_config(
         spi: &mut SPI,
         busy: BUSY,
         dc: DC,
         rst: RST,
         delay: &mut DELAY,
-        delay_us: Option<u32>,
-    ) -> Result<Self, SPI::Error> {
-        let interface = DisplayInterface::new(busy, dc, rst, delay_us);
+        config: EpdConfig<PWR>,
+    ) -> Result<Self, Error<SPI::Error>> {
+        let border = config.bw_border()?;
+        let interface = DisplayInterface::new(busy, dc, rst, config);
         let color = DEFAULT_BACKGROUND_COLOR;
 
         let mut epd = Epd1in02 {
@@ -85,14 +94,40 @@ where
             color,
             is_turned_on: false,
             refresh_mode: RefreshLut::Full,
+            border,
         };
 
         epd.init(spi, delay)?;
 
         Ok(epd)
     }
+}
+
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>
+    for Epd1in02<SPI, BUSY, DC, RST, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    type DisplayColor = Color;
 
-    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    const CAPABILITIES: Capabilities = Capabilities {
+        color: ColorKind::BlackWhite,
+        partial_update: false,
+        refresh_luts: &[
+            RefreshLut::Full,
+            RefreshLut::Quick,
+            RefreshLut::PartialRefresh,
+        ],
+        quick_refresh: true,
+        busy: BusyPolling::Pin,
+    };
+
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
         self.turn_off(spi, delay)?;
         self.cmd_with_data(spi, Command::DeepSleep, &[0xA5])?;
@@ -103,7 +138,11 @@ where
         Ok(())
     }
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn switch_power(&mut self, delay: &mut DELAY, on: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.switch_power(delay, on)
+    }
+
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)
     }
 
@@ -115,6 +154,10 @@ where
         &self.color
     }
 
+    fn set_busy_timeout(&mut self, timeout_us: Option<u32>) {
+        self.interface.set_busy_timeout(timeout_us);
+    }
+
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -128,7 +171,7 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
 
         self.set_full_mode(spi, delay)?;
@@ -154,16 +197,24 @@ where
         _y: u32,
         _width: u32,
         _height: u32,
-    ) -> Result<(), SPI::Error> {
-        unimplemented!()
+    ) -> Result<(), Error<SPI::Error>> {
+        Err(Error::Unsupported)
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.begin_display_frame(spi, delay)?;
+        self.wait_until_idle(spi, delay)
+    }
+
+    fn begin_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
         self.turn_on_if_turned_off(spi, delay)?;
 
         self.command(spi, Command::DisplayRefresh)?;
-        self.wait_until_idle(spi, delay)?;
         Ok(())
     }
 
@@ -172,13 +223,13 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.display_frame(spi, delay)?;
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
         self.set_full_mode(spi, delay)?;
 
@@ -200,7 +251,7 @@ where
         spi: &mut SPI,
         _delay: &mut DELAY,
         refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         let (white_lut, black_lut) = match refresh_rate {
             Some(RefreshLut::Full) => (&LUT_FULL_UPDATE_WHITE, &LUT_FULL_UPDATE_BLACK),
             Some(RefreshLut::Quick | RefreshLut::PartialRefresh) => {
@@ -214,20 +265,29 @@ where
         Ok(())
     }
 
-    fn wait_until_idle(&mut self, _spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.interface.wait_until_idle(delay, IS_BUSY_LOW);
+    fn wait_until_idle(
+        &mut self,
+        _spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(delay, IS_BUSY_LOW)?;
         Ok(())
     }
+
+    fn is_busy(&mut self, _spi: &mut SPI) -> Result<bool, Error<SPI::Error>> {
+        self.interface.is_busy(IS_BUSY_LOW)
+    }
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> QuickRefresh<SPI, BUSY, DC, RST, DELAY>
-    for Epd1in02<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> QuickRefresh<SPI, BUSY, DC, RST, DELAY>
+    for Epd1in02<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
     /// To be followed immediately by update_new_frame
     fn update_old_frame(
@@ -235,7 +295,7 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.set_partial_mode(spi, delay)?;
         self.set_partial_window(spi, delay, 0, 0, WIDTH, HEIGHT)?;
 
@@ -249,13 +309,17 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.cmd_with_data(spi, Command::DataStartTransmission2, buffer)?;
         Ok(())
     }
 
-    fn display_new_frame(&mut self, _spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        unimplemented!()
+    fn display_new_frame(
+        &mut self,
+        _spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        Err(Error::Unsupported)
     }
 
     fn update_and_display_new_frame(
@@ -263,8 +327,8 @@ where
         _spi: &mut SPI,
         _buffer: &[u8],
         _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
-        unimplemented!()
+    ) -> Result<(), Error<SPI::Error>> {
+        Err(Error::Unsupported)
     }
 
     /// To be followed immediately by update_partial_new_frame
@@ -278,10 +342,9 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
-        if !is_buffer_size_ok(buffer, width, height) {
-            panic!("Image buffer size is not correct")
-        }
+    ) -> Result<(), Error<SPI::Error>> {
+        check_window(x, y, width, height, WIDTH, HEIGHT)?;
+        check_window_buffer(buffer, width, height, 1)?;
 
         self.set_partial_mode(spi, delay)?;
         self.set_partial_window(spi, delay, x, y, width, height)?;
@@ -296,14 +359,13 @@ where
         spi: &mut SPI,
         _delay: &mut DELAY,
         buffer: &[u8],
-        _x: u32,
-        _y: u32,
+        x: u32,
+        y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
-        if !is_buffer_size_ok(buffer, width, height) {
-            panic!("Image buffer size is not correct")
-        }
+    ) -> Result<(), Error<SPI::Error>> {
+        check_window(x, y, width, height, WIDTH, HEIGHT)?;
+        check_window_buffer(buffer, width, height, 1)?;
 
         self.cmd_with_data(spi, Command::DataStartTransmission2, buffer)?;
         Ok(())
@@ -318,7 +380,8 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
+        check_window(x, y, width, height, WIDTH, HEIGHT)?;
         self.wait_until_idle(spi, delay)?;
         // set full LUT as quick LUT requires old image
         self.set_full_mode(spi, delay)?;
@@ -342,18 +405,19 @@ where
     }
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> InternalWiAdditions<SPI, BUSY, DC, RST, DELAY>
-    for Epd1in02<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> InternalWiAdditions<SPI, BUSY, DC, RST, DELAY>
+    for Epd1in02<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         // Reset the device
-        self.interface.reset(delay, 20_000, 2000);
+        self.interface.reset(delay, 20_000, 2000)?;
 
         // Set the panel settings: LUT from register
         self.cmd_with_data(spi, Command::PanelSetting, &[0x6F])?;
@@ -371,8 +435,8 @@ where
         self.cmd_with_data(spi, Command::PllControl, &[0x17])?;
 
         // Set Vcom and data interval: default
-        // set the border color the same as background color
-        let value = match self.background_color() {
+        // set the border color, the same as the background color by default
+        let value = match self.border.unwrap_or(self.color) {
             Color::Black => 0x57,
             Color::White => 0x97,
         };
@@ -397,19 +461,32 @@ where
     }
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> Epd1in02<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> Epd1in02<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), SPI::Error> {
+    /// Writes a custom waveform, see [`waveform`](crate::waveform)
+    pub fn set_custom_lut(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        waveform: &Uc8175Waveform,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle(spi, delay)?;
+        self.cmd_with_data(spi, Command::SetWhiteLut, &waveform.white)?;
+        self.cmd_with_data(spi, Command::SetBlackLut, &waveform.black)
+    }
+
+    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd(spi, command)
     }
 
-    fn send_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
+    fn send_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), Error<SPI::Error>> {
         self.interface.data(spi, data)
     }
 
@@ -418,11 +495,11 @@ where
         spi: &mut SPI,
         command: Command,
         data: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(spi, command, data)
     }
 
-    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
+    fn send_resolution(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
         let w = self.width();
         let h = self.height();
 
@@ -436,7 +513,7 @@ where
         &mut self,
         spi: &mut SPI,
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         if !self.is_turned_on {
             self.command(spi, Command::PowerOn)?;
             self.wait_until_idle(spi, delay)?;
@@ -445,14 +522,14 @@ where
         Ok(())
     }
 
-    fn turn_off(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn turn_off(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.command(spi, Command::PowerOff)?;
         self.wait_until_idle(spi, delay)?;
         self.is_turned_on = false;
         Ok(())
     }
 
-    fn set_full_mode(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn set_full_mode(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         if self.refresh_mode != RefreshLut::Full {
             self.command(spi, Command::PartialOut)?;
             self.set_lut(spi, delay, Some(RefreshLut::Full))?;
@@ -461,7 +538,11 @@ where
         Ok(())
     }
 
-    fn set_partial_mode(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn set_partial_mode(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
         if self.refresh_mode != RefreshLut::Quick {
             self.command(spi, Command::PartialIn)?;
             self.set_lut(spi, delay, Some(RefreshLut::Quick))?;
@@ -478,11 +559,7 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
-        if !is_window_size_ok(x, y, width, height) {
-            panic!("Partial update window size is not correct")
-        }
-
+    ) -> Result<(), Error<SPI::Error>> {
         self.cmd_with_data(
             spi,
             Command::PartialWindow,
@@ -499,17 +576,6 @@ where
     }
 }
 
-fn is_window_size_ok(x: u32, y: u32, width: u32, height: u32) -> bool {
-    // partial update window is inside the screen
-    x + width <= WIDTH && y + height <= HEIGHT
-    // 3 less significant bits are ignored
-    && x % 8 == 0 && width % 8 == 0
-}
-
-fn is_buffer_size_ok(buffer: &[u8], width: u32, height: u32) -> bool {
-    buffer_len(width as usize, height as usize) == buffer.len()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -523,38 +589,38 @@ mod tests {
 
     #[test]
     fn inside_of_screen() {
-        assert!(is_window_size_ok(0, 0, 80, 128));
+        assert!(check_window::<()>(0, 0, 80, 128, WIDTH, HEIGHT).is_ok());
     }
 
     #[test]
     fn x_too_big() {
-        assert!(!is_window_size_ok(8, 8, 80, 1));
+        assert!(check_window::<()>(8, 8, 80, 1, WIDTH, HEIGHT).is_err());
     }
 
     #[test]
     fn y_too_big() {
-        assert!(!is_window_size_ok(8, 8, 8, 121));
+        assert!(check_window::<()>(8, 8, 8, 121, WIDTH, HEIGHT).is_err());
     }
 
     #[test]
     fn x_is_not_multiple_of_8() {
-        assert!(!is_window_size_ok(1, 0, 72, 128));
+        assert!(check_window::<()>(1, 0, 72, 128, WIDTH, HEIGHT).is_err());
     }
 
     #[test]
     fn width_is_not_multiple_of_8() {
-        assert!(!is_window_size_ok(0, 0, 79, 128));
+        assert!(check_window::<()>(0, 0, 79, 128, WIDTH, HEIGHT).is_err());
     }
 
     #[test]
     fn buffer_size_incorrect() {
         let buf = [0u8; 10];
-        assert!(!is_buffer_size_ok(&buf, 10, 10));
+        assert!(check_window_buffer::<()>(&buf, 10, 10, 1).is_err());
     }
 
     #[test]
     fn buffer_size_correct() {
         let buf = [0u8; 10];
-        assert!(is_buffer_size_ok(&buf, 8, 10));
+        assert!(check_window_buffer::<()>(&buf, 8, 10, 1).is_ok());
     }
 }
@@ -4,7 +4,7 @@
 //!
 //!```rust, no_run
 //!# use embedded_hal_mock::eh1::*;
-//!# fn main() -> Result<(), embedded_hal::spi::ErrorKind> {
+//!# fn main() -> Result<(), epd_waveshare::Error<embedded_hal::spi::ErrorKind>> {
 //!use embedded_graphics::{
 //!    pixelcolor::BinaryColor::On as Black, prelude::*, primitives::{Line, PrimitiveStyleBuilder},
 //!};
@@ -63,10 +63,19 @@ use crate::type_a::{
 
 use crate::color::Color;
 
-use crate::traits::{RefreshLut, WaveshareDisplay};
+use crate::traits::{
+    BusyPolling, Capabilities, ColorKind, DeepSleepMode, RefreshLut, WaveshareDisplay,
+    WaveshareDisplayNew,
+};
 
 use crate::buffer_len;
+use crate::config::EpdConfig;
+use crate::error::Error;
 use crate::interface::DisplayInterface;
+use crate::power::NoPowerPin;
+use crate::type_a::{il3820_border_bits, write_waveform};
+use crate::waveform::{Ssd16xxWaveform, TemperatureBands};
+use crate::window::{check_window, check_window_buffer};
 
 /// Full size buffer for use with the 1in54b EPD
 #[cfg(feature = "graphics")]
@@ -79,25 +88,30 @@ pub type Display1in54 = crate::graphics::Display<
 >;
 
 /// Epd1in54 driver
-pub struct Epd1in54<SPI, BUSY, DC, RST, DELAY> {
+pub struct Epd1in54<SPI, BUSY, DC, RST, DELAY, PWR = NoPowerPin> {
     /// SPI
-    interface: DisplayInterface<SPI, BUSY, DC, RST, DELAY, SINGLE_BYTE_WRITE>,
+    interface: DisplayInterface<SPI, BUSY, DC, RST, DELAY, PWR, SINGLE_BYTE_WRITE>,
     /// Color
     background_color: Color,
     /// Refresh LUT
     refresh: RefreshLut,
+    /// Deep sleep mode entered by `sleep`
+    sleep_mode: DeepSleepMode,
+    /// Color of the border, `None` keeps the one of the controller
+    border: Option<Color>,
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> Epd1in54<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> Epd1in54<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.interface.reset(delay, 10_000, 10_000);
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.reset(delay, 10_000, 10_000)?;
 
         // 3 Databytes:
         // A[7:0]
@@ -135,6 +149,14 @@ where
         self.interface
             .cmd_with_data(spi, Command::DataEntryModeSetting, &[0x03])?;
 
+        if let Some(color) = self.border {
+            self.interface.cmd_with_data(
+                spi,
+                Command::BorderWaveformControl,
+                &[il3820_border_bits(color)],
+            )?;
+        }
+
         self.set_lut(spi, delay, None)?;
 
         self.wait_until_idle(spi, delay)?;
@@ -142,64 +164,97 @@ where
     }
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>
-    for Epd1in54<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplayNew<SPI, BUSY, DC, RST, DELAY, PWR>
+    for Epd1in54<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    type DisplayColor = Color;
-    fn width(&self) -> u32 {
-        WIDTH
-    }
-
-    fn height(&self) -> u32 {
-        HEIGHT
-    }
-
-    fn new(
+    fn with_config(
         spi: &mut SPI,
         busy: BUSY,
         dc: DC,
         rst: RST,
         delay: &mut DELAY,
-        delay_us: Option<u32>,
-    ) -> Result<Self, SPI::Error> {
-        let interface = DisplayInterface::new(busy, dc, rst, delay_us);
+        config: EpdConfig<PWR>,
+    ) -> Result<Self, Error<SPI::Error>> {
+        let border = config.bw_border()?;
+        let interface = DisplayInterface::new(busy, dc, rst, config);
 
         let mut epd = Epd1in54 {
             interface,
             background_color: DEFAULT_BACKGROUND_COLOR,
             refresh: RefreshLut::Full,
+            sleep_mode: DeepSleepMode::Normal,
+            border,
         };
 
         epd.init(spi, delay)?;
 
         Ok(epd)
     }
+}
+
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>
+    for Epd1in54<SPI, BUSY, DC, RST, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    type DisplayColor = Color;
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        color: ColorKind::BlackWhite,
+        partial_update: true,
+        refresh_luts: &[
+            RefreshLut::Full,
+            RefreshLut::Quick,
+            RefreshLut::PartialRefresh,
+        ],
+        quick_refresh: false,
+        busy: BusyPolling::Pin,
+    };
+
+    fn width(&self) -> u32 {
+        WIDTH
+    }
+
+    fn height(&self) -> u32 {
+        HEIGHT
+    }
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)
     }
 
-    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
-        // 0x00 for Normal mode (Power on Reset), 0x01 for Deep Sleep Mode
-        //TODO: is 0x00 needed here or would 0x01 be even more efficient?
-        self.interface
-            .cmd_with_data(spi, Command::DeepSleepMode, &[0x00])?;
+        self.interface.cmd_with_data(
+            spi,
+            Command::DeepSleepMode,
+            &[self.sleep_mode.ssd16xx_value()],
+        )?;
         Ok(())
     }
 
+    fn switch_power(&mut self, delay: &mut DELAY, on: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.switch_power(delay, on)
+    }
+
     fn update_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
         self.use_full_frame(spi, delay)?;
         self.interface
@@ -217,7 +272,9 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
+        check_window(x, y, width, height, WIDTH, HEIGHT)?;
+        check_window_buffer(buffer, width, height, 1)?;
         self.wait_until_idle(spi, delay)?;
         self.set_ram_area(spi, delay, x, y, x + width, y + height)?;
         self.set_ram_counter(spi, delay, x, y)?;
@@ -227,7 +284,15 @@ where
         Ok(())
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.begin_display_frame(spi, delay)
+    }
+
+    fn begin_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
         // enable clock signal, enable cp, display pattern -> 0xC4 (tested with the arduino version)
         //TODO: test control_1 or control_2 with default value 0xFF (from the datasheet)
@@ -246,13 +311,13 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.display_frame(spi, delay)?;
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
         self.use_full_frame(spi, delay)?;
 
@@ -273,12 +338,20 @@ where
         &self.background_color
     }
 
+    fn set_busy_timeout(&mut self, timeout_us: Option<u32>) {
+        self.interface.set_busy_timeout(timeout_us);
+    }
+
+    fn deep_sleep_mode(&self) -> DeepSleepMode {
+        self.sleep_mode
+    }
+
     fn set_lut(
         &mut self,
         spi: &mut SPI,
         delay: &mut DELAY,
         refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         if let Some(refresh_lut) = refresh_rate {
             self.refresh = refresh_lut;
         }
@@ -290,25 +363,68 @@ where
         }
     }
 
-    fn wait_until_idle(&mut self, _spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.interface.wait_until_idle(delay, IS_BUSY_LOW);
+    fn wait_until_idle(
+        &mut self,
+        _spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(delay, IS_BUSY_LOW)?;
         Ok(())
     }
+
+    fn is_busy(&mut self, _spi: &mut SPI) -> Result<bool, Error<SPI::Error>> {
+        self.interface.is_busy(IS_BUSY_LOW)
+    }
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> Epd1in54<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> Epd1in54<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
+    /// Selects the deep sleep mode [`sleep`](WaveshareDisplay::sleep) enters,
+    /// [`DeepSleepMode::Normal`] by default
+    ///
+    /// The controller has no mode 2, [`DeepSleepMode::Mode2`] selects mode 1 which keeps the RAM.
+    pub fn set_deep_sleep_mode(&mut self, mode: DeepSleepMode) {
+        self.sleep_mode = match mode {
+            DeepSleepMode::Mode2 => DeepSleepMode::Mode1,
+            mode => mode,
+        };
+    }
+
+    /// Writes a custom waveform of 30 bytes, see [`waveform`](crate::waveform)
+    pub fn set_custom_lut(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        waveform: &Ssd16xxWaveform<'_>,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle(spi, delay)?;
+        write_waveform(&mut self.interface, spi, waveform, 30, false)
+    }
+
+    /// Writes the waveform of `bands` made for `celsius`, measured e.g. by an external sensor
+    pub fn set_lut_for_temperature(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        bands: &TemperatureBands<'_, Ssd16xxWaveform<'_>>,
+        celsius: i8,
+    ) -> Result<(), Error<SPI::Error>> {
+        let waveform = bands.select(celsius).ok_or(Error::InvalidWaveform)?;
+        self.set_custom_lut(spi, delay, waveform)
+    }
+
     pub(crate) fn use_full_frame(
         &mut self,
         spi: &mut SPI,
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         // choose full frame/ram
         self.set_ram_area(spi, delay, 0, 0, WIDTH - 1, HEIGHT - 1)?;
 
@@ -324,7 +440,7 @@ where
         start_y: u32,
         end_x: u32,
         end_y: u32,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
         assert!(start_x < end_x);
         assert!(start_y < end_y);
@@ -357,7 +473,7 @@ where
         delay: &mut DELAY,
         x: u32,
         y: u32,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
         // x is positioned in bytes, so the last 3 bits which show the position inside a byte in the ram
         // aren't relevant
@@ -378,7 +494,7 @@ where
         spi: &mut SPI,
         delay: &mut DELAY,
         buffer: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
         assert!(buffer.len() == 30);
 
@@ -129,17 +129,6 @@ impl BorderWaveForm {
     }
 }
 
-#[allow(dead_code)]
-#[derive(Copy, Clone)]
-pub enum DeepSleepMode {
-    // Sleeps and keeps access to RAM and controller
-    Normal = 0x00,
-    // Sleeps without access to RAM/controller but keeps RAM content
-    Mode1 = 0x01,
-    // Same as MODE_1 but RAM content is not kept
-    Mode2 = 0x11,
-}
-
 impl traits::Command for Command {
     /// Returns the address of the command
     fn address(self) -> u8 {
@@ -88,12 +88,6 @@ pub(crate) enum OutputSource {
     S8ToS167 = 0x80,
 }
 
-pub(crate) enum DeepSleep {
-    Awake = 0b00,
-    SleepKeepingRAM = 0b01,
-    SleepLosingRAM = 0b11,
-}
-
 pub(crate) enum PatH {
     H8 = 0b000_0000,
     H16 = 0b001_0000,
@@ -173,14 +173,19 @@ use embedded_hal::{
 };
 
 use crate::color::TriColor;
+use crate::config::EpdConfig;
+use crate::error::Error;
 use crate::interface::DisplayInterface;
+use crate::power::NoPowerPin;
 use crate::traits::{
-    InternalWiAdditions, RefreshLut, WaveshareDisplay, WaveshareThreeColorDisplay,
+    BusyPolling, Capabilities, ColorKind, DeepSleepMode, InternalWiAdditions, RefreshLut,
+    WaveshareDisplay, WaveshareDisplayNew, WaveshareThreeColorDisplay,
 };
 
 pub(crate) mod command;
 use self::command::*;
 use crate::buffer_len;
+use crate::window::{check_window, check_window_buffer};
 
 /// Display height in pixels.
 pub const WIDTH: u32 = 152;
@@ -202,22 +207,37 @@ pub type Display2in66b = crate::graphics::Display<
     TriColor,
 >;
 
+/// Border bits of [`Command::BorderWaveformControl`]: the border follows the LUT of the pixels
+/// of its color, picked by their (red, b/w) RAM bits
+fn border_bits(color: TriColor) -> u8 {
+    match color {
+        TriColor::Black => 0x04,
+        TriColor::White => 0x05,
+        TriColor::Chromatic => 0x06,
+    }
+}
+
 /// The EPD 2in66-B driver.
-pub struct Epd2in66b<SPI, BUSY, DC, RST, DELAY> {
-    interface: DisplayInterface<SPI, BUSY, DC, RST, DELAY, SINGLE_BYTE_WRITE>,
+pub struct Epd2in66b<SPI, BUSY, DC, RST, DELAY, PWR = NoPowerPin> {
+    interface: DisplayInterface<SPI, BUSY, DC, RST, DELAY, PWR, SINGLE_BYTE_WRITE>,
     background: TriColor,
+    /// Deep sleep mode entered by `sleep`
+    sleep_mode: DeepSleepMode,
+    /// Color of the border, `None` keeps the one of the controller
+    border: Option<TriColor>,
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> InternalWiAdditions<SPI, BUSY, DC, RST, DELAY>
-    for Epd2in66b<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> InternalWiAdditions<SPI, BUSY, DC, RST, DELAY>
+    for Epd2in66b<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         // We follow the sequence of the Pi-Pico hat example code.
         self.hw_reset(delay)?;
         self.sw_reset(spi, delay)?;
@@ -231,18 +251,27 @@ where
         )?;
         self.set_cursor(spi, 0, 0)?;
 
+        if let Some(color) = self.border {
+            self.interface.cmd_with_data(
+                spi,
+                Command::BorderWaveformControl,
+                &[border_bits(color)],
+            )?;
+        }
+
         Ok(())
     }
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> WaveshareThreeColorDisplay<SPI, BUSY, DC, RST, DELAY>
-    for Epd2in66b<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareThreeColorDisplay<SPI, BUSY, DC, RST, DELAY>
+    for Epd2in66b<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
     fn update_color_frame(
         &mut self,
@@ -250,7 +279,7 @@ where
         delay: &mut DELAY,
         black: &[u8],
         chromatic: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_achromatic_frame(spi, delay, black)?;
         self.update_chromatic_frame(spi, delay, chromatic)
     }
@@ -260,7 +289,7 @@ where
         spi: &mut SPI,
         _delay: &mut DELAY,
         black: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.set_cursor(spi, 0, 0)?;
         self.interface.cmd(spi, Command::WriteBlackWhiteRAM)?;
         self.interface.data(spi, black)
@@ -271,52 +300,79 @@ where
         spi: &mut SPI,
         _delay: &mut DELAY,
         chromatic: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.set_cursor(spi, 0, 0)?;
         self.interface.cmd(spi, Command::WriteRedRAM)?;
         self.interface.data(spi, chromatic)
     }
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>
-    for Epd2in66b<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplayNew<SPI, BUSY, DC, RST, DELAY, PWR>
+    for Epd2in66b<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    type DisplayColor = TriColor;
-
-    fn new(
+    fn with_config(
         spi: &mut SPI,
         busy: BUSY,
         dc: DC,
         rst: RST,
         delay: &mut DELAY,
-        delay_us: Option<u32>,
-    ) -> Result<Self, SPI::Error>
+        config: EpdConfig<PWR>,
+    ) -> Result<Self, Error<SPI::Error>>
     where
         Self: Sized,
     {
+        let border = config.border();
         let mut epd = Self {
-            interface: DisplayInterface::new(busy, dc, rst, delay_us),
+            interface: DisplayInterface::new(busy, dc, rst, config),
             background: DEFAULT_BACKGROUND_COLOR,
+            sleep_mode: DeepSleepMode::Mode2,
+            border,
         };
         epd.init(spi, delay)?;
         Ok(epd)
     }
+}
 
-    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>
+    for Epd2in66b<SPI, BUSY, DC, RST, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    type DisplayColor = TriColor;
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        color: ColorKind::TriColor,
+        partial_update: true,
+        refresh_luts: &[RefreshLut::Full],
+        quick_refresh: false,
+        busy: BusyPolling::Pin,
+    };
+
+    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(
             spi,
             Command::DeepSleepMode,
-            &[DeepSleep::SleepLosingRAM as u8],
+            &[self.sleep_mode.ssd16xx_value()],
         )
     }
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn switch_power(&mut self, delay: &mut DELAY, on: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.switch_power(delay, on)
+    }
+
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)
     }
 
@@ -328,6 +384,14 @@ where
         &self.background
     }
 
+    fn set_busy_timeout(&mut self, timeout_us: Option<u32>) {
+        self.interface.set_busy_timeout(timeout_us);
+    }
+
+    fn deep_sleep_mode(&self) -> DeepSleepMode {
+        self.sleep_mode
+    }
+
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -341,7 +405,7 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.set_cursor(spi, 0, 0)?;
         self.update_achromatic_frame(spi, delay, buffer)?;
         self.red_pattern(spi, delay, PatW::W160, PatH::H296, StartWith::Zero) // do NOT consider background here since red overrides other colors
@@ -356,29 +420,40 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
+        check_window(x, y, width, height, WIDTH, HEIGHT)?;
+        check_window_buffer(buffer, width, height, 1)?;
         self.set_display_window(spi, x, y, x + width, y + height)?;
         self.set_cursor(spi, x, y)?;
         self.update_achromatic_frame(spi, delay, buffer)?;
         self.set_display_window(spi, 0, 0, WIDTH, HEIGHT)
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.interface.cmd(spi, Command::MasterActivation)?;
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.begin_display_frame(spi, delay)?;
         self.wait_until_idle(delay)
     }
 
+    fn begin_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.cmd(spi, Command::MasterActivation)?;
+        Ok(())
+    }
+
     fn update_and_display_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.display_frame(spi, delay)
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         let (white, red) = match self.background {
             TriColor::Black => (StartWith::Zero, StartWith::Zero),
             TriColor::White => (StartWith::One, StartWith::Zero),
@@ -393,34 +468,49 @@ where
         _spi: &mut SPI,
         _delay: &mut DELAY,
         _refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         Ok(())
     }
 
-    fn wait_until_idle(&mut self, _spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn wait_until_idle(
+        &mut self,
+        _spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(delay)
     }
+
+    fn is_busy(&mut self, _spi: &mut SPI) -> Result<bool, Error<SPI::Error>> {
+        self.interface.is_busy(false)
+    }
 }
 
 // Helper functions that enforce some type and value constraints. Meant to help with code readability. They caught some of my silly errors -> yay rust!.
-impl<SPI, BUSY, DC, RST, DELAY> Epd2in66b<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> Epd2in66b<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    fn wait_until_idle(&mut self, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.interface.wait_until_idle(delay, false);
+    /// Selects the deep sleep mode [`sleep`](WaveshareDisplay::sleep) enters,
+    /// [`DeepSleepMode::Mode2`] by default
+    pub fn set_deep_sleep_mode(&mut self, mode: DeepSleepMode) {
+        self.sleep_mode = mode;
+    }
+
+    fn wait_until_idle(&mut self, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(delay, false)?;
         Ok(())
     }
-    fn hw_reset(&mut self, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn hw_reset(&mut self, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         // The initial delay is taken from other code here, the 2 ms comes from the SSD1675B datasheet.
-        self.interface.reset(delay, 20_000, 2_000);
+        self.interface.reset(delay, 20_000, 2_000)?;
         self.wait_until_idle(delay)
     }
-    fn sw_reset(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn sw_reset(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd(spi, Command::Reset)?;
         self.wait_until_idle(delay)
     }
@@ -429,7 +519,7 @@ where
         spi: &mut SPI,
         row: DataEntryRow,
         sign: DataEntrySign,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface
             .cmd_with_data(spi, Command::DataEntryMode, &[row as u8 | sign as u8])
     }
@@ -440,7 +530,7 @@ where
         ystart: u32,
         xend: u32,
         yend: u32,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(
             spi,
             Command::SetXAddressRange,
@@ -463,7 +553,7 @@ where
         red_mode: WriteMode,
         bw_mode: WriteMode,
         source: OutputSource,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(
             spi,
             Command::DisplayUpdateControl1,
@@ -471,7 +561,7 @@ where
         )
     }
 
-    fn set_cursor(&mut self, spi: &mut SPI, x: u32, y: u32) -> Result<(), SPI::Error> {
+    fn set_cursor(&mut self, spi: &mut SPI, x: u32, y: u32) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(
             spi,
             Command::SetXAddressCounter,
@@ -491,7 +581,7 @@ where
         w: PatW,
         h: PatH,
         phase: StartWith,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(
             spi,
             Command::BlackWhiteRAMTestPattern,
@@ -506,7 +596,7 @@ where
         w: PatW,
         h: PatH,
         phase: StartWith,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(
             spi,
             Command::RedRAMTestPattern,
@@ -1,6 +1,9 @@
 //! A simple Driver for the Waveshare 2.7inch v2 e-Paper HAT Display via SPI
 //!
-//! 4 Gray support and partial refresh is not fully implemented yet.
+//! Partial refresh is not fully implemented yet.
+//!
+//! Besides black and white, the display supports 4 gray levels, see
+//! [`update_and_display_gray2_frame`](Epd2in7::update_and_display_gray2_frame).
 //!
 //! # References
 //!
@@ -14,14 +17,26 @@ use embedded_hal::{
     spi::SpiDevice,
 };
 
+use crate::type_a::{read_temperature, ssd1680_border_bits, write_temperature, write_waveform};
+use crate::waveform::{Ssd16xxWaveform, TemperatureBands};
+use crate::window::{check_window, check_window_buffer};
 use crate::{
     buffer_len,
     color::Color,
+    config::EpdConfig,
+    error::Error,
     interface::DisplayInterface,
-    traits::{InternalWiAdditions, RefreshLut, WaveshareDisplay},
+    power::NoPowerPin,
+    traits::{
+        BusyPolling, Capabilities, ColorKind, DeepSleepMode, InternalWiAdditions, RefreshLut,
+        WaveshareDisplay, WaveshareDisplayNew,
+    },
     type_a::command::Command,
 };
 
+#[cfg(feature = "graphics")]
+use crate::color::Gray2;
+
 /// Width of the display
 pub const WIDTH: u32 = 176;
 /// Height of the display
@@ -32,6 +47,19 @@ pub const DEFAULT_BACKGROUND_COLOR: Color = Color::White;
 const IS_BUSY_LOW: bool = false;
 const SINGLE_BYTE_WRITE: bool = true;
 
+/// Waveform for 4 gray levels, followed by the EOPQ, gate, source and VCOM voltages
+const LUT_4GRAY: [u8; 159] = [
+    0x40, 0x48, 0x80, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x8, 0x48, 0x10, 0x0, 0x0, 0x0,
+    0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x2, 0x48, 0x4, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    0x20, 0x48, 0x1, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0xA, 0x19, 0x0, 0x3, 0x8, 0x0, 0x0, 0x14, 0x1, 0x0, 0x14, 0x1,
+    0x0, 0x3, 0xA, 0x3, 0x0, 0x8, 0x19, 0x0, 0x0, 0x1, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1, 0x0, 0x0, 0x0,
+    0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0,
+    0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x22, 0x22, 0x22,
+    0x22, 0x22, 0x22, 0x0, 0x0, 0x0, 0x22, 0x17, 0x41, 0x0, 0x32, 0x1C,
+];
+
 /// Full size buffer for use with the 2in7B EPD
 /// TODO this should be a TriColor, but let's keep it as is at first
 #[cfg(feature = "graphics")]
@@ -43,27 +71,44 @@ pub type Display2in7 = crate::graphics::Display<
     Color,
 >;
 
+/// Full size buffer for use with the 2in7 v2 EPD in 4 gray mode
+#[cfg(feature = "graphics")]
+pub type Display2in7Gray2 = crate::graphics::Display<
+    WIDTH,
+    HEIGHT,
+    false,
+    { 2 * buffer_len(WIDTH as usize, HEIGHT as usize) },
+    Gray2,
+>;
+
 /// Epd2in7b driver
-pub struct Epd2in7<SPI, BUSY, DC, RST, DELAY> {
+pub struct Epd2in7<SPI, BUSY, DC, RST, DELAY, PWR = NoPowerPin> {
     /// Connection Interface
-    interface: DisplayInterface<SPI, BUSY, DC, RST, DELAY, SINGLE_BYTE_WRITE>,
+    interface: DisplayInterface<SPI, BUSY, DC, RST, DELAY, PWR, SINGLE_BYTE_WRITE>,
     /// Background Color
     color: Color,
     refresh: RefreshLut,
+    /// Deep sleep mode entered by `sleep`
+    sleep_mode: DeepSleepMode,
+    /// Color of the border, `None` keeps the one of the controller
+    border: Option<Color>,
+    /// Temperature written instead of the reading of the sensor
+    temperature: Option<i8>,
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> InternalWiAdditions<SPI, BUSY, DC, RST, DELAY>
-    for Epd2in7<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> InternalWiAdditions<SPI, BUSY, DC, RST, DELAY>
+    for Epd2in7<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         // reset the device
-        self.interface.reset(delay, 200_000, 2_000);
+        self.interface.reset(delay, 200_000, 2_000)?;
 
         self.wait_until_idle(spi, delay)?;
         self.command(spi, Command::SwReset)?;
@@ -74,59 +119,103 @@ where
         self.interface
             .cmd_with_data(spi, Command::DataEntryModeSetting, &[0x03])?;
 
+        if let Some(color) = self.border {
+            self.interface.cmd_with_data(
+                spi,
+                Command::BorderWaveformControl,
+                &[ssd1680_border_bits(color)],
+            )?;
+        }
+
+        if self.temperature.is_some() {
+            write_temperature(&mut self.interface, spi, self.temperature)?;
+        }
+
         Ok(())
     }
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>
-    for Epd2in7<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplayNew<SPI, BUSY, DC, RST, DELAY, PWR>
+    for Epd2in7<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    type DisplayColor = Color;
-    fn new(
+    fn with_config(
         spi: &mut SPI,
         busy: BUSY,
         dc: DC,
         rst: RST,
         delay: &mut DELAY,
-        delay_us: Option<u32>,
-    ) -> Result<Self, SPI::Error> {
-        let interface = DisplayInterface::new(busy, dc, rst, delay_us);
+        config: EpdConfig<PWR>,
+    ) -> Result<Self, Error<SPI::Error>> {
+        let border = config.bw_border()?;
+        let interface = DisplayInterface::new(busy, dc, rst, config);
         let color = DEFAULT_BACKGROUND_COLOR;
 
         let mut epd = Epd2in7 {
             interface,
             color,
             refresh: RefreshLut::Full,
+            sleep_mode: DeepSleepMode::Mode1,
+            border,
+            temperature: None,
         };
 
         epd.init(spi, delay)?;
 
         Ok(epd)
     }
+}
+
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>
+    for Epd2in7<SPI, BUSY, DC, RST, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    type DisplayColor = Color;
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    const CAPABILITIES: Capabilities = Capabilities {
+        color: ColorKind::BlackWhite,
+        partial_update: true,
+        refresh_luts: &[RefreshLut::Full, RefreshLut::Quick],
+        quick_refresh: false,
+        busy: BusyPolling::Pin,
+    };
+
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)
     }
 
-    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
-        self.interface
-            .cmd_with_data(spi, Command::DeepSleepMode, &[0x01])?;
+        self.interface.cmd_with_data(
+            spi,
+            Command::DeepSleepMode,
+            &[self.sleep_mode.ssd16xx_value()],
+        )?;
         Ok(())
     }
 
+    fn switch_power(&mut self, delay: &mut DELAY, on: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.switch_power(delay, on)
+    }
+
     fn update_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
         self.use_full_frame(spi, delay)?;
         self.interface
@@ -143,7 +232,9 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
+        check_window(x, y, width, height, WIDTH, HEIGHT)?;
+        check_window_buffer(buffer, width, height, 1)?;
         self.wait_until_idle(spi, delay)?;
         self.set_ram_area(spi, x, y, x + width, y + height)?;
         self.set_ram_counter(spi, delay, x, y)?;
@@ -154,7 +245,16 @@ where
         Ok(())
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.begin_display_frame(spi, delay)?;
+        self.wait_until_idle(spi, delay)
+    }
+
+    fn begin_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
         if self.refresh == RefreshLut::Full {
             self.interface
@@ -165,7 +265,6 @@ where
         }
 
         self.interface.cmd(spi, Command::MasterActivation)?;
-        self.wait_until_idle(spi, delay)?;
         Ok(())
     }
 
@@ -174,13 +273,13 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.display_frame(spi, delay)?;
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
         self.use_full_frame(spi, delay)?;
 
@@ -201,6 +300,14 @@ where
         &self.color
     }
 
+    fn set_busy_timeout(&mut self, timeout_us: Option<u32>) {
+        self.interface.set_busy_timeout(timeout_us);
+    }
+
+    fn deep_sleep_mode(&self) -> DeepSleepMode {
+        self.sleep_mode
+    }
+
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -214,28 +321,151 @@ where
         _spi: &mut SPI,
         _delay: &mut DELAY,
         refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         if let Some(refresh_lut) = refresh_rate {
             self.refresh = refresh_lut;
         }
         Ok(())
     }
 
-    fn wait_until_idle(&mut self, _spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.interface.wait_until_idle(delay, IS_BUSY_LOW);
+    fn wait_until_idle(
+        &mut self,
+        _spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(delay, IS_BUSY_LOW)?;
         Ok(())
     }
+
+    fn is_busy(&mut self, _spi: &mut SPI) -> Result<bool, Error<SPI::Error>> {
+        self.interface.is_busy(IS_BUSY_LOW)
+    }
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> Epd2in7<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> Epd2in7<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), SPI::Error> {
+    /// Selects the deep sleep mode [`sleep`](WaveshareDisplay::sleep) enters,
+    /// [`DeepSleepMode::Mode1`] by default
+    pub fn set_deep_sleep_mode(&mut self, mode: DeepSleepMode) {
+        self.sleep_mode = mode;
+    }
+
+    /// Writes a custom waveform of 153 bytes, optionally with voltages, see
+    /// [`waveform`](crate::waveform)
+    ///
+    /// Only refreshes with [`RefreshLut::Quick`] use it, full refreshes load the waveform of the
+    /// OTP.
+    pub fn set_custom_lut(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        waveform: &Ssd16xxWaveform<'_>,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle(spi, delay)?;
+        write_waveform(&mut self.interface, spi, waveform, 153, true)
+    }
+
+    /// Reads the on-chip temperature sensor, in whole °C
+    ///
+    /// The controller answers on DIN, which needs the wiring described in
+    /// [reading from the controller](crate#reading-from-the-controller).
+    pub fn read_temperature(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<i8, Error<SPI::Error>> {
+        let celsius = read_temperature(&mut self.interface, spi, delay)?;
+        // measuring selected the on-chip sensor
+        if self.temperature.is_some() {
+            write_temperature(&mut self.interface, spi, self.temperature)?;
+        }
+        Ok(celsius)
+    }
+
+    /// Sets the temperature the waveforms are chosen for, e.g. from an external sensor, or goes
+    /// back to the on-chip sensor with `None`
+    ///
+    /// The temperature is written again by `init`, e.g. when waking up.
+    pub fn set_temperature(
+        &mut self,
+        spi: &mut SPI,
+        celsius: Option<i8>,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.temperature = celsius;
+        write_temperature(&mut self.interface, spi, celsius)
+    }
+
+    /// Writes the waveform of `bands` made for `celsius`, measured e.g. by an external sensor or
+    /// [`read_temperature`](Self::read_temperature)
+    pub fn set_lut_for_temperature(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        bands: &TemperatureBands<'_, Ssd16xxWaveform<'_>>,
+        celsius: i8,
+    ) -> Result<(), Error<SPI::Error>> {
+        let waveform = bands.select(celsius).ok_or(Error::InvalidWaveform)?;
+        self.set_custom_lut(spi, delay, waveform)
+    }
+
+    /// Displays a frame with 4 gray levels, e.g. the buffer of a [`Display2in7Gray2`]
+    ///
+    /// The buffer holds the two bit planes of [`Gray2`] one after another, the high bits
+    /// first. The low bits are written to the b/w RAM and the high bits to the red
+    /// RAM: `LUT_4GRAY` picks its waveform LUT0 to LUT3 by the (red, b/w) bits and drives them
+    /// from black to white, the mapping of `display_4Gray` in the
+    /// [Waveshare Python driver](https://github.com/waveshareteam/e-Paper/blob/master/RaspberryPi_JetsonNano/python/lib/waveshare_epd/epd2in7_V2.py).
+    ///
+    /// As the 4 gray waveform comes with its own voltages, the display is initialised
+    /// again for black and white frames once the refresh is done.
+    pub fn update_and_display_gray2_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        if buffer.len() != 2 * buffer_len(WIDTH as usize, HEIGHT as usize) {
+            return Err(Error::InvalidBuffer);
+        }
+        let (high, low) = buffer.split_at(buffer.len() / 2);
+
+        self.wait_until_idle(spi, delay)?;
+        self.interface
+            .cmd_with_data(spi, Command::BorderWaveformControl, &[0x00])?;
+        self.interface
+            .cmd_with_data(spi, Command::WriteLutRegister, &LUT_4GRAY[0..153])?;
+        self.interface
+            .cmd_with_data(spi, Command::WriteLutRegisterEnd, &LUT_4GRAY[153..154])?;
+        self.interface
+            .cmd_with_data(spi, Command::GateDrivingVoltage, &LUT_4GRAY[154..155])?;
+        self.interface
+            .cmd_with_data(spi, Command::SourceDrivingVoltage, &LUT_4GRAY[155..158])?;
+        self.interface
+            .cmd_with_data(spi, Command::WriteVcomRegister, &LUT_4GRAY[158..159])?;
+
+        self.use_full_frame(spi, delay)?;
+        self.interface.cmd_with_data(spi, Command::WriteRam, low)?;
+        self.use_full_frame(spi, delay)?;
+        self.interface
+            .cmd_with_data(spi, Command::WriteRam2, high)?;
+
+        // display with the LUT loaded above
+        self.interface
+            .cmd_with_data(spi, Command::DisplayUpdateControl2, &[0xC7])?;
+        self.interface.cmd(spi, Command::MasterActivation)?;
+        self.wait_until_idle(spi, delay)?;
+
+        self.init(spi, delay)
+    }
+
+    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd(spi, command)
     }
 
@@ -246,7 +476,7 @@ where
         start_y: u32,
         end_x: u32,
         end_y: u32,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         assert!(start_x < end_x);
         assert!(start_y < end_y);
 
@@ -275,7 +505,7 @@ where
         delay: &mut DELAY,
         x: u32,
         y: u32,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
         self.interface
             .cmd_with_data(spi, Command::SetRamXAddressCounter, &[(x & 0xFF) as u8])?;
@@ -288,7 +518,11 @@ where
         Ok(())
     }
 
-    fn use_full_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn use_full_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
         // choose full frame/ram
         self.set_ram_area(spi, 0, 0, WIDTH - 1, HEIGHT - 1)?;
 
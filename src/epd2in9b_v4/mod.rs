@@ -4,11 +4,18 @@
 //!
 //! [Reference code](https://github.com/waveshareteam/e-Paper/blob/master/RaspberryPi_JetsonNano/c/lib/e-Paper/EPD_2in9b_V4.c)
 
+use crate::window::{check_window, check_window_buffer};
 use crate::{
     buffer_len,
     color::TriColor,
+    config::EpdConfig,
+    error::Error,
     interface::DisplayInterface,
-    traits::{InternalWiAdditions, WaveshareDisplay, WaveshareThreeColorDisplay},
+    power::NoPowerPin,
+    traits::{
+        BusyPolling, Capabilities, ColorKind, DeepSleepMode, InternalWiAdditions, RefreshLut,
+        WaveshareDisplay, WaveshareDisplayNew, WaveshareThreeColorDisplay,
+    },
 };
 use embedded_hal::{
     delay::DelayNs,
@@ -40,12 +47,26 @@ pub type Display2in9b = crate::graphics::Display<
     TriColor,
 >;
 
+/// Border bits of [`Command::BorderWavefrom`]: the border follows the LUT of the pixels of its
+/// color, picked by their (red, b/w) RAM bits
+fn border_bits(color: TriColor) -> u8 {
+    match color {
+        TriColor::Black => 0x04,
+        TriColor::White => 0x05,
+        TriColor::Chromatic => 0x06,
+    }
+}
+
 /// Epd2in9b (v4) driver
-pub struct Epd2in9b<SPI, BUSY, DC, RST, DELAY> {
+pub struct Epd2in9b<SPI, BUSY, DC, RST, DELAY, PWR = NoPowerPin> {
     /// SPI
-    interface: DisplayInterface<SPI, BUSY, DC, RST, DELAY, SINGLE_BYTE_WRITE>,
+    interface: DisplayInterface<SPI, BUSY, DC, RST, DELAY, PWR, SINGLE_BYTE_WRITE>,
     /// Color
     background_color: TriColor,
+    /// Deep sleep mode entered by `sleep`
+    sleep_mode: DeepSleepMode,
+    /// Color of the border, set again by `init`
+    border: TriColor,
 }
 
 #[allow(dead_code)]
@@ -56,14 +77,21 @@ enum DisplayMode {
     Base,
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> Epd2in9b<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> Epd2in9b<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
+    /// Selects the deep sleep mode [`sleep`](WaveshareDisplay::sleep) enters,
+    /// [`DeepSleepMode::Mode1`] by default
+    pub fn set_deep_sleep_mode(&mut self, mode: DeepSleepMode) {
+        self.sleep_mode = mode;
+    }
+
     /// set the base image before partially update
     ///
     /// <https://github.com/waveshareteam/e-Paper/blob/bc23f8ee814486edb6a364c802847224e079e523/RaspberryPi_JetsonNano/c/examples/EPD_2in9b_V4_test.c#L130>
@@ -73,7 +101,7 @@ where
         black: &[u8],
         chromatic: Option<&[u8]>,
         delay: &mut DELAY,
-    ) -> Result<(), <SPI>::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, black, delay)?;
         if let Some(chromatic) = chromatic {
             self.update_chromatic_frame(spi, delay, chromatic)?;
@@ -95,16 +123,16 @@ where
         &mut self,
         spi: &mut SPI,
         delay: &mut DELAY,
-    ) -> Result<(), <SPI>::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.turn_on_display(spi, delay, DisplayMode::Partial)?;
         Ok(())
     }
 
-    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), SPI::Error> {
+    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd(spi, command)
     }
 
-    fn send_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
+    fn send_data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), Error<SPI::Error>> {
         self.interface.data(spi, data)
     }
 
@@ -113,7 +141,17 @@ where
         spi: &mut SPI,
         delay: &mut DELAY,
         mode: DisplayMode,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
+        self.begin_turn_on_display(spi, mode)?;
+        self.wait_until_idle(spi, delay)
+    }
+
+    /// Same as `turn_on_display`, without waiting for the update to finish
+    fn begin_turn_on_display(
+        &mut self,
+        spi: &mut SPI,
+        mode: DisplayMode,
+    ) -> Result<(), Error<SPI::Error>> {
         self.command(spi, Command::TurnOnDisplay)?;
 
         let data = match mode {
@@ -125,25 +163,25 @@ where
 
         self.send_data(spi, &[data])?;
         self.command(spi, Command::ActivateDisplayUpdateSequence)?;
-        self.wait_until_idle(spi, delay)?;
         Ok(())
     }
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> InternalWiAdditions<SPI, BUSY, DC, RST, DELAY>
-    for Epd2in9b<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> InternalWiAdditions<SPI, BUSY, DC, RST, DELAY>
+    for Epd2in9b<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         let w = self.width();
         let h = self.height();
 
-        self.interface.reset(delay, 200_000, 2_000);
+        self.interface.reset(delay, 200_000, 2_000)?;
 
         self.wait_until_idle(spi, delay)?;
         self.command(spi, Command::SwReset)?;
@@ -168,7 +206,7 @@ where
         self.send_data(spi, &[((h - 1) / 256) as u8])?;
 
         self.command(spi, Command::BorderWavefrom)?;
-        self.send_data(spi, &[0x05])?;
+        self.send_data(spi, &[border_bits(self.border)])?;
 
         self.command(spi, Command::DisplayUpdateControl)?;
         self.send_data(spi, &[0x00])?;
@@ -189,14 +227,15 @@ where
     }
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> WaveshareThreeColorDisplay<SPI, BUSY, DC, RST, DELAY>
-    for Epd2in9b<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareThreeColorDisplay<SPI, BUSY, DC, RST, DELAY>
+    for Epd2in9b<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
     fn update_color_frame(
         &mut self,
@@ -204,7 +243,7 @@ where
         delay: &mut DELAY,
         black: &[u8],
         chromatic: &[u8],
-    ) -> Result<(), <SPI>::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_achromatic_frame(spi, delay, black)?;
         self.update_chromatic_frame(spi, delay, chromatic)?;
         Ok(())
@@ -215,7 +254,7 @@ where
         spi: &mut SPI,
         _delay: &mut DELAY,
         black: &[u8],
-    ) -> Result<(), <SPI>::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.command(spi, Command::WriteBlackData)?;
         self.send_data(spi, black)?;
         Ok(())
@@ -226,57 +265,84 @@ where
         spi: &mut SPI,
         _delay: &mut DELAY,
         chromatic: &[u8],
-    ) -> Result<(), <SPI>::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.command(spi, Command::WriteRedData)?;
         self.send_data(spi, chromatic)?;
         Ok(())
     }
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>
-    for Epd2in9b<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplayNew<SPI, BUSY, DC, RST, DELAY, PWR>
+    for Epd2in9b<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    type DisplayColor = TriColor;
-
-    fn new(
+    fn with_config(
         spi: &mut SPI,
         busy: BUSY,
         dc: DC,
         rst: RST,
         delay: &mut DELAY,
-        delay_us: Option<u32>,
-    ) -> Result<Self, <SPI>::Error>
+        config: EpdConfig<PWR>,
+    ) -> Result<Self, Error<SPI::Error>>
     where
         Self: Sized,
     {
-        let interface = DisplayInterface::new(busy, dc, rst, delay_us);
+        let border = config.border().unwrap_or(TriColor::White);
+        let interface = DisplayInterface::new(busy, dc, rst, config);
         let background_color = DEFAULT_BACKGROUND_COLOR;
 
         let mut epd = Epd2in9b {
             interface,
             background_color,
+            sleep_mode: DeepSleepMode::Mode1,
+            border,
         };
 
         epd.init(spi, delay)?;
 
         Ok(epd)
     }
+}
+
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>
+    for Epd2in9b<SPI, BUSY, DC, RST, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    type DisplayColor = TriColor;
 
-    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), <SPI>::Error> {
+    const CAPABILITIES: Capabilities = Capabilities {
+        color: ColorKind::TriColor,
+        partial_update: true,
+        refresh_luts: &[RefreshLut::Full],
+        quick_refresh: false,
+        busy: BusyPolling::Pin,
+    };
+
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.command(spi, Command::DeepSleep)?;
-        self.send_data(spi, &[1])?;
+        self.send_data(spi, &[self.sleep_mode.ssd16xx_value()])?;
         delay.delay_ms(100);
 
         Ok(())
     }
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), <SPI>::Error> {
+    fn switch_power(&mut self, delay: &mut DELAY, on: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.switch_power(delay, on)
+    }
+
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)?;
         Ok(())
     }
@@ -289,6 +355,14 @@ where
         &self.background_color
     }
 
+    fn set_busy_timeout(&mut self, timeout_us: Option<u32>) {
+        self.interface.set_busy_timeout(timeout_us);
+    }
+
+    fn deep_sleep_mode(&self) -> DeepSleepMode {
+        self.sleep_mode
+    }
+
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -302,7 +376,7 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         _delay: &mut DELAY,
-    ) -> Result<(), <SPI>::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.command(spi, Command::WriteBlackData)?;
         self.send_data(spi, buffer)?;
 
@@ -320,8 +394,9 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), <SPI>::Error> {
-        assert!(width % 8 == 0, "width must multiple of 8");
+    ) -> Result<(), Error<SPI::Error>> {
+        check_window(x, y, width, height, WIDTH, HEIGHT)?;
+        check_window_buffer(buffer, width, height, 1)?;
         let mut x_start = x;
         let mut x_end = x + width;
 
@@ -372,25 +447,33 @@ where
         Ok(())
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), <SPI>::Error> {
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.turn_on_display(spi, delay, DisplayMode::Default)?;
 
         Ok(())
     }
 
+    fn begin_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.begin_turn_on_display(spi, DisplayMode::Default)
+    }
+
     fn update_and_display_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), <SPI>::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.display_frame(spi, delay)?;
 
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), <SPI>::Error> {
+    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         const SIZE: u32 = WIDTH / 8 * HEIGHT;
 
         self.command(spi, Command::WriteBlackData)?;
@@ -407,13 +490,21 @@ where
         &mut self,
         _spi: &mut SPI,
         _delay: &mut DELAY,
-        _refresh_rate: Option<crate::traits::RefreshLut>,
-    ) -> Result<(), <SPI>::Error> {
+        _refresh_rate: Option<RefreshLut>,
+    ) -> Result<(), Error<SPI::Error>> {
         Ok(())
     }
 
-    fn wait_until_idle(&mut self, _spi: &mut SPI, delay: &mut DELAY) -> Result<(), <SPI>::Error> {
-        self.interface.wait_until_idle(delay, IS_BUSY_LOW);
+    fn wait_until_idle(
+        &mut self,
+        _spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(delay, IS_BUSY_LOW)?;
         Ok(())
     }
+
+    fn is_busy(&mut self, _spi: &mut SPI) -> Result<bool, Error<SPI::Error>> {
+        self.interface.is_busy(IS_BUSY_LOW)
+    }
 }
@@ -2,6 +2,9 @@
 //!
 //!
 //! Build with the help of documentation/code from [Waveshare](https://www.waveshare.com/wiki/3.7inch_e-Paper_HAT),
+//!
+//! Besides black and white, the display supports 4 gray levels, see
+//! [`update_and_display_gray2_frame`](EPD3in7::update_and_display_gray2_frame).
 use embedded_hal::{
     delay::DelayNs,
     digital::{InputPin, OutputPin},
@@ -16,8 +19,19 @@ use self::constants::*;
 
 use crate::buffer_len;
 use crate::color::Color;
+#[cfg(feature = "graphics")]
+use crate::color::Gray2;
+use crate::config::EpdConfig;
+use crate::error::Error;
 use crate::interface::DisplayInterface;
-use crate::traits::{InternalWiAdditions, RefreshLut, WaveshareDisplay};
+use crate::power::NoPowerPin;
+use crate::traits::{
+    BusyPolling, Capabilities, ColorKind, DeepSleepMode, InternalWiAdditions, RefreshLut,
+    WaveshareDisplay, WaveshareDisplayNew,
+};
+use crate::type_a::write_waveform;
+use crate::waveform::Ssd16xxWaveform;
+use crate::window::{check_window, check_window_buffer};
 
 /// Width of the display.
 pub const WIDTH: u32 = 280;
@@ -42,36 +56,60 @@ pub type Display3in7 = crate::graphics::Display<
     Color,
 >;
 
+/// Display with Fullsize buffer for use with the 3in7 EPD in 4 gray mode
+#[cfg(feature = "graphics")]
+pub type Display3in7Gray2 = crate::graphics::Display<
+    WIDTH,
+    HEIGHT,
+    false,
+    { 2 * buffer_len(WIDTH as usize, HEIGHT as usize) },
+    Gray2,
+>;
+
+/// Border bits of [`Command::BorderWaveformControl`]: the border follows the LUT of the pixels
+/// with both RAM bits cleared (black) or set (white)
+fn border_bits(color: Color) -> u8 {
+    match color {
+        Color::Black => 0x00,
+        Color::White => 0x03,
+    }
+}
+
 /// EPD3in7 driver
-pub struct EPD3in7<SPI, BUSY, DC, RST, DELAY> {
+pub struct EPD3in7<SPI, BUSY, DC, RST, DELAY, PWR = NoPowerPin> {
     /// Connection Interface
-    interface: DisplayInterface<SPI, BUSY, DC, RST, DELAY, SINGLE_BYTE_WRITE>,
+    interface: DisplayInterface<SPI, BUSY, DC, RST, DELAY, PWR, SINGLE_BYTE_WRITE>,
     /// Background Color
     background_color: Color,
+    /// Deep sleep mode entered by `sleep`
+    sleep_mode: DeepSleepMode,
+    /// Color of the border, set again by `init`
+    border: Color,
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> InternalWiAdditions<SPI, BUSY, DC, RST, DELAY>
-    for EPD3in7<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> InternalWiAdditions<SPI, BUSY, DC, RST, DELAY>
+    for EPD3in7<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         // reset the device
-        self.interface.reset(delay, 30, 10);
+        self.interface.reset(delay, 30, 10)?;
 
         self.interface.cmd(spi, Command::SwReset)?;
         delay.delay_us(300000u32);
 
         self.interface
             .cmd_with_data(spi, Command::AutoWriteRedRamRegularPattern, &[0xF7])?;
-        self.interface.wait_until_idle(delay, IS_BUSY_LOW);
+        self.interface.wait_until_idle(delay, IS_BUSY_LOW)?;
         self.interface
             .cmd_with_data(spi, Command::AutoWriteBwRamRegularPattern, &[0xF7])?;
-        self.interface.wait_until_idle(delay, IS_BUSY_LOW);
+        self.interface.wait_until_idle(delay, IS_BUSY_LOW)?;
 
         self.interface
             .cmd_with_data(spi, Command::GateSetting, &[0xDF, 0x01, 0x00])?;
@@ -83,8 +121,11 @@ where
         self.interface
             .cmd_with_data(spi, Command::DataEntrySequence, &[0x03])?;
 
-        self.interface
-            .cmd_with_data(spi, Command::BorderWaveformControl, &[0x03])?;
+        self.interface.cmd_with_data(
+            spi,
+            Command::BorderWaveformControl,
+            &[border_bits(self.border)],
+        )?;
 
         self.interface.cmd_with_data(
             spi,
@@ -104,16 +145,7 @@ where
             &[0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x4F, 0xFF, 0xFF, 0xFF, 0xFF],
         )?;
 
-        self.interface.cmd_with_data(
-            spi,
-            Command::SetRamXAddressStartEndPosition,
-            &[0x00, 0x00, 0x17, 0x01],
-        )?;
-        self.interface.cmd_with_data(
-            spi,
-            Command::SetRamYAddressStartEndPosition,
-            &[0x00, 0x00, 0xDF, 0x01],
-        )?;
+        self.set_ram_area(spi, 0, 0, WIDTH - 1, HEIGHT - 1)?;
 
         self.interface
             .cmd_with_data(spi, Command::DisplayUpdateSequenceSetting, &[0xCF])?;
@@ -123,44 +155,73 @@ where
     }
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>
-    for EPD3in7<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplayNew<SPI, BUSY, DC, RST, DELAY, PWR>
+    for EPD3in7<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    type DisplayColor = Color;
-
-    fn new(
+    fn with_config(
         spi: &mut SPI,
         busy: BUSY,
         dc: DC,
         rst: RST,
         delay: &mut DELAY,
-        delay_us: Option<u32>,
-    ) -> Result<Self, SPI::Error> {
+        config: EpdConfig<PWR>,
+    ) -> Result<Self, Error<SPI::Error>> {
+        let border = config.bw_border()?.unwrap_or(Color::White);
         let mut epd = EPD3in7 {
-            interface: DisplayInterface::new(busy, dc, rst, delay_us),
+            interface: DisplayInterface::new(busy, dc, rst, config),
             background_color: DEFAULT_BACKGROUND_COLOR,
+            sleep_mode: DeepSleepMode::Mode1,
+            border,
         };
 
         epd.init(spi, delay)?;
         Ok(epd)
     }
+}
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>
+    for EPD3in7<SPI, BUSY, DC, RST, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    type DisplayColor = Color;
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        color: ColorKind::BlackWhite,
+        partial_update: true,
+        refresh_luts: &[
+            RefreshLut::Full,
+            RefreshLut::Quick,
+            RefreshLut::PartialRefresh,
+        ],
+        quick_refresh: false,
+        busy: BusyPolling::Pin,
+    };
+
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)
     }
 
-    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.interface.cmd_with_data(spi, Command::Sleep, &[0xF7])?;
-        self.interface.cmd(spi, Command::PowerOff)?;
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle(spi, delay)?;
         self.interface
-            .cmd_with_data(spi, Command::Sleep2, &[0xA5])?;
-        Ok(())
+            .cmd_with_data(spi, Command::DeepSleep, &[self.sleep_mode.ssd16xx_value()])
+    }
+
+    fn switch_power(&mut self, delay: &mut DELAY, on: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.switch_power(delay, on)
     }
 
     fn set_background_color(&mut self, color: Self::DisplayColor) {
@@ -171,6 +232,14 @@ where
         &self.background_color
     }
 
+    fn set_busy_timeout(&mut self, timeout_us: Option<u32>) {
+        self.interface.set_busy_timeout(timeout_us);
+    }
+
+    fn deep_sleep_mode(&self) -> DeepSleepMode {
+        self.sleep_mode
+    }
+
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -184,7 +253,7 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         assert!(buffer.len() == buffer_len(WIDTH as usize, HEIGHT as usize));
         self.interface
             .cmd_with_data(spi, Command::SetRamXAddressCounter, &[0x00, 0x00])?;
@@ -197,25 +266,41 @@ where
         Ok(())
     }
 
-    #[allow(unused)]
     fn update_partial_frame(
         &mut self,
         spi: &mut SPI,
-        delay: &mut DELAY,
+        _delay: &mut DELAY,
         buffer: &[u8],
         x: u32,
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
-        todo!()
+    ) -> Result<(), Error<SPI::Error>> {
+        check_window(x, y, width, height, WIDTH, HEIGHT)?;
+        check_window_buffer(buffer, width, height, 1)?;
+
+        self.set_ram_area(spi, x, y, x + width - 1, y + height - 1)?;
+        self.set_ram_counter(spi, x, y)?;
+        self.interface
+            .cmd_with_data(spi, Command::WriteRam, buffer)?;
+
+        // the full frame updates expect the whole RAM as window
+        self.set_ram_area(spi, 0, 0, WIDTH - 1, HEIGHT - 1)
+    }
+
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.begin_display_frame(spi, delay)?;
+        self.wait_until_idle(spi, delay)
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn begin_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
         //self.interface
         //    .cmd_with_data(spi, Command::WRITE_LUT_REGISTER, &LUT_1GRAY_GC)?;
         self.interface.cmd(spi, Command::DisplayUpdateSequence)?;
-        self.interface.wait_until_idle(delay, IS_BUSY_LOW);
         Ok(())
     }
 
@@ -224,13 +309,13 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.display_frame(spi, delay)?;
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn clear_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.interface
             .cmd_with_data(spi, Command::SetRamXAddressCounter, &[0x00, 0x00])?;
         self.interface
@@ -248,7 +333,7 @@ where
         spi: &mut SPI,
         _delay: &mut DELAY,
         refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         let buffer = match refresh_rate {
             Some(RefreshLut::Full) | None => &LUT_1GRAY_GC,
             Some(RefreshLut::Quick | RefreshLut::PartialRefresh) => &LUT_1GRAY_DU,
@@ -259,8 +344,136 @@ where
         Ok(())
     }
 
-    fn wait_until_idle(&mut self, _spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
-        self.interface.wait_until_idle(delay, IS_BUSY_LOW);
+    fn wait_until_idle(
+        &mut self,
+        _spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(delay, IS_BUSY_LOW)?;
         Ok(())
     }
+
+    fn is_busy(&mut self, _spi: &mut SPI) -> Result<bool, Error<SPI::Error>> {
+        self.interface.is_busy(IS_BUSY_LOW)
+    }
+}
+
+impl<SPI, BUSY, DC, RST, DELAY, PWR> EPD3in7<SPI, BUSY, DC, RST, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    /// Selects the deep sleep mode [`sleep`](WaveshareDisplay::sleep) enters,
+    /// [`DeepSleepMode::Mode1`] by default
+    pub fn set_deep_sleep_mode(&mut self, mode: DeepSleepMode) {
+        self.sleep_mode = mode;
+    }
+
+    /// Writes a custom waveform of 105 bytes, see [`waveform`](crate::waveform)
+    pub fn set_custom_lut(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        waveform: &Ssd16xxWaveform<'_>,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.wait_until_idle(spi, delay)?;
+        write_waveform(&mut self.interface, spi, waveform, 105, false)
+    }
+
+    /// Displays a frame with 4 gray levels, e.g. the buffer of a [`Display3in7Gray2`]
+    ///
+    /// The buffer holds the two bit planes of [`Gray2`] one after another, the high bits
+    /// first. The low bits go to the b/w RAM and the high bits to the red RAM:
+    /// `LUT_4GRAY_GC` picks its waveform LUT0 to LUT3 by the (red, b/w) bits and drives them
+    /// from black to white, the mapping of `display_4Gray` in the
+    /// [Waveshare Python driver](https://github.com/waveshareteam/e-Paper/blob/master/RaspberryPi_JetsonNano/python/lib/waveshare_epd/epd3in7.py).
+    ///
+    /// The black/white waveform is loaded again once the refresh is done.
+    pub fn update_and_display_gray2_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        if buffer.len() != 2 * buffer_len(WIDTH as usize, HEIGHT as usize) {
+            return Err(Error::InvalidBuffer);
+        }
+        let (high, low) = buffer.split_at(buffer.len() / 2);
+
+        self.write_ram(spi, Command::WriteRam, low)?;
+        self.write_ram(spi, Command::WriteRam2, high)?;
+
+        self.interface
+            .cmd_with_data(spi, Command::WriteLutRegister, &LUT_4GRAY_GC)?;
+        self.interface
+            .cmd_with_data(spi, Command::DisplayUpdateSequenceSetting, &[0xC7])?;
+        self.interface.cmd(spi, Command::DisplayUpdateSequence)?;
+        self.interface.wait_until_idle(delay, IS_BUSY_LOW)?;
+
+        // back to the settings of init
+        self.interface
+            .cmd_with_data(spi, Command::DisplayUpdateSequenceSetting, &[0xCF])?;
+        self.set_lut(spi, delay, Some(RefreshLut::Full))
+    }
+
+    /// Sets the RAM window, the controller addresses pixels rather than bytes horizontally
+    fn set_ram_area(
+        &mut self,
+        spi: &mut SPI,
+        start_x: u32,
+        start_y: u32,
+        end_x: u32,
+        end_y: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.cmd_with_data(
+            spi,
+            Command::SetRamXAddressStartEndPosition,
+            &[
+                start_x as u8,
+                (start_x >> 8) as u8,
+                end_x as u8,
+                (end_x >> 8) as u8,
+            ],
+        )?;
+        self.interface.cmd_with_data(
+            spi,
+            Command::SetRamYAddressStartEndPosition,
+            &[
+                start_y as u8,
+                (start_y >> 8) as u8,
+                end_y as u8,
+                (end_y >> 8) as u8,
+            ],
+        )
+    }
+
+    fn set_ram_counter(&mut self, spi: &mut SPI, x: u32, y: u32) -> Result<(), Error<SPI::Error>> {
+        self.interface.cmd_with_data(
+            spi,
+            Command::SetRamXAddressCounter,
+            &[x as u8, (x >> 8) as u8],
+        )?;
+        self.interface.cmd_with_data(
+            spi,
+            Command::SetRamYAddressCounter,
+            &[y as u8, (y >> 8) as u8],
+        )
+    }
+
+    fn write_ram(
+        &mut self,
+        spi: &mut SPI,
+        ram: Command,
+        data: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .cmd_with_data(spi, Command::SetRamXAddressCounter, &[0x00, 0x00])?;
+        self.interface
+            .cmd_with_data(spi, Command::SetRamYAddressCounter, &[0x00, 0x00])?;
+        self.interface.cmd_with_data(spi, ram, data)
+    }
 }
@@ -14,9 +14,15 @@ use embedded_hal::{
 
 use crate::{
     buffer_len,
-    color::OctColor,
+    color::{Lab, OctColor, Palette, PaletteEntry, TriColor},
+    config::EpdConfig,
+    error::Error,
     interface::DisplayInterface,
-    traits::{InternalWiAdditions, WaveshareDisplay},
+    power::NoPowerPin,
+    traits::{
+        BusyPolling, Capabilities, ColorKind, InternalWiAdditions, RefreshLut, WaveshareDisplay,
+        WaveshareDisplayNew,
+    },
 };
 
 use self::command::Command;
@@ -39,29 +45,90 @@ pub const WIDTH: u32 = 800;
 pub const HEIGHT: u32 = 480;
 /// Default Background Color
 pub const DEFAULT_BACKGROUND_COLOR: OctColor = OctColor::White;
+/// Colors of the pigments of the 7.3" ACeP panel
+///
+/// The colors are the saturated palette of the Pimoroni inky library for the same 800x480 panel
+/// (`inky_ac073tc1a.py`). Measure your panel under your lighting and use [`Palette::from_rgb`]
+/// for an accurate calibration.
+///
+/// Use it to match or dither graphics to the display, e.g. with
+/// [`Dither::with_palette`](crate::graphics::dither::Dither::with_palette).
+pub const PALETTE: Palette = match Palette::from_entries([
+    PaletteEntry::new(
+        OctColor::Black,
+        (0x00, 0x00, 0x00),
+        Lab::new(0.00, 0.00, 0.00),
+    ),
+    PaletteEntry::new(
+        OctColor::White,
+        (0xd9, 0xf2, 0xff),
+        Lab::new(94.09, -5.57, -9.01),
+    ),
+    PaletteEntry::new(
+        OctColor::Green,
+        (0x03, 0x7c, 0x4c),
+        Lab::new(45.57, -41.38, 18.17),
+    ),
+    PaletteEntry::new(
+        OctColor::Blue,
+        (0x1b, 0x2e, 0x9b),
+        Lab::new(25.42, 34.43, -60.85),
+    ),
+    PaletteEntry::new(
+        OctColor::Red,
+        (0xf5, 0x24, 0x00),
+        Lab::new(52.59, 73.61, 65.74),
+    ),
+    PaletteEntry::new(
+        OctColor::Yellow,
+        (0xff, 0xff, 0x44),
+        Lab::new(97.31, -20.20, 82.93),
+    ),
+    PaletteEntry::new(
+        OctColor::Orange,
+        (0xef, 0x79, 0x2c),
+        Lab::new(63.51, 40.77, 59.58),
+    ),
+]) {
+    Some(palette) => palette,
+    None => panic!("the palette is not in the order of the nibbles"),
+};
+
 /// Default mode of writing data (single byte vs blockwise)
 const SINGLE_BYTE_WRITE: bool = true;
 
+/// Color of the border for a [`TriColor`] of the config, red for [`TriColor::Chromatic`]
+fn border_color(color: TriColor) -> OctColor {
+    match color {
+        TriColor::Black => OctColor::Black,
+        TriColor::White => OctColor::White,
+        TriColor::Chromatic => OctColor::Red,
+    }
+}
+
 /// Epd57n3f driver
-pub struct Epd7in3f<SPI, BUSY, DC, RST, DELAY> {
+pub struct Epd7in3f<SPI, BUSY, DC, RST, DELAY, PWR = NoPowerPin> {
     /// Connection Interface
-    interface: DisplayInterface<SPI, BUSY, DC, RST, DELAY, SINGLE_BYTE_WRITE>,
+    interface: DisplayInterface<SPI, BUSY, DC, RST, DELAY, PWR, SINGLE_BYTE_WRITE>,
     /// Background Color
     color: OctColor,
+    /// Color of the border, set again by `init`
+    border: OctColor,
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> InternalWiAdditions<SPI, BUSY, DC, RST, DELAY>
-    for Epd7in3f<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> InternalWiAdditions<SPI, BUSY, DC, RST, DELAY>
+    for Epd7in3f<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), <SPI>::Error> {
-        self.interface.reset(delay, 20_000, 2_000);
-        self.wait_busy_low(delay);
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.reset(delay, 20_000, 2_000)?;
+        self.wait_busy_low(delay)?;
         delay.delay_ms(30);
 
         self.cmd_with_data(spi, Command::CMDH, &[0x49, 0x55, 0x20, 0x08, 0x09, 0x18])?;
@@ -74,7 +141,9 @@ where
         self.cmd_with_data(spi, Command::IPC, &[0x00, 0x04])?;
         self.cmd_with_data(spi, Command::Ox30, &[0x3C])?;
         self.cmd_with_data(spi, Command::TSE, &[0x00])?;
-        self.cmd_with_data(spi, Command::Ox50, &[0x3F])?;
+        // the border color in the upper 3 bits
+        let border = (self.border.get_nibble() & 0b111) << 5;
+        self.cmd_with_data(spi, Command::Ox50, &[border | 0x1F])?;
         self.cmd_with_data(spi, Command::Ox60, &[0x02, 0x00])?;
         self.cmd_with_data(spi, Command::Ox61, &[0x03, 0x20, 0x01, 0xE0])?;
         self.cmd_with_data(spi, Command::Ox82, &[0x1E])?;
@@ -86,43 +155,72 @@ where
     }
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>
-    for Epd7in3f<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplayNew<SPI, BUSY, DC, RST, DELAY, PWR>
+    for Epd7in3f<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    type DisplayColor = OctColor;
-
-    fn new(
+    fn with_config(
         spi: &mut SPI,
         busy: BUSY,
         dc: DC,
         rst: RST,
         delay: &mut DELAY,
-        delay_us: Option<u32>,
-    ) -> Result<Self, <SPI>::Error>
+        config: EpdConfig<PWR>,
+    ) -> Result<Self, Error<SPI::Error>>
     where
         Self: Sized,
     {
-        let interface = DisplayInterface::new(busy, dc, rst, delay_us);
+        let border = config.border().map_or(OctColor::White, border_color);
+        let interface = DisplayInterface::new(busy, dc, rst, config);
         let color = DEFAULT_BACKGROUND_COLOR;
 
-        let mut epd = Epd7in3f { interface, color };
+        let mut epd = Epd7in3f {
+            interface,
+            color,
+            border,
+        };
 
         epd.init(spi, delay)?;
 
         Ok(epd)
     }
+}
+
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>
+    for Epd7in3f<SPI, BUSY, DC, RST, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    type DisplayColor = OctColor;
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        color: ColorKind::Octal,
+        partial_update: false,
+        refresh_luts: &[RefreshLut::Full],
+        quick_refresh: false,
+        busy: BusyPolling::Pin,
+    };
 
-    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), <SPI>::Error> {
+    fn sleep(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.cmd_with_data(spi, Command::DeepSleep, &[0xA5])
     }
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), <SPI>::Error> {
+    fn switch_power(&mut self, delay: &mut DELAY, on: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.switch_power(delay, on)
+    }
+
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)
     }
 
@@ -134,6 +232,10 @@ where
         &self.color
     }
 
+    fn set_busy_timeout(&mut self, timeout_us: Option<u32>) {
+        self.interface.set_busy_timeout(timeout_us);
+    }
+
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -147,7 +249,7 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), <SPI>::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.wait_until_idle(spi, delay)?;
         self.cmd_with_data(spi, Command::DataStartTransmission, buffer)
     }
@@ -161,37 +263,47 @@ where
         _y: u32,
         _width: u32,
         _height: u32,
-    ) -> Result<(), <SPI>::Error> {
-        unimplemented!()
+    ) -> Result<(), Error<SPI::Error>> {
+        Err(Error::Unsupported)
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), <SPI>::Error> {
-        self.command(spi, Command::PowerOn)?;
-        self.wait_busy_low(delay);
-
-        self.cmd_with_data(spi, Command::DataFresh, &[0x00])?;
-        self.wait_busy_low(delay);
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.begin_display_frame(spi, delay)?;
+        self.wait_busy_low(delay)?;
 
         self.cmd_with_data(spi, Command::PowerOff, &[0x00])?;
-        self.wait_busy_low(delay);
+        self.wait_busy_low(delay)?;
 
         Ok(())
     }
 
+    /// The panel isn't powered off after the refresh like in
+    /// [`display_frame`](WaveshareDisplay::display_frame), use `sleep` for that once it's idle.
+    fn begin_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.command(spi, Command::PowerOn)?;
+        self.wait_busy_low(delay)?;
+
+        self.cmd_with_data(spi, Command::DataFresh, &[0x00])
+    }
+
     fn update_and_display_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), <SPI>::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.display_frame(spi, delay)
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), <SPI>::Error> {
+    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         let bg = OctColor::colors_byte(self.color, self.color);
 
-        self.wait_busy_low(delay);
+        self.wait_busy_low(delay)?;
         self.command(spi, Command::DataStartTransmission)?;
         self.interface.data_x_times(spi, bg, WIDTH * HEIGHT / 2)?;
 
@@ -202,26 +314,38 @@ where
         &mut self,
         _spi: &mut SPI,
         _delay: &mut DELAY,
-        _refresh_rate: Option<crate::traits::RefreshLut>,
-    ) -> Result<(), <SPI>::Error> {
-        unimplemented!()
+        refresh_rate: Option<RefreshLut>,
+    ) -> Result<(), Error<SPI::Error>> {
+        match refresh_rate {
+            Some(RefreshLut::Full) | None => Ok(()),
+            Some(RefreshLut::Quick | RefreshLut::PartialRefresh) => Err(Error::Unsupported),
+        }
     }
 
-    fn wait_until_idle(&mut self, _spi: &mut SPI, delay: &mut DELAY) -> Result<(), <SPI>::Error> {
-        self.wait_busy_low(delay);
+    fn wait_until_idle(
+        &mut self,
+        _spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.wait_busy_low(delay)?;
         Ok(())
     }
+
+    fn is_busy(&mut self, _spi: &mut SPI) -> Result<bool, Error<SPI::Error>> {
+        self.interface.is_busy(true)
+    }
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> Epd7in3f<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> Epd7in3f<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), SPI::Error> {
+    fn command(&mut self, spi: &mut SPI, command: Command) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd(spi, command)
     }
 
@@ -230,16 +354,20 @@ where
         spi: &mut SPI,
         command: Command,
         data: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(spi, command, data)
     }
 
-    fn wait_busy_low(&mut self, delay: &mut DELAY) {
-        self.interface.wait_until_idle(delay, true);
+    fn wait_busy_low(&mut self, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.interface.wait_until_idle(delay, true)
     }
 
     /// Show 7 blocks of color, used for quick testing
-    pub fn show_7block(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    pub fn show_7block(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
         let color_7 = [
             OctColor::Black,
             OctColor::White,
@@ -0,0 +1,338 @@
+//! Async driver for the Waveshare 7.5" E-Ink Display (V2), built on `embedded-hal-async`
+
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal_async::{delay::DelayNs, digital::Wait, spi::SpiDevice};
+
+use crate::color::Color;
+use crate::config::EpdConfig;
+use crate::error::Error;
+use crate::interface_async::DisplayInterfaceAsync;
+use crate::power::NoPowerPin;
+use crate::traits::{BusyPolling, Capabilities, ColorKind, RefreshLut};
+use crate::traits_async::{
+    InternalWiAdditionsAsync, WaveshareDisplayAsync, WaveshareDisplayAsyncNew,
+};
+use crate::window::{check_window, check_window_buffer};
+
+use super::command::Command;
+use super::{border_bits, DEFAULT_BACKGROUND_COLOR, HEIGHT, IS_BUSY_LOW, SINGLE_BYTE_WRITE, WIDTH};
+
+/// Epd7in5 (V2) async driver
+///
+/// Same as [`Epd7in5`](super::Epd7in5), but implements [`WaveshareDisplayAsync`].
+pub struct Epd7in5Async<SPI, BUSY, DC, RST, DELAY, PWR = NoPowerPin> {
+    /// Connection Interface
+    interface: DisplayInterfaceAsync<SPI, BUSY, DC, RST, DELAY, PWR, SINGLE_BYTE_WRITE>,
+    /// Background Color
+    color: Color,
+    /// LUT refresh mode
+    refresh: RefreshLut,
+    /// Color of the border, set again by `init`
+    border: Color,
+}
+
+impl<SPI, BUSY, DC, RST, DELAY, PWR> InternalWiAdditionsAsync<SPI, BUSY, DC, RST, DELAY>
+    for Epd7in5Async<SPI, BUSY, DC, RST, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin + Wait,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    async fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        // Reset the device
+        self.interface.reset(delay, 10_000, 2_000).await?;
+
+        // see Epd7in5::init for the references of this sequence
+        self.cmd_with_data(spi, Command::BoosterSoftStart, &[0x17, 0x17, 0x28, 0x17])
+            .await?;
+        self.cmd(spi, Command::PowerOn).await?;
+        delay.delay_ms(100).await;
+        self.wait_until_idle(spi, delay).await?;
+        self.cmd_with_data(spi, Command::PanelSetting, &[0x1F])
+            .await?; // Sets black and white as opposed to black, white and red.
+                     // Sets NEW/OLD buffer behavior, polarity and the border
+        self.cmd_with_data(
+            spi,
+            Command::VcomAndDataIntervalSetting,
+            &[border_bits(self.border), 0x07],
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplayAsyncNew<SPI, BUSY, DC, RST, DELAY, PWR>
+    for Epd7in5Async<SPI, BUSY, DC, RST, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin + Wait,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    async fn with_config(
+        spi: &mut SPI,
+        busy: BUSY,
+        dc: DC,
+        rst: RST,
+        delay: &mut DELAY,
+        config: EpdConfig<PWR>,
+    ) -> Result<Self, Error<SPI::Error>> {
+        let border = config.bw_border()?.unwrap_or(Color::White);
+        let interface = DisplayInterfaceAsync::new(busy, dc, rst, config);
+        let color = DEFAULT_BACKGROUND_COLOR;
+
+        let mut epd = Epd7in5Async {
+            interface,
+            color,
+            refresh: RefreshLut::default(),
+            border,
+        };
+
+        epd.init(spi, delay).await?;
+
+        Ok(epd)
+    }
+}
+
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplayAsync<SPI, BUSY, DC, RST, DELAY>
+    for Epd7in5Async<SPI, BUSY, DC, RST, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin + Wait,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    type DisplayColor = Color;
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        color: ColorKind::BlackWhite,
+        partial_update: true,
+        refresh_luts: &[
+            RefreshLut::Full,
+            RefreshLut::Quick,
+            RefreshLut::PartialRefresh,
+        ],
+        quick_refresh: false,
+        busy: BusyPolling::Command,
+    };
+
+    async fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.init(spi, delay).await
+    }
+
+    async fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.cmd(spi, Command::PowerOff).await?;
+        self.wait_until_idle(spi, delay).await?;
+        self.cmd_with_data(spi, Command::DeepSleep, &[0xA5]).await
+    }
+
+    async fn switch_power(&mut self, delay: &mut DELAY, on: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.switch_power(delay, on).await
+    }
+
+    async fn update_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.cmd_with_data(spi, Command::DataStartTransmission2, buffer)
+            .await
+    }
+
+    async fn update_partial_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        check_window(x, y, width, height, WIDTH, HEIGHT)?;
+        check_window_buffer(buffer, width, height, 1)?;
+
+        let x_end = x + width - 1;
+        let y_end = y + height - 1;
+
+        let pt_scan = 0x01; // Gates scan both inside and outside of the partial window. (default)
+
+        self.cmd(spi, Command::PartialIn).await?;
+        self.cmd_with_data(
+            spi,
+            Command::PartialWindow,
+            &[
+                (x >> 8) as u8,
+                x as u8,
+                (x_end >> 8) as u8,
+                x_end as u8,
+                (y >> 8) as u8,
+                y as u8,
+                (y_end >> 8) as u8,
+                y_end as u8,
+                pt_scan,
+            ],
+        )
+        .await?;
+
+        self.update_frame(spi, buffer, delay).await?;
+
+        self.cmd(spi, Command::PartialOut).await
+    }
+
+    async fn display_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.begin_display_frame(spi, delay).await?;
+        self.wait_until_idle(spi, delay).await
+    }
+
+    async fn begin_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.cmd(spi, Command::DisplayRefresh).await
+    }
+
+    async fn update_and_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.update_frame(spi, buffer, delay).await?;
+        self.display_frame(spi, delay).await
+    }
+
+    async fn clear_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.cmd(spi, Command::DataStartTransmission2).await?;
+        self.interface
+            .data_x_times(spi, 0xFF, WIDTH / 8 * HEIGHT)
+            .await?;
+
+        self.display_frame(spi, delay).await
+    }
+
+    fn set_background_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
+    fn background_color(&self) -> &Color {
+        &self.color
+    }
+
+    fn set_busy_timeout(&mut self, timeout_us: Option<u32>) {
+        self.interface.set_busy_timeout(timeout_us);
+    }
+
+    fn width(&self) -> u32 {
+        WIDTH
+    }
+
+    fn height(&self) -> u32 {
+        HEIGHT
+    }
+
+    async fn set_lut(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+        refresh_rate: Option<RefreshLut>,
+    ) -> Result<(), Error<SPI::Error>> {
+        let refresh_rate = refresh_rate.unwrap_or(RefreshLut::Full);
+
+        if self.refresh == refresh_rate {
+            return Ok(());
+        }
+
+        if self.refresh == RefreshLut::Quick {
+            // Return booster power settings to default
+            self.cmd_with_data(spi, Command::BoosterSoftStart, &[0x17, 0x17, 0x28, 0x17])
+                .await?;
+        }
+
+        // see Epd7in5::set_lut about the (undocumented) use of the cascade setting
+        match refresh_rate {
+            RefreshLut::Full => {
+                self.cmd_with_data(spi, Command::CascadeSetting, &[0x00])
+                    .await?;
+            }
+            RefreshLut::Quick => {
+                self.cmd_with_data(spi, Command::BoosterSoftStart, &[0x27, 0x27, 0x18, 0x17])
+                    .await?;
+                self.cmd_with_data(spi, Command::CascadeSetting, &[0x02])
+                    .await?;
+                self.cmd_with_data(spi, Command::ForceTemperature, &[0x5A])
+                    .await?;
+            }
+            RefreshLut::PartialRefresh => {
+                self.cmd_with_data(spi, Command::CascadeSetting, &[0x02])
+                    .await?;
+                self.cmd_with_data(spi, Command::ForceTemperature, &[0x6E])
+                    .await?;
+            }
+        }
+
+        self.refresh = refresh_rate;
+
+        Ok(())
+    }
+
+    async fn wait_until_idle(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface
+            .wait_until_idle_with_cmd(spi, delay, IS_BUSY_LOW, Command::GetStatus)
+            .await
+    }
+
+    async fn is_busy(&mut self, spi: &mut SPI) -> Result<bool, Error<SPI::Error>> {
+        self.interface
+            .is_busy_with_cmd(spi, IS_BUSY_LOW, Command::GetStatus)
+            .await
+    }
+}
+
+impl<SPI, BUSY, DC, RST, DELAY, PWR> Epd7in5Async<SPI, BUSY, DC, RST, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin + Wait,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    async fn cmd(&mut self, spi: &mut SPI, command: Command) -> Result<(), Error<SPI::Error>> {
+        self.interface.cmd(spi, command).await
+    }
+
+    async fn cmd_with_data(
+        &mut self,
+        spi: &mut SPI,
+        command: Command,
+        data: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.interface.cmd_with_data(spi, command, data).await
+    }
+}
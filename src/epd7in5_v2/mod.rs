@@ -17,13 +17,25 @@ use embedded_hal::{
 };
 
 use crate::color::Color;
+use crate::config::EpdConfig;
+use crate::error::Error;
 use crate::interface::DisplayInterface;
-use crate::traits::{InternalWiAdditions, RefreshLut, WaveshareDisplay};
+use crate::power::NoPowerPin;
+use crate::traits::{
+    BusyPolling, Capabilities, ColorKind, InternalWiAdditions, RefreshLut, WaveshareDisplay,
+    WaveshareDisplayNew,
+};
+use crate::window::{check_window, check_window_buffer};
 
 pub(crate) mod command;
 use self::command::Command;
 use crate::buffer_len;
 
+#[cfg(feature = "async")]
+mod asynch;
+#[cfg(feature = "async")]
+pub use asynch::Epd7in5Async;
+
 /// Full size buffer for use with the 7in5 v2 EPD
 #[cfg(feature = "graphics")]
 pub type Display7in5 = crate::graphics::Display<
@@ -43,28 +55,42 @@ pub const DEFAULT_BACKGROUND_COLOR: Color = Color::White;
 const IS_BUSY_LOW: bool = true;
 const SINGLE_BYTE_WRITE: bool = false;
 
+/// First byte of [`Command::VcomAndDataIntervalSetting`] with the border bits, for the
+/// NEW/OLD buffer behavior of the driver
+fn border_bits(color: Color) -> u8 {
+    match color {
+        Color::Black => 0x19,
+        Color::White => 0x29,
+    }
+}
+
 /// Epd7in5 (V2) driver
-pub struct Epd7in5<SPI, BUSY, DC, RST, DELAY> {
+pub struct Epd7in5<SPI, BUSY, DC, RST, DELAY, PWR = NoPowerPin> {
     /// Connection Interface
-    interface: DisplayInterface<SPI, BUSY, DC, RST, DELAY, SINGLE_BYTE_WRITE>,
+    interface: DisplayInterface<SPI, BUSY, DC, RST, DELAY, PWR, SINGLE_BYTE_WRITE>,
     /// Background Color
     color: Color,
     /// LUT refresh mode
     refresh: RefreshLut,
+    /// Temperature forced instead of the reading of the sensor
+    temperature: Option<i8>,
+    /// Color of the border, set again by `init`
+    border: Color,
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> InternalWiAdditions<SPI, BUSY, DC, RST, DELAY>
-    for Epd7in5<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> InternalWiAdditions<SPI, BUSY, DC, RST, DELAY>
+    for Epd7in5<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         // Reset the device
-        self.interface.reset(delay, 10_000, 2_000);
+        self.interface.reset(delay, 10_000, 2_000)?;
 
         // V2 procedure as described here:
         // https://github.com/waveshare/e-Paper/blob/master/RaspberryPi%26JetsonNano/python/lib/waveshare_epd/epd7in5bc_V2.py
@@ -78,60 +104,100 @@ where
         delay.delay_ms(100);
         self.wait_until_idle(spi, delay)?;
         self.cmd_with_data(spi, Command::PanelSetting, &[0x1F])?; // Sets black and white as opposed to black, white and red.
-        self.cmd_with_data(spi, Command::VcomAndDataIntervalSetting, &[0x29, 0x07])?; // Sets NEW/OLD buffer behavior and polarity
+                                                                  // Sets NEW/OLD buffer behavior, polarity and the border
+        self.cmd_with_data(
+            spi,
+            Command::VcomAndDataIntervalSetting,
+            &[border_bits(self.border), 0x07],
+        )?;
+        if self.refresh == RefreshLut::Full {
+            self.apply_temperature(spi)?;
+        }
         Ok(())
     }
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>
-    for Epd7in5<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplayNew<SPI, BUSY, DC, RST, DELAY, PWR>
+    for Epd7in5<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    type DisplayColor = Color;
-    fn new(
+    fn with_config(
         spi: &mut SPI,
         busy: BUSY,
         dc: DC,
         rst: RST,
         delay: &mut DELAY,
-        delay_us: Option<u32>,
-    ) -> Result<Self, SPI::Error> {
-        let interface = DisplayInterface::new(busy, dc, rst, delay_us);
+        config: EpdConfig<PWR>,
+    ) -> Result<Self, Error<SPI::Error>> {
+        let border = config.bw_border()?.unwrap_or(Color::White);
+        let interface = DisplayInterface::new(busy, dc, rst, config);
         let color = DEFAULT_BACKGROUND_COLOR;
 
         let mut epd = Epd7in5 {
             interface,
             color,
             refresh: RefreshLut::default(),
+            temperature: None,
+            border,
         };
 
         epd.init(spi, delay)?;
 
         Ok(epd)
     }
+}
+
+impl<SPI, BUSY, DC, RST, DELAY, PWR> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>
+    for Epd7in5<SPI, BUSY, DC, RST, DELAY, PWR>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    type DisplayColor = Color;
 
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    const CAPABILITIES: Capabilities = Capabilities {
+        color: ColorKind::BlackWhite,
+        partial_update: true,
+        refresh_luts: &[
+            RefreshLut::Full,
+            RefreshLut::Quick,
+            RefreshLut::PartialRefresh,
+        ],
+        quick_refresh: false,
+        busy: BusyPolling::Command,
+    };
+
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.init(spi, delay)
     }
 
-    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.cmd(spi, Command::PowerOff)?;
         self.wait_until_idle(spi, delay)?;
         self.cmd_with_data(spi, Command::DeepSleep, &[0xA5])?;
         Ok(())
     }
 
+    fn switch_power(&mut self, delay: &mut DELAY, on: bool) -> Result<(), Error<SPI::Error>> {
+        self.interface.switch_power(delay, on)
+    }
+
     fn update_frame(
         &mut self,
         spi: &mut SPI,
         buffer: &[u8],
         _delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.cmd_with_data(spi, Command::DataStartTransmission2, buffer)?;
         Ok(())
     }
@@ -145,20 +211,13 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         if width == 0 || height == 0 {
             return Ok(());
         }
 
-        if x % 8 != 0 || width % 8 != 0 {
-            panic!("x and width should be byte-aligned (multiples of 8).")
-        }
-
-        let expected_size = buffer_len(width as usize, height as usize);
-        let actual_size = buffer.len();
-        if actual_size != expected_size {
-            panic!("Buffer is incorrect size. Expected: {expected_size}. Actual: {actual_size}.")
-        }
+        check_window(x, y, width, height, WIDTH, HEIGHT)?;
+        check_window_buffer(buffer, width, height, 1)?;
 
         let x_end = x + width - 1;
 
@@ -192,9 +251,17 @@ where
         Ok(())
     }
 
-    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.begin_display_frame(spi, delay)?;
+        self.wait_until_idle(spi, delay)
+    }
+
+    fn begin_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
         self.cmd(spi, Command::DisplayRefresh)?;
-        self.wait_until_idle(spi, delay)?;
         Ok(())
     }
 
@@ -203,14 +270,14 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.update_frame(spi, buffer, delay)?;
         self.cmd(spi, Command::DisplayRefresh)?;
         self.wait_until_idle(spi, delay)?;
         Ok(())
     }
 
-    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
         self.cmd(spi, Command::DataStartTransmission2)?;
         self.interface.data_x_times(spi, 0xFF, WIDTH / 8 * HEIGHT)?;
 
@@ -227,6 +294,10 @@ where
         &self.color
     }
 
+    fn set_busy_timeout(&mut self, timeout_us: Option<u32>) {
+        self.interface.set_busy_timeout(timeout_us);
+    }
+
     fn width(&self) -> u32 {
         WIDTH
     }
@@ -240,7 +311,7 @@ where
         spi: &mut SPI,
         _delay: &mut DELAY,
         refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         let refresh_rate = if let Some(refresh_rate) = refresh_rate {
             refresh_rate
         } else {
@@ -263,7 +334,7 @@ where
         match refresh_rate {
             RefreshLut::Full => {
                 // This disables custom LUT indices and uses normal temperature-based operation
-                self.cmd_with_data(spi, Command::CascadeSetting, &[0x00])?;
+                self.apply_temperature(spi)?;
             }
             RefreshLut::Quick => {
                 // Booster power settings for quick LUT
@@ -288,21 +359,75 @@ where
         Ok(())
     }
 
-    fn wait_until_idle(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error> {
+    fn wait_until_idle(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface
             .wait_until_idle_with_cmd(spi, delay, IS_BUSY_LOW, Command::GetStatus)
     }
+
+    fn is_busy(&mut self, spi: &mut SPI) -> Result<bool, Error<SPI::Error>> {
+        self.interface
+            .is_busy_with_cmd(spi, IS_BUSY_LOW, Command::GetStatus)
+    }
 }
 
-impl<SPI, BUSY, DC, RST, DELAY> Epd7in5<SPI, BUSY, DC, RST, DELAY>
+impl<SPI, BUSY, DC, RST, DELAY, PWR> Epd7in5<SPI, BUSY, DC, RST, DELAY, PWR>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
-    fn cmd(&mut self, spi: &mut SPI, command: Command) -> Result<(), SPI::Error> {
+    /// Reads the on-chip temperature sensor, in whole °C
+    ///
+    /// The controller answers on DIN, which needs the wiring described in
+    /// [reading from the controller](crate#reading-from-the-controller).
+    pub fn read_temperature(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<i8, Error<SPI::Error>> {
+        self.wait_until_idle(spi, delay)?;
+        self.cmd(spi, Command::TemperatureSensor)?;
+        // integer part first, then the fraction in the upper bits
+        let mut data = [0; 2];
+        self.interface.read(spi, &mut data)?;
+        Ok(data[0] as i8)
+    }
+
+    /// Sets the temperature the full refresh waveform is chosen for, e.g. from an external
+    /// sensor, or goes back to the on-chip sensor with `None`
+    ///
+    /// The quick and partial refreshes select their waveform through the same register, so the
+    /// temperature only applies to [`RefreshLut::Full`].
+    pub fn set_temperature(
+        &mut self,
+        spi: &mut SPI,
+        celsius: Option<i8>,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.temperature = celsius;
+        if self.refresh == RefreshLut::Full {
+            self.apply_temperature(spi)?;
+        }
+        Ok(())
+    }
+
+    fn apply_temperature(&mut self, spi: &mut SPI) -> Result<(), Error<SPI::Error>> {
+        match self.temperature {
+            Some(celsius) => {
+                self.cmd_with_data(spi, Command::CascadeSetting, &[0x02])?;
+                self.cmd_with_data(spi, Command::ForceTemperature, &[celsius as u8])
+            }
+            None => self.cmd_with_data(spi, Command::CascadeSetting, &[0x00]),
+        }
+    }
+
+    fn cmd(&mut self, spi: &mut SPI, command: Command) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd(spi, command)
     }
 
@@ -311,7 +436,7 @@ where
         spi: &mut SPI,
         command: Command,
         data: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.interface.cmd_with_data(spi, command, data)
     }
 }
@@ -0,0 +1,43 @@
+//! Errors returned by the EPD drivers
+
+/// Error returned by all the functions interacting with an EPD
+///
+/// - `SpiE` is the error type of the SPI device
+/// - `PinE` is the error type of the control pins (busy, dc and rst). As these pins
+///   usually have different types, their errors are reduced to their
+///   [`ErrorKind`](embedded_hal::digital::ErrorKind) by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<SpiE, PinE = embedded_hal::digital::ErrorKind> {
+    /// Communication over the SPI bus failed
+    Spi(SpiE),
+    /// Reading or setting one of the control pins failed
+    Pin(PinE),
+    /// The display was still busy after the configured timeout
+    ///
+    /// This usually means the panel is disconnected or the busy pin is wired
+    /// (or configured) with the wrong polarity.
+    BusyTimeout,
+    /// The provided buffer doesn't have the size expected by the display
+    InvalidBuffer,
+    /// The window of a partial update is empty, outside of the display or not aligned as the
+    /// controller requires
+    InvalidWindow,
+    /// The display or its driver doesn't support this operation
+    Unsupported,
+    /// The waveform doesn't fit the LUT registers of the controller
+    InvalidWaveform,
+}
+
+impl<SpiE: core::fmt::Debug, PinE: core::fmt::Debug> core::fmt::Display for Error<SpiE, PinE> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Spi(e) => write!(f, "SPI error: {:?}", e),
+            Error::Pin(e) => write!(f, "Pin error: {:?}", e),
+            Error::BusyTimeout => write!(f, "Timeout while waiting for the display to be idle"),
+            Error::InvalidBuffer => write!(f, "Buffer size doesn't match the display"),
+            Error::InvalidWindow => write!(f, "Partial window doesn't fit the display"),
+            Error::Unsupported => write!(f, "Operation not supported by the display"),
+            Error::InvalidWaveform => write!(f, "Waveform doesn't fit the controller"),
+        }
+    }
+}
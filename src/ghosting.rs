@@ -0,0 +1,571 @@
+//! Scheduling of full refreshes to clear the ghosting left by quick and partial refreshes
+//!
+//! A [`GhostingManager`] wraps a display and counts the quick refreshes of every region of it.
+//! Once a region has been refreshed more often than the [`GhostingPolicy`] allows, or too much
+//! time has passed since the last full refresh, the next refresh touching it is done with
+//! [`RefreshLut::Full`] instead.
+//!
+//! The crate has no clock, the time is advanced with [`GhostingManager::advance_time`], e.g.
+//! with the time spent in deep sleep. [`GhostingState`] only holds plain numbers, so it can be
+//! kept in RTC memory or flash and given back with [`GhostingManager::restore_state`] after a
+//! reboot.
+//!
+//! ```rust, no_run
+//! # use embedded_hal_mock::eh1::*;
+//! # fn main() -> Result<(), epd_waveshare::Error<embedded_hal::spi::ErrorKind>> {
+//! use epd_waveshare::{epd4in2::*, ghosting::*, prelude::*, rect::Rect};
+//! # let mut spi = spi::Mock::new(&[]);
+//! # let pin = || digital::Mock::new(&[]);
+//! # let mut delay = delay::NoopDelay::new();
+//! # let (busy, dc, rst) = (pin(), pin(), pin());
+//! # let buffer = [0u8; 40 / 8 * 20];
+//! let epd = Epd4in2::new(&mut spi, busy, dc, rst, &mut delay, None)?;
+//!
+//! // a full refresh after 5 quick ones per half of the display, or after an hour
+//! let policy = GhostingPolicy::new(5).with_max_elapsed_s(3600);
+//! let halves = [Rect::new(0, 0, WIDTH, HEIGHT / 2), Rect::new(0, HEIGHT / 2, WIDTH, HEIGHT / 2)];
+//! let mut epd = GhostingManager::with_regions(epd, policy, halves);
+//!
+//! // only counts for the top half
+//! epd.update_and_display_partial_frame(&mut spi, &mut delay, &buffer, 8, 16, 40, 20)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::Error;
+use crate::rect::Rect;
+use crate::traits::{QuickRefresh, RefreshLut, WaveshareDisplay};
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{InputPin, OutputPin},
+    spi::SpiDevice,
+};
+
+/// When a [`GhostingManager`] does a full refresh
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GhostingPolicy {
+    /// Quick refreshes of a region after which the next one is a full refresh
+    pub max_quick_refreshes: u16,
+    /// Seconds since the last full refresh after which the next refresh is a full one
+    pub max_elapsed_s: Option<u32>,
+    /// Look-up table used for the quick refreshes
+    pub quick_lut: RefreshLut,
+}
+
+impl GhostingPolicy {
+    /// Full refresh after `max_quick_refreshes` quick ones, without time limit
+    pub const fn new(max_quick_refreshes: u16) -> Self {
+        GhostingPolicy {
+            max_quick_refreshes,
+            max_elapsed_s: None,
+            quick_lut: RefreshLut::Quick,
+        }
+    }
+
+    /// Adds a time limit
+    pub const fn with_max_elapsed_s(mut self, max_elapsed_s: u32) -> Self {
+        self.max_elapsed_s = Some(max_elapsed_s);
+        self
+    }
+
+    /// Uses another look-up table for the quick refreshes, e.g. [`RefreshLut::PartialRefresh`]
+    pub const fn with_quick_lut(mut self, quick_lut: RefreshLut) -> Self {
+        self.quick_lut = quick_lut;
+        self
+    }
+}
+
+/// A full refresh every 5 quick refreshes, as recommended by Waveshare
+impl Default for GhostingPolicy {
+    fn default() -> Self {
+        GhostingPolicy::new(5)
+    }
+}
+
+/// Quick refreshes since the last full refresh, per region
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GhostingState<const N: usize> {
+    /// Quick refreshes of every region
+    pub quick_refreshes: [u16; N],
+    /// Seconds since the last full refresh
+    pub elapsed_s: u32,
+}
+
+impl<const N: usize> Default for GhostingState<N> {
+    fn default() -> Self {
+        GhostingState {
+            quick_refreshes: [0; N],
+            elapsed_s: 0,
+        }
+    }
+}
+
+impl<const N: usize> GhostingState<N> {
+    /// Whether a refresh of the `touched` regions must be a full one
+    fn full_refresh_due(&self, policy: &GhostingPolicy, touched: &[bool; N]) -> bool {
+        let expired = matches!(policy.max_elapsed_s, Some(max) if self.elapsed_s >= max);
+        let worn = self
+            .quick_refreshes
+            .iter()
+            .zip(touched)
+            .any(|(&count, &touched)| touched && count >= policy.max_quick_refreshes);
+        expired || worn
+    }
+
+    fn record_quick_refresh(&mut self, touched: &[bool; N]) {
+        for (count, &touched) in self.quick_refreshes.iter_mut().zip(touched) {
+            if touched {
+                *count = count.saturating_add(1);
+            }
+        }
+    }
+
+    fn record_full_refresh(&mut self) {
+        *self = GhostingState::default();
+    }
+}
+
+/// Wrapper doing a full refresh when the ghosting of quick refreshes gets too strong
+///
+/// The display is divided in `N` regions, by default a single one covering all of it.
+pub struct GhostingManager<EPD, const N: usize = 1> {
+    epd: EPD,
+    policy: GhostingPolicy,
+    regions: [Rect; N],
+    state: GhostingState<N>,
+}
+
+impl<EPD> GhostingManager<EPD, 1> {
+    /// Wraps a display with a single region
+    pub fn new(epd: EPD, policy: GhostingPolicy) -> Self {
+        GhostingManager::with_regions(epd, policy, [Rect::new(0, 0, u32::MAX, u32::MAX)])
+    }
+}
+
+impl<EPD, const N: usize> GhostingManager<EPD, N> {
+    /// Wraps a display, counting the quick refreshes of every region separately
+    ///
+    /// The regions are in the coordinates of the unrotated display and may overlap.
+    pub fn with_regions(epd: EPD, policy: GhostingPolicy, regions: [Rect; N]) -> Self {
+        GhostingManager {
+            epd,
+            policy,
+            regions,
+            state: GhostingState::default(),
+        }
+    }
+
+    /// The wrapped display
+    pub fn epd(&self) -> &EPD {
+        &self.epd
+    }
+
+    /// The wrapped display, refreshes made directly with it aren't counted
+    pub fn epd_mut(&mut self) -> &mut EPD {
+        &mut self.epd
+    }
+
+    /// Returns the wrapped display
+    pub fn into_inner(self) -> EPD {
+        self.epd
+    }
+
+    /// Current policy
+    pub fn policy(&self) -> &GhostingPolicy {
+        &self.policy
+    }
+
+    /// Changes the policy, the counts are kept
+    pub fn set_policy(&mut self, policy: GhostingPolicy) {
+        self.policy = policy;
+    }
+
+    /// Current counts, to keep them across deep sleep or reboots
+    pub fn state(&self) -> &GhostingState<N> {
+        &self.state
+    }
+
+    /// Restores counts saved with [`state`](Self::state)
+    pub fn restore_state(&mut self, state: GhostingState<N>) {
+        self.state = state;
+    }
+
+    /// Adds to the time since the last full refresh
+    pub fn advance_time(&mut self, seconds: u32) {
+        self.state.elapsed_s = self.state.elapsed_s.saturating_add(seconds);
+    }
+
+    /// Whether the next refresh of the whole display will be a full one
+    pub fn full_refresh_due(&self) -> bool {
+        self.state.full_refresh_due(&self.policy, &[true; N])
+    }
+
+    /// Regions intersecting with an area
+    fn touched(&self, area: Rect) -> [bool; N] {
+        let mut touched = [false; N];
+        for (touched, region) in touched.iter_mut().zip(&self.regions) {
+            *touched = !region.intersect(area).is_empty();
+        }
+        touched
+    }
+
+    /// Refreshes the whole display with the full look-up table and resets the counts
+    pub fn full_refresh<SPI, BUSY, DC, RST, DELAY>(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        self.epd.set_lut(spi, delay, Some(RefreshLut::Full))?;
+        self.epd.display_frame(spi, delay)?;
+        self.state.record_full_refresh();
+        Ok(())
+    }
+
+    /// Updates and displays a whole frame, with the quick look-up table unless a full refresh
+    /// is due
+    pub fn update_and_display_frame<SPI, BUSY, DC, RST, DELAY>(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        let touched = [true; N];
+        let full = self.state.full_refresh_due(&self.policy, &touched);
+        self.set_lut(spi, delay, full)?;
+        self.epd.update_and_display_frame(spi, buffer, delay)?;
+        self.record(full, &touched);
+        Ok(())
+    }
+
+    /// Updates a part of the frame and displays it, with the quick look-up table unless a full
+    /// refresh of one of the regions it touches is due
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_and_display_partial_frame<SPI, BUSY, DC, RST, DELAY>(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        let touched = self.touched(Rect::new(x, y, width, height));
+        let full = self.state.full_refresh_due(&self.policy, &touched);
+        self.set_lut(spi, delay, full)?;
+        self.epd
+            .update_partial_frame(spi, delay, buffer, x, y, width, height)?;
+        self.epd.display_frame(spi, delay)?;
+        self.record(full, &touched);
+        Ok(())
+    }
+
+    /// Displays a new frame with [`QuickRefresh`], or with a full refresh when it is due
+    ///
+    /// `old` is what the display currently shows.
+    pub fn update_and_display_new_frame<SPI, BUSY, DC, RST, DELAY>(
+        &mut self,
+        spi: &mut SPI,
+        old: &[u8],
+        new: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY> + QuickRefresh<SPI, BUSY, DC, RST, DELAY>,
+    {
+        let touched = [true; N];
+        let full = self.state.full_refresh_due(&self.policy, &touched);
+        self.set_lut(spi, delay, full)?;
+        if full {
+            self.epd.update_and_display_frame(spi, new, delay)?;
+        } else {
+            self.epd.update_old_frame(spi, old, delay)?;
+            self.epd.update_and_display_new_frame(spi, new, delay)?;
+        }
+        self.record(full, &touched);
+        Ok(())
+    }
+
+    fn set_lut<SPI, BUSY, DC, RST, DELAY>(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        full: bool,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        let lut = if full {
+            RefreshLut::Full
+        } else {
+            self.policy.quick_lut
+        };
+        self.epd.set_lut(spi, delay, Some(lut))
+    }
+
+    fn record(&mut self, full: bool, touched: &[bool; N]) {
+        if full {
+            self.state.record_full_refresh();
+        } else {
+            self.state.record_quick_refresh(touched);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::color::Color;
+    use crate::traits::{BusyPolling, Capabilities, ColorKind};
+    use embedded_hal_mock::eh1::delay::NoopDelay;
+    use embedded_hal_mock::eh1::digital::Mock as PinMock;
+    use embedded_hal_mock::eh1::spi::Mock as SpiMock;
+    use std::vec::Vec;
+
+    /// Records the look-up table of every refresh instead of driving a display
+    #[derive(Default)]
+    struct Recorder {
+        lut: RefreshLut,
+        refreshes: Vec<RefreshLut>,
+    }
+
+    impl<SPI, BUSY, DC, RST, DELAY> WaveshareDisplay<SPI, BUSY, DC, RST, DELAY> for Recorder
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+    {
+        type DisplayColor = Color;
+
+        const CAPABILITIES: Capabilities = Capabilities {
+            color: ColorKind::BlackWhite,
+            partial_update: true,
+            refresh_luts: &[RefreshLut::Full, RefreshLut::Quick],
+            quick_refresh: false,
+            busy: BusyPolling::Pin,
+        };
+
+        fn sleep(&mut self, _: &mut SPI, _: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+            Ok(())
+        }
+
+        fn wake_up(&mut self, _: &mut SPI, _: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+            Ok(())
+        }
+
+        fn switch_power(&mut self, _: &mut DELAY, _: bool) -> Result<(), Error<SPI::Error>> {
+            Err(Error::Unsupported)
+        }
+
+        fn set_background_color(&mut self, _: Color) {}
+
+        fn background_color(&self) -> &Color {
+            &Color::White
+        }
+
+        fn set_busy_timeout(&mut self, _: Option<u32>) {}
+
+        fn width(&self) -> u32 {
+            16
+        }
+
+        fn height(&self) -> u32 {
+            16
+        }
+
+        fn update_frame(
+            &mut self,
+            _: &mut SPI,
+            _: &[u8],
+            _: &mut DELAY,
+        ) -> Result<(), Error<SPI::Error>> {
+            Ok(())
+        }
+
+        fn update_partial_frame(
+            &mut self,
+            _: &mut SPI,
+            _: &mut DELAY,
+            _: &[u8],
+            _: u32,
+            _: u32,
+            _: u32,
+            _: u32,
+        ) -> Result<(), Error<SPI::Error>> {
+            Ok(())
+        }
+
+        fn display_frame(&mut self, _: &mut SPI, _: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+            self.refreshes.push(self.lut);
+            Ok(())
+        }
+
+        fn begin_display_frame(
+            &mut self,
+            _: &mut SPI,
+            _: &mut DELAY,
+        ) -> Result<(), Error<SPI::Error>> {
+            self.refreshes.push(self.lut);
+            Ok(())
+        }
+
+        fn update_and_display_frame(
+            &mut self,
+            _: &mut SPI,
+            _: &[u8],
+            _: &mut DELAY,
+        ) -> Result<(), Error<SPI::Error>> {
+            self.refreshes.push(self.lut);
+            Ok(())
+        }
+
+        fn clear_frame(&mut self, _: &mut SPI, _: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+            Ok(())
+        }
+
+        fn set_lut(
+            &mut self,
+            _: &mut SPI,
+            _: &mut DELAY,
+            refresh_rate: Option<RefreshLut>,
+        ) -> Result<(), Error<SPI::Error>> {
+            if let Some(lut) = refresh_rate {
+                self.lut = lut;
+            }
+            Ok(())
+        }
+
+        fn wait_until_idle(&mut self, _: &mut SPI, _: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+            Ok(())
+        }
+
+        fn is_busy(&mut self, _: &mut SPI) -> Result<bool, Error<SPI::Error>> {
+            Ok(false)
+        }
+    }
+
+    #[test]
+    fn full_refresh_after_max_count() {
+        let policy = GhostingPolicy::new(2);
+        let mut state = GhostingState::<2>::default();
+        let top = [true, false];
+
+        for _ in 0..2 {
+            assert!(!state.full_refresh_due(&policy, &top));
+            state.record_quick_refresh(&top);
+        }
+        assert!(state.full_refresh_due(&policy, &top));
+        // the bottom region is still fine
+        assert!(!state.full_refresh_due(&policy, &[false, true]));
+
+        state.record_full_refresh();
+        assert_eq!(state, GhostingState::default());
+    }
+
+    #[test]
+    fn full_refresh_after_max_time() {
+        let policy = GhostingPolicy::new(10).with_max_elapsed_s(60);
+        let mut state = GhostingState::<1> {
+            quick_refreshes: [1],
+            elapsed_s: 59,
+        };
+        assert!(!state.full_refresh_due(&policy, &[true]));
+        state.elapsed_s += 1;
+        assert!(state.full_refresh_due(&policy, &[true]));
+    }
+
+    #[test]
+    fn manager_switches_to_the_full_lut() {
+        let mut spi = SpiMock::new(&[]);
+        let mut delay = NoopDelay::new();
+        let halves = [Rect::new(0, 0, 16, 8), Rect::new(0, 8, 16, 8)];
+        let mut epd =
+            GhostingManager::with_regions(Recorder::default(), GhostingPolicy::new(2), halves);
+        let mut refresh = |epd: &mut GhostingManager<Recorder, 2>, y| {
+            epd.update_and_display_partial_frame::<_, PinMock, PinMock, PinMock, _>(
+                &mut spi, &mut delay, &[0; 2], 0, y, 16, 1,
+            )
+            .unwrap()
+        };
+
+        refresh(&mut epd, 0);
+        refresh(&mut epd, 0);
+        refresh(&mut epd, 8);
+        assert_eq!(epd.state().quick_refreshes, [2, 1]);
+        assert!(epd.full_refresh_due());
+
+        // the top half is worn out
+        refresh(&mut epd, 0);
+        assert_eq!(epd.state(), &GhostingState::default());
+        refresh(&mut epd, 0);
+        assert_eq!(epd.state().quick_refreshes, [1, 0]);
+
+        use RefreshLut::{Full, Quick};
+        assert_eq!(epd.epd().refreshes, [Quick, Quick, Quick, Full, Quick]);
+        spi.done();
+    }
+
+    #[test]
+    fn manager_full_refresh_resets_the_counts() {
+        let mut spi = SpiMock::new(&[]);
+        let mut delay = NoopDelay::new();
+        let policy = GhostingPolicy::new(5).with_max_elapsed_s(60);
+        let mut epd = GhostingManager::new(Recorder::default(), policy);
+
+        epd.update_and_display_frame::<_, PinMock, PinMock, PinMock, _>(&mut spi, &[], &mut delay)
+            .unwrap();
+        assert_eq!(epd.epd().lut, RefreshLut::Quick);
+        epd.full_refresh::<_, PinMock, PinMock, PinMock, _>(&mut spi, &mut delay)
+            .unwrap();
+        assert_eq!(epd.epd().lut, RefreshLut::Full);
+        assert_eq!(epd.state(), &GhostingState::default());
+
+        // too long since the last full refresh
+        epd.advance_time(60);
+        epd.update_and_display_frame::<_, PinMock, PinMock, PinMock, _>(&mut spi, &[], &mut delay)
+            .unwrap();
+        assert_eq!(epd.state().elapsed_s, 0);
+
+        use RefreshLut::{Full, Quick};
+        assert_eq!(epd.epd().refreshes, [Quick, Full, Full]);
+        spi.done();
+    }
+}
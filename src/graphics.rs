@@ -1,9 +1,16 @@
 //! Graphics Support for EPDs
 
-use crate::color::{Color, ColorType, TriColor};
+use crate::color::{ColorType, TriColor};
+use crate::rect::Rect;
 use core::marker::PhantomData;
 use embedded_graphics_core::prelude::*;
 
+pub mod band;
+pub mod dirty;
+pub mod dither;
+
+use dirty::{DirtyTracker, DirtyTracking, DirtyWindows};
+
 /// Display rotation, only 90° increments supported
 #[derive(Clone, Copy, Default)]
 pub enum DisplayRotation {
@@ -24,19 +31,25 @@ const fn line_bytes(width: u32, bits_per_pixel: usize) -> usize {
     (width as usize * bits_per_pixel + 7) / 8
 }
 
+/// Widens a window to whole bytes of a buffer, returns the aligned x and width
+const fn align_window(x: u32, width: u32, bits_per_pixel: usize) -> (u32, u32) {
+    let pixels_per_byte = (8 / bits_per_pixel) as u32;
+    let aligned_x = x - x % pixels_per_byte;
+    let x_end = x + width - 1;
+    let aligned_x_end = x_end - x_end % pixels_per_byte + pixels_per_byte - 1;
+    (aligned_x, aligned_x_end - aligned_x + 1)
+}
+
 /// count the number of bytes needed for a partial window buffer
 const fn partial_frame_buffer_size(
     x: u32,
     width: u32,
     height: u32,
     bits_per_pixel: usize,
+    buffer_count: usize,
 ) -> usize {
-    let aligned_x = x & !0b111;
-    let x_end = x + width - 1;
-    let aligned_x_end = x_end | 0b111;
-    let aligned_width = aligned_x_end - aligned_x + 1;
-    let buffer_size = height as usize * line_bytes(aligned_width, bits_per_pixel);
-    buffer_size
+    let (_, aligned_width) = align_window(x, width, bits_per_pixel);
+    buffer_count * height as usize * line_bytes(aligned_width, bits_per_pixel)
 }
 
 /// Display buffer used for drawing with embedded graphics
@@ -45,10 +58,10 @@ const fn partial_frame_buffer_size(
 /// - WIDTH: width in pixel when display is not rotated
 /// - HEIGHT: height in pixel when display is not rotated
 /// - BWRBIT: mandatory value of the B/W when chromatic bit is set, can be any value for non
-///           tricolor epd
+///   tricolor epd
 /// - COLOR: color type used by the target display
 /// - BYTECOUNT: This is redundant with previous data and should be removed when const generic
-///              expressions are stabilized
+///   expressions are stabilized
 ///
 /// More on BWRBIT:
 ///
@@ -70,6 +83,7 @@ pub struct Display<
 > {
     buffer: [u8; BYTECOUNT],
     rotation: DisplayRotation,
+    dirty: DirtyTracker,
     _color: PhantomData<COLOR>,
 }
 
@@ -95,6 +109,7 @@ impl<
             // default color must be 0 for every bit in a pixel to make this work everywere
             buffer: [0u8; BYTECOUNT],
             rotation: DisplayRotation::default(),
+            dirty: DirtyTracker::new(),
             _color: PhantomData,
         }
     }
@@ -168,14 +183,46 @@ impl<
 
     /// Set a specific pixel color on this display
     pub fn set_pixel(&mut self, pixel: Pixel<COLOR>) {
-        set_pixel(
+        if let Some((x, y)) = set_pixel(
             &mut self.buffer,
             WIDTH,
             HEIGHT,
             self.rotation,
             BWRBIT,
             pixel,
-        );
+        ) {
+            self.dirty.mark(x, y);
+        }
+    }
+
+    /// Starts recording the changed parts of the buffer, or stops with `DirtyTracking::Off`
+    ///
+    /// What was recorded so far is forgotten.
+    pub fn set_dirty_tracking(&mut self, tracking: DirtyTracking) {
+        self.dirty.set_mode(tracking);
+    }
+
+    /// Get current dirty tracking mode
+    pub fn dirty_tracking(&self) -> DirtyTracking {
+        self.dirty.mode()
+    }
+
+    /// Returns the byte-aligned windows changed since the last call, see [`dirty`]
+    pub fn take_dirty(&mut self) -> DirtyWindows {
+        self.dirty.take(WIDTH)
+    }
+
+    /// Copies a window returned by [`take_dirty`](Self::take_dirty) into `buffer`, ready for
+    /// `update_partial_frame`
+    ///
+    /// Fails with [`VarDisplayError::InvalidWindow`] when the window isn't byte-aligned or
+    /// doesn't lie within the display, like the windows `update_partial_frame` refuses.
+    pub fn update_parameters<'b>(
+        &self,
+        window: Rect,
+        buffer: &'b mut [u8],
+    ) -> Result<PartialUpdateParameters<'b>, VarDisplayError> {
+        dirty::copy_window::<COLOR>(&self.buffer, WIDTH, window, buffer)
     }
 
     /// count the number of bytes needed for a partial window buffer
@@ -184,12 +231,16 @@ impl<
             x,
             width,
             height,
-            COLOR::BITS_PER_PIXEL_PER_BUFFER * COLOR::BUFFER_COUNT,
+            COLOR::BITS_PER_PIXEL_PER_BUFFER,
+            COLOR::BUFFER_COUNT,
         )
     }
 
     /// Creates a virtual partial frame
     /// Handles byte-alignment for you and keeps the full display buffer in sync
+    ///
+    /// Fails when the window is empty or out of bounds, or when `buffer` is smaller than
+    /// [`partial_frame_buffer_size`](Self::partial_frame_buffer_size), see [`PartialFrame::new`].
     pub fn get_partial_frame<'a, 'b>(
         &'a mut self,
         buffer: &'b mut [u8],
@@ -197,7 +248,7 @@ impl<
         y: u32,
         width: u32,
         height: u32,
-    ) -> PartialFrame<'a, 'b, COLOR> {
+    ) -> Result<PartialFrame<'a, 'b, COLOR>, VarDisplayError> {
         PartialFrame::new(
             x,
             y,
@@ -207,7 +258,6 @@ impl<
             &mut self.buffer,
             WIDTH,
             HEIGHT,
-            BYTECOUNT,
             BWRBIT,
         )
     }
@@ -228,6 +278,42 @@ impl<const WIDTH: u32, const HEIGHT: u32, const BWRBIT: bool, const BYTECOUNT: u
     }
 }
 
+/// Computes the byte-aligned windows in which two frames differ, in the coordinates of the
+/// unrotated display
+///
+/// Windows at most `merge_distance` pixels apart are merged, since sending a few unchanged bytes
+/// is usually cheaper than starting another partial update. When there are more than
+/// [`dirty::MAX_DIRTY_RECTS`] windows, the closest ones are merged as well.
+///
+/// The windows can be copied out with [`Display::update_parameters`] and sent with
+/// `update_partial_frame` or `QuickRefresh::update_partial_new_frame`:
+///
+/// ```rust
+/// # use epd_waveshare::{color::Color, graphics::{diff, Display}};
+/// # use embedded_graphics::{prelude::*, primitives::{PrimitiveStyle, Rectangle}};
+/// let old = Display::<64, 32, false, { 64 * 32 / 8 }, Color>::default();
+/// let mut new = Display::<64, 32, false, { 64 * 32 / 8 }, Color>::default();
+/// let _ = Rectangle::new(Point::new(10, 4), Size::new(4, 3))
+///     .into_styled(PrimitiveStyle::with_fill(Color::White))
+///     .draw(&mut new);
+///
+/// let windows = diff(&old, &new, 8);
+/// assert_eq!(windows.as_slice(), &[epd_waveshare::rect::Rect::new(8, 4, 8, 3)]);
+/// ```
+pub fn diff<
+    const WIDTH: u32,
+    const HEIGHT: u32,
+    const BWRBIT: bool,
+    const BYTECOUNT: usize,
+    COLOR: ColorType + PixelColor,
+>(
+    old: &Display<WIDTH, HEIGHT, BWRBIT, BYTECOUNT, COLOR>,
+    new: &Display<WIDTH, HEIGHT, BWRBIT, BYTECOUNT, COLOR>,
+    merge_distance: u32,
+) -> DirtyWindows {
+    dirty::diff::<COLOR>(&old.buffer, &new.buffer, WIDTH, merge_distance)
+}
+
 /// Same as `Display`, except that its characteristics are defined at runtime.
 /// See display for documentation as everything is the same except that default
 /// is replaced by a `new` method.
@@ -237,6 +323,7 @@ pub struct VarDisplay<'a, COLOR: ColorType + PixelColor> {
     bwrbit: bool,
     buffer: &'a mut [u8],
     rotation: DisplayRotation,
+    dirty: DirtyTracker,
     _color: PhantomData<COLOR>,
 }
 
@@ -275,6 +362,8 @@ impl<COLOR: ColorType + PixelColor> OriginDimensions for VarDisplay<'_, COLOR> {
 pub enum VarDisplayError {
     /// The provided buffer was too small
     BufferTooSmall,
+    /// The partial window is empty or doesn't lie within the display
+    InvalidWindow,
 }
 
 impl<'a, COLOR: ColorType + PixelColor> VarDisplay<'a, COLOR> {
@@ -294,6 +383,7 @@ impl<'a, COLOR: ColorType + PixelColor> VarDisplay<'a, COLOR> {
             bwrbit,
             buffer,
             rotation: DisplayRotation::default(),
+            dirty: DirtyTracker::new(),
             _color: PhantomData,
         };
         // enforce some constraints dynamically
@@ -333,14 +423,51 @@ impl<'a, COLOR: ColorType + PixelColor> VarDisplay<'a, COLOR> {
     /// Set a specific pixel color on this display
     pub fn set_pixel(&mut self, pixel: Pixel<COLOR>) {
         let size = self.buffer_size();
-        set_pixel(
+        if let Some((x, y)) = set_pixel(
             &mut self.buffer[..size],
             self.width,
             self.height,
             self.rotation,
             self.bwrbit,
             pixel,
-        );
+        ) {
+            self.dirty.mark(x, y);
+        }
+    }
+
+    /// Starts recording the changed parts of the buffer, or stops with `DirtyTracking::Off`
+    ///
+    /// What was recorded so far is forgotten.
+    pub fn set_dirty_tracking(&mut self, tracking: DirtyTracking) {
+        self.dirty.set_mode(tracking);
+    }
+
+    /// Get current dirty tracking mode
+    pub fn dirty_tracking(&self) -> DirtyTracking {
+        self.dirty.mode()
+    }
+
+    /// Returns the byte-aligned windows changed since the last call, see [`dirty`]
+    pub fn take_dirty(&mut self) -> DirtyWindows {
+        self.dirty.take(self.width)
+    }
+
+    /// Copies a window returned by [`take_dirty`](Self::take_dirty) into `buffer`, ready for
+    /// `update_partial_frame`
+    ///
+    /// Fails with [`VarDisplayError::InvalidWindow`] when the window isn't byte-aligned or
+    /// doesn't lie within the display, like the windows `update_partial_frame` refuses.
+    pub fn update_parameters<'b>(
+        &self,
+        window: Rect,
+        buffer: &'b mut [u8],
+    ) -> Result<PartialUpdateParameters<'b>, VarDisplayError> {
+        dirty::copy_window::<COLOR>(
+            &self.buffer[..self.buffer_size()],
+            self.width,
+            window,
+            buffer,
+        )
     }
 
     /// count the number of bytes needed for a partial window buffer
@@ -349,12 +476,16 @@ impl<'a, COLOR: ColorType + PixelColor> VarDisplay<'a, COLOR> {
             x,
             width,
             height,
-            COLOR::BITS_PER_PIXEL_PER_BUFFER * COLOR::BUFFER_COUNT,
+            COLOR::BITS_PER_PIXEL_PER_BUFFER,
+            COLOR::BUFFER_COUNT,
         )
     }
 
     /// Creates a virtual partial frame
     /// Handles byte-alignment for you and keeps the full display buffer in sync
+    ///
+    /// Fails when the window is empty or out of bounds, or when `buffer` is smaller than
+    /// [`partial_frame_buffer_size`](Self::partial_frame_buffer_size), see [`PartialFrame::new`].
     pub fn get_partial_frame<'b, 'c>(
         &'b mut self,
         buffer: &'c mut [u8],
@@ -362,18 +493,16 @@ impl<'a, COLOR: ColorType + PixelColor> VarDisplay<'a, COLOR> {
         y: u32,
         width: u32,
         height: u32,
-    ) -> PartialFrame<'b, 'c, COLOR> {
-        let buffer_size = self.buffer_size();
+    ) -> Result<PartialFrame<'b, 'c, COLOR>, VarDisplayError> {
         PartialFrame::new(
             x,
             y,
             width,
             height,
             buffer,
-            &mut self.buffer,
+            self.buffer,
             self.width,
             self.height,
-            buffer_size,
             self.bwrbit,
         )
     }
@@ -393,7 +522,8 @@ impl VarDisplay<'_, TriColor> {
 }
 
 /// Same as `Display`, except that its characteristics are defined at runtime, and it's buffer is
-/// byte-aligned relative to the full display.
+/// byte-aligned relative to the full display: 8 pixels for 1 bit per pixel, 2 pixels for
+/// `OctColor`.
 /// See display for documentation as everything is the same except that default
 /// is replaced by a `new` method.
 pub struct PartialFrame<'a, 'b, COLOR: ColorType + PixelColor> {
@@ -407,7 +537,6 @@ pub struct PartialFrame<'a, 'b, COLOR: ColorType + PixelColor> {
     buffer: &'b mut [u8],
     full_display_buffer: &'a mut [u8],
     full_display_width: u32,
-    full_display_size: usize,
     rotation: DisplayRotation,
     _color: PhantomData<COLOR>,
 }
@@ -460,11 +589,15 @@ impl<COLOR: ColorType + PixelColor> OriginDimensions for PartialFrame<'_, '_, CO
 impl<'a, 'b, COLOR: ColorType + PixelColor> PartialFrame<'a, 'b, COLOR> {
     /// Creates a byte-aligned buffer for you, based on X-coordinate and height.
     ///
-    /// Parameters are documented in `Display` as they are the same as the const generics there.
+    /// `buffer` must hold at least [`Display::partial_frame_buffer_size`] bytes and
+    /// `full_display_buffer` the whole display, whose other parameters are documented in
+    /// `Display` as they are the same as the const generics there.
     /// bwrbit should be false for non tricolor displays
     ///
-    /// Panics when width or height is equal to 0.
-    fn new(
+    /// Fails with [`VarDisplayError::InvalidWindow`] when width or height is equal to 0 or when
+    /// the window doesn't lie within the display.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
         x: u32,
         y: u32,
         width: u32,
@@ -473,25 +606,32 @@ impl<'a, 'b, COLOR: ColorType + PixelColor> PartialFrame<'a, 'b, COLOR> {
         full_display_buffer: &'a mut [u8],
         full_display_width: u32,
         full_display_height: u32,
-        full_display_size: usize,
         bwrbit: bool,
-    ) -> Self {
-        if width == 0 {
-            panic!("The width should be greater than 0");
-        }
-        if height == 0 {
-            panic!("The height should be greater than 0");
-        }
-        if x + width > full_display_width || y + height > full_display_height {
-            panic!("Partial window out of bounds of full display. Partial windows should fully lie within the bounds of the display.")
+    ) -> Result<Self, VarDisplayError> {
+        if width == 0
+            || height == 0
+            || x.checked_add(width)
+                .map_or(true, |x_end| x_end > full_display_width)
+            || y.checked_add(height)
+                .map_or(true, |y_end| y_end > full_display_height)
+        {
+            return Err(VarDisplayError::InvalidWindow);
         }
 
-        let aligned_x = x & !0b111;
-        let x_end = x + width - 1;
-        let aligned_x_end = x_end | 0b111;
-        let aligned_width = aligned_x_end - aligned_x + 1;
-
-        Self {
+        let bits = COLOR::BITS_PER_PIXEL_PER_BUFFER;
+        let (aligned_x, aligned_width) = align_window(x, width, bits);
+        let size = partial_frame_buffer_size(x, width, height, bits, COLOR::BUFFER_COUNT);
+        let full_display_size = COLOR::BUFFER_COUNT
+            * full_display_height as usize
+            * line_bytes(full_display_width, bits);
+        let buffer = buffer
+            .get_mut(..size)
+            .ok_or(VarDisplayError::BufferTooSmall)?;
+        let full_display_buffer = full_display_buffer
+            .get_mut(..full_display_size)
+            .ok_or(VarDisplayError::BufferTooSmall)?;
+
+        Ok(Self {
             original_x: x,
             aligned_x,
             y,
@@ -502,10 +642,9 @@ impl<'a, 'b, COLOR: ColorType + PixelColor> PartialFrame<'a, 'b, COLOR> {
             buffer,
             full_display_buffer,
             full_display_width,
-            full_display_size,
             rotation: DisplayRotation::default(),
             _color: PhantomData,
-        }
+        })
     }
 
     /// get the number of used bytes in the buffer
@@ -575,12 +714,15 @@ impl<'a, 'b, COLOR: ColorType + PixelColor> PartialFrame<'a, 'b, COLOR> {
             &mut self.full_display_buffer[full_display_start..full_display_end];
         let partial_buffer_slice = &mut self.buffer[partial_buffer_start..partial_buffer_end];
 
-        let partial_row_bytes = (self.aligned_width as usize + 7) / 8;
-        let full_display_row_bytes = (self.full_display_width as usize + 7) / 8;
-        let partial_x_byte_offset = self.aligned_x as usize / 8;
+        let bits = COLOR::BITS_PER_PIXEL_PER_BUFFER;
+        let partial_row_bytes = line_bytes(self.aligned_width, bits);
+        let full_display_row_bytes = line_bytes(self.full_display_width, bits);
+        let partial_x_byte_offset = self.aligned_x as usize * bits / 8;
 
-        let left_padding_bits = self.original_x - self.aligned_x;
-        let right_padding_bits = self.aligned_width - left_padding_bits - self.original_width;
+        let left_padding_pixels = self.original_x - self.aligned_x;
+        let right_padding_pixels = self.aligned_width - left_padding_pixels - self.original_width;
+        let left_padding_bits = left_padding_pixels * bits as u32;
+        let right_padding_bits = right_padding_pixels * bits as u32;
 
         for row_idx in 0..self.height as usize {
             let partial_row_start = row_idx * partial_row_bytes;
@@ -611,23 +753,31 @@ impl<'a, 'b, COLOR: ColorType + PixelColor> PartialFrame<'a, 'b, COLOR> {
                 );
         }
     }
-}
 
-/// Some Monochrome specifics
-impl PartialFrame<'_, '_, Color> {
     /// To be used as parameters for the [`crate::traits::WaveshareDisplay::update_partial_frame`] function.
     ///
     /// Copies padding pixels from `from_buffer` to fill the byte-alignment offset on both left and right sides.
     /// Also updates `from_buffer` with the contents of the partial frame to keep it consistent.
+    ///
+    /// Split buffers, like the ones of `TriColor`, are handled plane by plane.
     pub fn get_update_parameters(&mut self) -> PartialUpdateParameters<'_> {
-        self.copy_and_sync_buffer(0, self.full_display_size, 0, self.buffer.len());
+        let plane = self.buffer_size() / COLOR::BUFFER_COUNT;
+        let full_display_plane = self.full_display_buffer.len() / COLOR::BUFFER_COUNT;
+        for i in 0..COLOR::BUFFER_COUNT {
+            self.copy_and_sync_buffer(
+                i * full_display_plane,
+                (i + 1) * full_display_plane,
+                i * plane,
+                (i + 1) * plane,
+            );
+        }
 
         PartialUpdateParameters {
             x: self.aligned_x,
             y: self.y,
             width: self.aligned_width,
             height: self.height,
-            buffer: &self.buffer,
+            buffer: self.buffer,
         }
     }
 }
@@ -643,59 +793,31 @@ impl PartialFrame<'_, '_, TriColor> {
     pub fn chromatic_buffer(&self) -> &[u8] {
         &self.buffer[self.buffer_size() / 2..self.buffer_size()]
     }
-
-    /// To be used as parameters for the [`crate::traits::WaveshareDisplay::update_partial_frame`] function.
-    ///
-    /// Copies padding pixels from `from_buffer` to fill the byte-alignment offset on both left and right sides.
-    /// Also updates `from_buffer` with the contents of the partial frame to keep it consistent.
-    pub fn get_update_parameters(&mut self) -> PartialUpdateParameters<'_> {
-        let half_size = self.buffer_size() / 2;
-        let full_display_half_size = self.full_display_size / 2;
-
-        // Process BW buffer
-        self.copy_and_sync_buffer(0, full_display_half_size, 0, half_size);
-
-        // Process chromatic buffer
-        self.copy_and_sync_buffer(
-            full_display_half_size,
-            self.full_display_size,
-            half_size,
-            self.buffer_size(),
-        );
-
-        PartialUpdateParameters {
-            x: self.aligned_x,
-            y: self.y,
-            width: self.aligned_width,
-            height: self.height,
-            buffer: &self.buffer,
-        }
-    }
 }
 
-/// Copy the leftmost `offset_pixels` bits from src to dst
-fn copy_left_padding_bits(dst: &mut u8, src: u8, offset_pixels: u32) {
-    if offset_pixels == 0 {
+/// Copy the leftmost `offset_bits` bits from src to dst
+fn copy_left_padding_bits(dst: &mut u8, src: u8, offset_bits: u32) {
+    if offset_bits == 0 {
         return;
     }
 
-    // Create mask for the padding bits (leftmost offset_pixels bits)
-    // For example, if offset_pixels = 3: mask = 0b11100000
-    let padding_mask = 0xFFu8 << (8 - offset_pixels);
+    // Create mask for the padding bits (leftmost offset_bits bits)
+    // For example, if offset_bits = 3: mask = 0b11100000
+    let padding_mask = 0xFFu8 << (8 - offset_bits);
 
     // Clear padding bits in dst and copy from src
     *dst = (*dst & !padding_mask) | (src & padding_mask);
 }
 
-/// Copy the rightmost `offset_pixels` bits from src to dst
-fn copy_right_padding_bits(dst: &mut u8, src: u8, offset_pixels: u32) {
-    if offset_pixels == 0 {
+/// Copy the rightmost `offset_bits` bits from src to dst
+fn copy_right_padding_bits(dst: &mut u8, src: u8, offset_bits: u32) {
+    if offset_bits == 0 {
         return;
     }
 
-    // Create mask for the padding bits (rightmost offset_pixels bits)
-    // For example, if offset_pixels = 3: mask = 0b00000111
-    let padding_mask = (1u8 << offset_pixels) - 1;
+    // Create mask for the padding bits (rightmost offset_bits bits)
+    // For example, if offset_bits = 3: mask = 0b00000111
+    let padding_mask = (1u8 << offset_bits) - 1;
 
     // Clear padding bits in dst and copy from src
     *dst = (*dst & !padding_mask) | (src & padding_mask);
@@ -712,27 +834,47 @@ fn set_pixel<COLOR: ColorType + PixelColor>(
     rotation: DisplayRotation,
     bwrbit: bool,
     pixel: Pixel<COLOR>,
-) {
+) -> Option<(u32, u32)> {
     let Pixel(point, color) = pixel;
 
     // final coordinates
-    let (x, y) = match rotation {
+    let (x, y) = rotate_point(point, width, height, rotation);
+
+    // Out of range check
+    if (x < 0) || (x >= width as i32) || (y < 0) || (y >= height as i32) {
+        // don't do anything in case of out of range
+        return None;
+    }
+
+    write_pixel(buffer, width, bwrbit, x as u32, y as u32, color);
+    Some((x as u32, y as u32))
+}
+
+/// Maps a point of the rotated display to the coordinates of the unrotated buffer
+fn rotate_point(point: Point, width: u32, height: u32, rotation: DisplayRotation) -> (i32, i32) {
+    match rotation {
         // as i32 = never use more than 2 billion pixel per line or per column
         DisplayRotation::Rotate0 => (point.x, point.y),
         DisplayRotation::Rotate90 => (width as i32 - 1 - point.y, point.x),
         DisplayRotation::Rotate180 => (width as i32 - 1 - point.x, height as i32 - 1 - point.y),
         DisplayRotation::Rotate270 => (point.y, height as i32 - 1 - point.x),
-    };
-
-    // Out of range check
-    if (x < 0) || (x >= width as i32) || (y < 0) || (y >= height as i32) {
-        // don't do anything in case of out of range
-        return;
     }
+}
 
+/// Writes a pixel at coordinates already checked to be inside of the buffer
+///
+/// Split buffers are expected to be exactly two planes of the same size.
+fn write_pixel<COLOR: ColorType>(
+    buffer: &mut [u8],
+    width: u32,
+    bwrbit: bool,
+    x: u32,
+    y: u32,
+    color: COLOR,
+) {
     let index = x as usize * COLOR::BITS_PER_PIXEL_PER_BUFFER / 8
         + y as usize * line_bytes(width, COLOR::BITS_PER_PIXEL_PER_BUFFER);
-    let (mask, bits) = color.bitmask(bwrbit, x as u32);
+    let (mask, bits) = color.bitmask(bwrbit, x);
 
     if COLOR::BUFFER_COUNT == 2 {
         // split buffer is for tricolor displays that use 2 buffer for 2 bits per pixel
@@ -761,6 +903,19 @@ mod tests {
         assert_eq!(display.buffer().len(), 5000);
     }
 
+    #[test]
+    fn graphics_gray2_planes() {
+        let mut display = Display::<16, 2, false, { 2 * 16 * 2 / 8 }, Gray2>::default();
+        display.set_pixel(Pixel(Point::new(0, 0), Gray2::White));
+        display.set_pixel(Pixel(Point::new(1, 0), Gray2::LightGray));
+        display.set_pixel(Pixel(Point::new(2, 0), Gray2::DarkGray));
+        display.set_pixel(Pixel(Point::new(8, 1), Gray2::White));
+
+        let (high, low) = display.buffer().split_at(4);
+        assert_eq!(high, &[0b1100_0000, 0, 0, 0b1000_0000]);
+        assert_eq!(low, &[0b1010_0000, 0, 0, 0b1000_0000]);
+    }
+
     // test default background color on all bytes
     #[test]
     fn graphics_default() {
@@ -878,4 +1033,49 @@ mod tests {
         assert_eq!(bw_buffer, [128, 0]);
         assert_eq!(chromatic_buffer, [64, 0]);
     }
+    #[test]
+    fn partial_frame_octcolor_padding() {
+        let mut display = Display::<6, 2, false, { 6 * 2 / 2 }, OctColor>::default();
+        display.set_pixel(Pixel(Point::new(0, 0), OctColor::Green));
+        display.set_pixel(Pixel(Point::new(3, 0), OctColor::Red));
+
+        // pixels 1 and 2 are aligned to the 2 pixels of a byte
+        let size =
+            Display::<6, 2, false, { 6 * 2 / 2 }, OctColor>::partial_frame_buffer_size(1, 2, 1);
+        assert_eq!(size, 2);
+        let mut buffer = [0u8; 2];
+        let mut partial = display.get_partial_frame(&mut buffer, 1, 0, 2, 1).unwrap();
+        partial.set_pixel(Pixel(Point::new(0, 0), OctColor::Blue));
+        partial.set_pixel(Pixel(Point::new(1, 0), OctColor::Yellow));
+
+        let params = partial.get_update_parameters();
+        assert_eq!((params.x, params.width), (0, 4));
+        let expected = [
+            OctColor::Green.get_nibble() << 4 | OctColor::Blue.get_nibble(),
+            OctColor::Yellow.get_nibble() << 4 | OctColor::Red.get_nibble(),
+        ];
+        assert_eq!(params.buffer, expected);
+        assert_eq!(display.buffer()[..2], expected);
+    }
+
+    #[test]
+    fn partial_frame_errors() {
+        let mut full = [0u8; 16 * 4 / 8];
+        let mut buffer = [0u8; 2];
+        assert!(matches!(
+            PartialFrame::<Color>::new(0, 0, 0, 1, &mut buffer, &mut full, 16, 4, false),
+            Err(VarDisplayError::InvalidWindow)
+        ));
+        assert!(matches!(
+            PartialFrame::<Color>::new(10, 0, 8, 1, &mut buffer, &mut full, 16, 4, false),
+            Err(VarDisplayError::InvalidWindow)
+        ));
+        assert!(matches!(
+            PartialFrame::<Color>::new(4, 0, 8, 2, &mut buffer, &mut full, 16, 4, false),
+            Err(VarDisplayError::BufferTooSmall)
+        ));
+        assert!(
+            PartialFrame::<Color>::new(4, 0, 8, 1, &mut buffer, &mut full, 16, 4, false).is_ok()
+        );
+    }
 }
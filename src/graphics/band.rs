@@ -0,0 +1,369 @@
+//! Rendering in horizontal bands, for displays whose frame buffer doesn't fit in RAM
+//!
+//! A [`BandRenderer`] only needs a buffer for a few lines of the display. The drawing closure is
+//! run once per band against a [`Band`], which has the size of the whole display but only keeps
+//! the pixels inside its lines. Every band is then sent to the controller with
+//! [`update_partial_frame`](crate::prelude::WaveshareDisplay::update_partial_frame), and the
+//! display is refreshed once at the end:
+//!
+//! ```rust, no_run
+//! # use embedded_hal_mock::eh1::*;
+//! # fn main() -> Result<(), epd_waveshare::Error<embedded_hal::spi::ErrorKind>> {
+//! use embedded_graphics::{prelude::*, primitives::{Circle, PrimitiveStyle}};
+//! use epd_waveshare::{color::Color, epd7in5_v2::*, graphics::band::BandRenderer, prelude::*};
+//! # let mut spi = spi::Mock::new(&[]);
+//! # let pin = || digital::Mock::new(&[]);
+//! # let mut delay = delay::NoopDelay::new();
+//! # let (busy, dc, rst) = (pin(), pin(), pin());
+//! let mut epd = Epd7in5::new(&mut spi, busy, dc, rst, &mut delay, None)?;
+//!
+//! // 4 KB instead of the 48 KB of a full `Display7in5`
+//! let mut buffer = [0u8; WIDTH as usize / 8 * 40];
+//! let mut renderer = BandRenderer::<Color>::new(&mut buffer, WIDTH, HEIGHT, false).unwrap();
+//! renderer.update_frame(&mut epd, &mut spi, &mut delay, |band| {
+//!     let _ = band.clear(Color::White);
+//!     let _ = Circle::new(Point::new(300, 140), 200)
+//!         .into_styled(PrimitiveStyle::with_fill(Color::Black))
+//!         .draw(band);
+//! })?;
+//! epd.display_frame(&mut spi, &mut delay)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Everything is drawn once per band, so drawing gets slower the more bands there are. Drawing
+//! code can skip what's outside of [`Band::area`].
+
+use super::{
+    line_bytes, rotate_point, write_pixel, DisplayRotation, PartialUpdateParameters,
+    VarDisplayError,
+};
+use crate::color::ColorType;
+use crate::error::Error;
+use crate::traits::WaveshareDisplay;
+use core::marker::PhantomData;
+use embedded_graphics_core::prelude::*;
+use embedded_graphics_core::primitives::Rectangle;
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{InputPin, OutputPin},
+    spi::SpiDevice,
+};
+
+/// Renders a display in horizontal bands of the unrotated display
+///
+/// The bands are as high as the buffer allows, the last one may be lower.
+pub struct BandRenderer<'a, COLOR: ColorType + PixelColor> {
+    buffer: &'a mut [u8],
+    width: u32,
+    height: u32,
+    band_height: u32,
+    bwrbit: bool,
+    rotation: DisplayRotation,
+    _color: PhantomData<COLOR>,
+}
+
+impl<'a, COLOR: ColorType + PixelColor> BandRenderer<'a, COLOR> {
+    /// Creates a renderer for a display of `width` x `height` pixels (unrotated)
+    ///
+    /// The buffer must hold at least one line, see [`band_buffer_size`](Self::band_buffer_size).
+    /// bwrbit has the same meaning as in [`Display`](super::Display) and should be false for
+    /// non tricolor displays.
+    pub fn new(
+        buffer: &'a mut [u8],
+        width: u32,
+        height: u32,
+        bwrbit: bool,
+    ) -> Result<Self, VarDisplayError> {
+        let band_height = buffer.len() / Self::band_buffer_size(width, 1);
+        if band_height == 0 {
+            return Err(VarDisplayError::BufferTooSmall);
+        }
+        Ok(BandRenderer {
+            buffer,
+            width,
+            height,
+            band_height: (band_height as u32).min(height),
+            bwrbit,
+            rotation: DisplayRotation::default(),
+            _color: PhantomData,
+        })
+    }
+
+    /// Number of bytes needed for a band of `lines` lines of a display `width` pixels wide
+    pub fn band_buffer_size(width: u32, lines: u32) -> usize {
+        COLOR::BUFFER_COUNT * line_bytes(width, COLOR::BITS_PER_PIXEL_PER_BUFFER) * lines as usize
+    }
+
+    /// Number of lines of every band but the last
+    pub fn band_height(&self) -> u32 {
+        self.band_height
+    }
+
+    /// Set the rotation used to draw on the bands
+    pub fn set_rotation(&mut self, rotation: DisplayRotation) {
+        self.rotation = rotation;
+    }
+
+    /// Get current rotation
+    pub fn rotation(&self) -> DisplayRotation {
+        self.rotation
+    }
+
+    /// Runs `draw` for every band from top to bottom and passes the result to `send`
+    ///
+    /// Every band starts with all bits cleared, like [`Display::default`](super::Display).
+    /// The buffer of split colors like [`TriColor`](crate::color::TriColor) holds both planes
+    /// of the band, one after the other.
+    pub fn render<E, F, S>(&mut self, mut draw: F, mut send: S) -> Result<(), E>
+    where
+        F: FnMut(&mut Band<'_, COLOR>),
+        S: FnMut(PartialUpdateParameters<'_>) -> Result<(), E>,
+    {
+        let mut y = 0;
+        while y < self.height {
+            let lines = self.band_height.min(self.height - y);
+            let size = Self::band_buffer_size(self.width, lines);
+            let buffer = &mut self.buffer[..size];
+            buffer.fill(0);
+
+            let mut band = Band {
+                buffer,
+                width: self.width,
+                height: self.height,
+                y,
+                lines,
+                bwrbit: self.bwrbit,
+                rotation: self.rotation,
+                _color: PhantomData,
+            };
+            draw(&mut band);
+
+            send(PartialUpdateParameters {
+                x: 0,
+                y,
+                width: self.width,
+                height: lines,
+                buffer: band.buffer,
+            })?;
+            y += lines;
+        }
+        Ok(())
+    }
+
+    /// Renders the frame into the RAM of the display, one partial update per band
+    ///
+    /// The display isn't refreshed, call
+    /// [`display_frame`](crate::prelude::WaveshareDisplay::display_frame) afterwards. The
+    /// driver must write the band buffer as produced by its color type, for the tricolor
+    /// displays this is currently only the case for
+    /// [`Epd12in48b`](crate::epd12in48b_v2::Epd12in48b).
+    pub fn update_frame<SPI, BUSY, DC, RST, DELAY, EPD, F>(
+        &mut self,
+        epd: &mut EPD,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        draw: F,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY, DisplayColor = COLOR>,
+        F: FnMut(&mut Band<'_, COLOR>),
+    {
+        self.render(draw, |band| {
+            epd.update_partial_frame(
+                spi,
+                delay,
+                band.buffer,
+                band.x,
+                band.y,
+                band.width,
+                band.height,
+            )
+        })
+    }
+}
+
+/// Horizontal band of a display, to draw on with embedded-graphics
+///
+/// Its size is the size of the whole (rotated) display, pixels outside of the band are dropped.
+pub struct Band<'a, COLOR: ColorType + PixelColor> {
+    buffer: &'a mut [u8],
+    width: u32,
+    height: u32,
+    y: u32,
+    lines: u32,
+    bwrbit: bool,
+    rotation: DisplayRotation,
+    _color: PhantomData<COLOR>,
+}
+
+impl<COLOR: ColorType + PixelColor> Band<'_, COLOR> {
+    /// First line of the band on the unrotated display
+    pub fn y(&self) -> u32 {
+        self.y
+    }
+
+    /// Number of lines of the band
+    pub fn lines(&self) -> u32 {
+        self.lines
+    }
+
+    /// Area covered by the band, in drawing (rotated) coordinates
+    pub fn area(&self) -> Rectangle {
+        let (width, height) = (self.width, self.height);
+        let (y, lines) = (self.y, self.lines);
+        // inverse of rotate_point for the band's lines
+        match self.rotation {
+            DisplayRotation::Rotate0 => {
+                Rectangle::new(Point::new(0, y as i32), Size::new(width, lines))
+            }
+            DisplayRotation::Rotate90 => {
+                Rectangle::new(Point::new(y as i32, 0), Size::new(lines, width))
+            }
+            DisplayRotation::Rotate180 => Rectangle::new(
+                Point::new(0, (height - y - lines) as i32),
+                Size::new(width, lines),
+            ),
+            DisplayRotation::Rotate270 => Rectangle::new(
+                Point::new((height - y - lines) as i32, 0),
+                Size::new(lines, width),
+            ),
+        }
+    }
+
+    /// The buffer of the band, with both planes for split colors
+    pub fn buffer(&self) -> &[u8] {
+        self.buffer
+    }
+
+    fn set_pixel(&mut self, Pixel(point, color): Pixel<COLOR>) {
+        let (x, y) = rotate_point(point, self.width, self.height, self.rotation);
+        let top = self.y as i32;
+        if x < 0 || x >= self.width as i32 || y < top || y >= top + self.lines as i32 {
+            return;
+        }
+        write_pixel(
+            self.buffer,
+            self.width,
+            self.bwrbit,
+            x as u32,
+            (y - top) as u32,
+            color,
+        );
+    }
+}
+
+/// For use with embedded_grahics
+impl<COLOR: ColorType + PixelColor> DrawTarget for Band<'_, COLOR> {
+    type Color = COLOR;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for pixel in pixels {
+            self.set_pixel(pixel);
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        // only iterate over the part inside of the band
+        let area = area.intersection(&self.area());
+        self.draw_iter(area.points().map(|point| Pixel(point, color)))
+    }
+}
+
+/// For use with embedded_grahics
+impl<COLOR: ColorType + PixelColor> OriginDimensions for Band<'_, COLOR> {
+    fn size(&self) -> Size {
+        match self.rotation {
+            DisplayRotation::Rotate0 | DisplayRotation::Rotate180 => {
+                Size::new(self.width, self.height)
+            }
+            DisplayRotation::Rotate90 | DisplayRotation::Rotate270 => {
+                Size::new(self.height, self.width)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{Color, TriColor};
+    use crate::graphics::VarDisplay;
+    use embedded_graphics::{
+        prelude::*,
+        primitives::{Circle, Line, PrimitiveStyle},
+    };
+
+    fn draw<D: DrawTarget<Color = C>, C: PixelColor>(target: &mut D, fg: C, bg: C) {
+        let _ = target.clear(bg);
+        let _ = Circle::new(Point::new(3, 2), 9)
+            .into_styled(PrimitiveStyle::with_fill(fg))
+            .draw(target);
+        let _ = Line::new(Point::new(0, 15), Point::new(15, 0))
+            .into_styled(PrimitiveStyle::with_stroke(fg, 1))
+            .draw(target);
+    }
+
+    // the bands put together must give the same frame as a full display
+    fn assert_same_as_display<C: ColorType + PixelColor>(fg: C, bg: C) {
+        let rotations = [
+            DisplayRotation::Rotate0,
+            DisplayRotation::Rotate90,
+            DisplayRotation::Rotate180,
+            DisplayRotation::Rotate270,
+        ];
+        for rotation in rotations {
+            let mut full = [0u8; 2 * 24 * 20 / 8];
+            let mut display = VarDisplay::<C>::new(24, 20, &mut full, false).unwrap();
+            display.set_rotation(rotation);
+            draw(&mut display, fg, bg);
+            let plane = 24 * 20 / 8;
+
+            let mut buffer = [0u8; 2 * 3 * 7];
+            let mut renderer = BandRenderer::<C>::new(&mut buffer, 24, 20, false).unwrap();
+            renderer.set_rotation(rotation);
+            assert_eq!(renderer.band_height(), 7 * 2 / C::BUFFER_COUNT as u32);
+
+            let mut lines = 0;
+            renderer
+                .render(
+                    |band| draw(band, fg, bg),
+                    |band| {
+                        assert_eq!((band.x, band.y, band.width), (0, lines, 24));
+                        let size = band.height as usize * 3;
+                        let start = band.y as usize * 3;
+                        for (i, chunk) in band.buffer.chunks(size).enumerate() {
+                            let start = start + i * plane;
+                            assert_eq!(chunk, &display.buffer()[start..start + size]);
+                        }
+                        lines += band.height;
+                        Ok::<(), ()>(())
+                    },
+                )
+                .unwrap();
+            assert_eq!(lines, 20);
+        }
+    }
+
+    #[test]
+    fn bands_match_display() {
+        assert_same_as_display(Color::Black, Color::White);
+        assert_same_as_display(TriColor::Chromatic, TriColor::White);
+    }
+
+    #[test]
+    fn buffer_too_small() {
+        let mut buffer = [0u8; 2];
+        assert!(BandRenderer::<Color>::new(&mut buffer, 24, 20, false).is_err());
+    }
+}
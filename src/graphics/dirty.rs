@@ -0,0 +1,400 @@
+//! Tracking of the parts of a display buffer changed since the last update
+//!
+//! With tracking enabled, [`Display`](super::Display) and [`VarDisplay`](super::VarDisplay)
+//! record where pixels are set. [`take_dirty`](super::Display::take_dirty) returns byte-aligned
+//! windows around them, whose content can be copied out with
+//! [`update_parameters`](super::Display::update_parameters) and sent with
+//! [`update_partial_frame`](crate::prelude::WaveshareDisplay::update_partial_frame):
+//!
+//! ```rust
+//! # use epd_waveshare::{color::Color, graphics::{dirty::DirtyTracking, Display}};
+//! # use embedded_graphics::{prelude::*, primitives::{Line, PrimitiveStyle}};
+//! let mut display = Display::<64, 32, false, { 64 * 32 / 8 }, Color>::default();
+//! display.set_dirty_tracking(DirtyTracking::Rects);
+//!
+//! let _ = Line::new(Point::new(3, 2), Point::new(20, 2))
+//!     .into_styled(PrimitiveStyle::with_stroke(Color::White, 1))
+//!     .draw(&mut display);
+//!
+//! let mut window_buffer = [0u8; 64 * 32 / 8];
+//! for window in display.take_dirty() {
+//!     let params = display.update_parameters(window, &mut window_buffer).unwrap();
+//!     assert_eq!((params.x, params.y, params.width, params.height), (0, 2, 24, 1));
+//!     // epd.update_partial_frame(spi, delay, params.buffer, params.x, params.y, params.width, params.height)
+//! }
+//! assert!(display.take_dirty().is_empty());
+//! ```
+
+use super::{line_bytes, PartialUpdateParameters, VarDisplayError};
+use crate::color::ColorType;
+use crate::rect::Rect;
+
+/// Maximum number of windows recorded with [`DirtyTracking::Rects`]
+pub const MAX_DIRTY_RECTS: usize = 4;
+
+/// How the changes of a display buffer are recorded
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DirtyTracking {
+    /// Nothing is recorded
+    #[default]
+    Off,
+    /// A single window around all changes
+    BoundingBox,
+    /// Up to [`MAX_DIRTY_RECTS`] windows, the closest ones are merged when there are more
+    Rects,
+}
+
+/// Byte-aligned windows returned by `take_dirty` and [`diff`](super::diff), in the coordinates of the unrotated display
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DirtyWindows {
+    rects: [Rect; MAX_DIRTY_RECTS],
+    len: usize,
+}
+
+impl DirtyWindows {
+    /// The windows, they don't overlap
+    pub fn as_slice(&self) -> &[Rect] {
+        &self.rects[..self.len]
+    }
+
+    /// Whether nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl IntoIterator for DirtyWindows {
+    type Item = Rect;
+    type IntoIter = core::iter::Take<core::array::IntoIter<Rect, MAX_DIRTY_RECTS>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.rects.into_iter().take(self.len)
+    }
+}
+
+/// Records the changed pixels of a display
+#[derive(Clone, Copy)]
+pub(crate) struct DirtyTracker {
+    mode: DirtyTracking,
+    rects: [Rect; MAX_DIRTY_RECTS],
+    len: usize,
+}
+
+impl DirtyTracker {
+    pub(crate) const fn new() -> Self {
+        DirtyTracker {
+            mode: DirtyTracking::Off,
+            rects: [Rect::new(0, 0, 0, 0); MAX_DIRTY_RECTS],
+            len: 0,
+        }
+    }
+
+    pub(crate) fn mode(&self) -> DirtyTracking {
+        self.mode
+    }
+
+    /// Changes the mode and forgets what was recorded
+    pub(crate) fn set_mode(&mut self, mode: DirtyTracking) {
+        self.mode = mode;
+        self.len = 0;
+    }
+
+    pub(crate) fn mark(&mut self, x: u32, y: u32) {
+        // pixels in the same byte or the next line extend a window without sending much more
+        self.add(Rect::new(x, y, 1, 1), 7, 0);
+    }
+
+    /// Records a changed area, merged with a window at most `dx`/`dy` pixels away
+    fn add(&mut self, area: Rect, dx: u32, dy: u32) {
+        let capacity = match self.mode {
+            DirtyTracking::Off => return,
+            DirtyTracking::BoundingBox => 1,
+            DirtyTracking::Rects => MAX_DIRTY_RECTS,
+        };
+        let rects = &mut self.rects[..self.len];
+
+        if let Some(rect) = rects.iter_mut().find(|rect| near(**rect, area, dx, dy)) {
+            *rect = rect.union(area);
+        } else if self.len < capacity {
+            self.rects[self.len] = area;
+            self.len += 1;
+        } else if let Some(rect) = rects
+            .iter_mut()
+            .min_by_key(|rect| surface(rect.union(area)) - surface(**rect))
+        {
+            *rect = rect.union(area);
+        }
+    }
+
+    /// Returns the aligned and merged windows and starts over
+    ///
+    /// The windows are widened to whole bytes, except at the right edge of a display `width`
+    /// pixels wide, where `update_partial_frame` expects the window to end.
+    pub(crate) fn take(&mut self, width: u32) -> DirtyWindows {
+        let mut windows = DirtyWindows {
+            rects: self.rects,
+            len: self.len,
+        };
+        self.len = 0;
+
+        for rect in &mut windows.rects[..windows.len] {
+            let x_end = (rect.x + rect.w - 1) | 0b111;
+            rect.x &= !0b111;
+            rect.w = (x_end + 1).min(width) - rect.x;
+        }
+        // aligning may have made windows overlap
+        merge(&mut windows, |a, b| !a.intersect(b).is_empty());
+        windows
+    }
+}
+
+/// Merges the windows for which `close` is true until no such pair is left
+fn merge(windows: &mut DirtyWindows, close: impl Fn(Rect, Rect) -> bool) {
+    let mut i = 0;
+    while i < windows.len {
+        match (i + 1..windows.len).find(|&j| close(windows.rects[i], windows.rects[j])) {
+            Some(j) => {
+                windows.rects[i] = windows.rects[i].union(windows.rects[j]);
+                windows.rects[j] = windows.rects[windows.len - 1];
+                windows.len -= 1;
+                // the grown window may be close to earlier ones
+                i = 0;
+            }
+            None => i += 1,
+        }
+    }
+}
+
+/// Whether the gap between two rectangles is at most `dx` pixels wide and `dy` pixels high
+fn near(a: Rect, b: Rect, dx: u32, dy: u32) -> bool {
+    // negative when they overlap
+    let gap = |a: u32, a_len: u32, b: u32, b_len: u32| {
+        i64::from(a.max(b)) - i64::from((a + a_len).min(b + b_len))
+    };
+    gap(a.x, a.w, b.x, b.w) <= i64::from(dx) && gap(a.y, a.h, b.y, b.h) <= i64::from(dy)
+}
+
+fn surface(rect: Rect) -> u64 {
+    u64::from(rect.w) * u64::from(rect.h)
+}
+
+/// Byte-aligned windows around the differences of two buffers of the same display
+///
+/// Windows at most `merge_distance` pixels apart are merged.
+pub(crate) fn diff<COLOR: ColorType>(
+    old: &[u8],
+    new: &[u8],
+    width: u32,
+    merge_distance: u32,
+) -> DirtyWindows {
+    assert_eq!(
+        old.len(),
+        new.len(),
+        "The buffers should have the same size"
+    );
+    let bits = COLOR::BITS_PER_PIXEL_PER_BUFFER;
+    let line = line_bytes(width, bits);
+    let plane = old.len() / COLOR::BUFFER_COUNT;
+
+    let mut tracker = DirtyTracker::new();
+    tracker.set_mode(DirtyTracking::Rects);
+    for y in 0..plane / line {
+        // groups of 8 pixels, `bits` bytes in every plane
+        for (group, start) in (y * line..(y + 1) * line).step_by(bits).enumerate() {
+            let end = (start + bits).min((y + 1) * line);
+            let differs = (0..COLOR::BUFFER_COUNT).map(|p| p * plane).any(|offset| {
+                old[offset + start..offset + end] != new[offset + start..offset + end]
+            });
+            if differs {
+                let area = Rect::new(group as u32 * 8, y as u32, 8, 1);
+                tracker.add(area, merge_distance, merge_distance);
+            }
+        }
+    }
+
+    let mut windows = tracker.take(width);
+    merge(&mut windows, |a, b| {
+        near(a, b, merge_distance, merge_distance)
+    });
+    windows
+}
+
+/// Copies a byte-aligned window of a display buffer, split buffers plane by plane
+///
+/// `x` must be a multiple of 8, and so must the width unless the window ends at the right edge
+/// of the display. Fails with [`VarDisplayError::InvalidWindow`] otherwise, or when the window
+/// is empty or outside of the display.
+pub(crate) fn copy_window<'a, COLOR: ColorType>(
+    buffer: &[u8],
+    width: u32,
+    window: Rect,
+    window_buffer: &'a mut [u8],
+) -> Result<PartialUpdateParameters<'a>, VarDisplayError> {
+    let bits = COLOR::BITS_PER_PIXEL_PER_BUFFER;
+    let line = line_bytes(width, bits);
+    let plane = buffer.len() / COLOR::BUFFER_COUNT;
+    let x_end = window.x.checked_add(window.w);
+    let y_end = window.y.checked_add(window.h);
+    let fits = !window.is_empty()
+        && x_end.map_or(false, |end| end <= width)
+        && y_end.map_or(false, |end| end as usize <= plane / line);
+    let aligned = window.x % 8 == 0 && (window.w % 8 == 0 || x_end == Some(width));
+    if !fits || !aligned {
+        return Err(VarDisplayError::InvalidWindow);
+    }
+    let start = window.x as usize * bits / 8;
+    let window_line = line_bytes(window.w, bits);
+    let window_plane = window_line * window.h as usize;
+
+    let size = window_plane * COLOR::BUFFER_COUNT;
+    let window_buffer = window_buffer
+        .get_mut(..size)
+        .ok_or(VarDisplayError::BufferTooSmall)?;
+    for (i, window_line_buffer) in window_buffer.chunks_mut(window_line).enumerate() {
+        let (plane_index, row) = (i / window.h as usize, i % window.h as usize);
+        let from = plane_index * plane + (window.y as usize + row) * line + start;
+        window_line_buffer.copy_from_slice(&buffer[from..from + window_line]);
+    }
+
+    Ok(PartialUpdateParameters {
+        x: window.x,
+        y: window.y,
+        width: window.w,
+        height: window.h,
+        buffer: window_buffer,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distant_changes_stay_apart() {
+        let mut tracker = DirtyTracker::new();
+        tracker.set_mode(DirtyTracking::Rects);
+        for x in 3..20 {
+            tracker.mark(x, 2);
+        }
+        tracker.mark(40, 30);
+        let windows = tracker.take(64);
+        assert_eq!(
+            windows.as_slice(),
+            &[Rect::new(0, 2, 24, 1), Rect::new(40, 30, 8, 1)]
+        );
+        assert!(tracker.take(64).is_empty());
+    }
+
+    #[test]
+    fn bounding_box() {
+        let mut tracker = DirtyTracker::new();
+        tracker.set_mode(DirtyTracking::BoundingBox);
+        tracker.mark(3, 2);
+        tracker.mark(40, 30);
+        assert_eq!(tracker.take(64).as_slice(), &[Rect::new(0, 2, 48, 29)]);
+    }
+
+    #[test]
+    fn full_list_grows_the_closest() {
+        let mut tracker = DirtyTracker::new();
+        tracker.set_mode(DirtyTracking::Rects);
+        for (x, y) in [(0, 0), (40, 0), (0, 40), (40, 40), (2, 3)] {
+            tracker.mark(x, y);
+        }
+        assert_eq!(
+            tracker.take(64).as_slice(),
+            &[
+                Rect::new(0, 0, 8, 4),
+                Rect::new(40, 0, 8, 1),
+                Rect::new(0, 40, 8, 1),
+                Rect::new(40, 40, 8, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_merges_close_windows() {
+        let old = [0u8; 4 * 8];
+        let mut new = old;
+        // 32x8 pixels: byte 0 of line 1, byte 1 of line 2 and byte 3 of line 7
+        new[4] = 0x01;
+        new[9] = 0x80;
+        new[31] = 0xFF;
+
+        let windows = diff::<crate::color::Color>(&old, &new, 32, 0);
+        assert_eq!(
+            windows.as_slice(),
+            &[Rect::new(0, 1, 16, 2), Rect::new(24, 7, 8, 1)]
+        );
+        let windows = diff::<crate::color::Color>(&old, &new, 32, 8);
+        assert_eq!(windows.as_slice(), &[Rect::new(0, 1, 32, 7)]);
+        assert!(diff::<crate::color::Color>(&old, &old, 32, 8).is_empty());
+    }
+
+    #[test]
+    fn copies_both_planes() {
+        use crate::color::TriColor;
+        use crate::graphics::VarDisplay;
+        use embedded_graphics_core::prelude::*;
+
+        let mut buffer = [0u8; 2 * 16 * 4 / 8];
+        let mut display = VarDisplay::<TriColor>::new(16, 4, &mut buffer, false).unwrap();
+        display.set_dirty_tracking(DirtyTracking::BoundingBox);
+        display.set_pixel(Pixel(Point::new(9, 2), TriColor::Chromatic));
+
+        let windows = display.take_dirty();
+        assert_eq!(windows.as_slice(), &[Rect::new(8, 2, 8, 1)]);
+        let mut window_buffer = [0u8; 2];
+        let params = display
+            .update_parameters(windows.as_slice()[0], &mut window_buffer)
+            .unwrap();
+        assert_eq!(params.buffer, &[0x40, 0x40]);
+        assert!(matches!(
+            display.update_parameters(Rect::new(0, 0, 16, 4), &mut window_buffer),
+            Err(VarDisplayError::BufferTooSmall)
+        ));
+    }
+
+    #[test]
+    fn windows_end_at_the_right_edge() {
+        use crate::color::Color;
+        use crate::graphics::Display;
+        use crate::window::check_window;
+        use embedded_graphics_core::prelude::*;
+
+        // a line of 122 pixels takes 16 bytes, the last one is only partially used
+        let mut display = Display::<122, 250, false, { 16 * 250 }, Color>::default();
+        display.set_dirty_tracking(DirtyTracking::Rects);
+        display.set_pixel(Pixel(Point::new(121, 3), Color::White));
+
+        let windows = display.take_dirty();
+        assert_eq!(windows.as_slice(), &[Rect::new(120, 3, 2, 1)]);
+        let mut window_buffer = [0u8; 16];
+        let params = display
+            .update_parameters(windows.as_slice()[0], &mut window_buffer)
+            .unwrap();
+        assert_eq!(params.buffer, &[0x40]);
+        assert_eq!(
+            check_window::<()>(params.x, params.y, params.width, params.height, 122, 250),
+            Ok(())
+        );
+
+        let old = Display::<122, 250, false, { 16 * 250 }, Color>::default();
+        let windows = crate::graphics::diff(&old, &display, 0);
+        assert_eq!(windows.as_slice(), &[Rect::new(120, 3, 2, 1)]);
+
+        // misaligned or outside of the display
+        for window in [
+            Rect::new(4, 0, 8, 1),
+            Rect::new(0, 0, 12, 1),
+            Rect::new(120, 0, 8, 1),
+            Rect::new(0, 249, 8, 2),
+            Rect::new(0, 0, 0, 1),
+        ] {
+            assert!(matches!(
+                display.update_parameters(window, &mut window_buffer),
+                Err(VarDisplayError::InvalidWindow)
+            ));
+        }
+    }
+}
@@ -0,0 +1,457 @@
+//! Dithering of true color graphics onto the limited palettes of EPDs
+//!
+//! [`Dither`] wraps any of the display buffers ([`Display`](super::Display),
+//! [`VarDisplay`](super::VarDisplay) or [`PartialFrame`](super::PartialFrame)) and accepts
+//! `Rgb888` (or any color convertible into it, like `Gray8`) instead of the display color.
+//!
+//! ```rust
+//! # use epd_waveshare::{color::OctColor, graphics::{dither::*, Display}};
+//! # use embedded_graphics::{pixelcolor::Rgb888, prelude::*, primitives::{PrimitiveStyle, Rectangle}};
+//! let mut display = Display::<64, 32, false, { 64 * 32 / 2 }, OctColor>::default();
+//!
+//! // the line buffer must be at least as wide as what gets drawn
+//! let mut dither = Dither::<_, 64>::new(&mut display, DitherAlgorithm::FloydSteinberg);
+//! let _ = Rectangle::new(Point::zero(), Size::new(64, 32))
+//!     .into_styled(PrimitiveStyle::with_fill(Rgb888::new(0x80, 0x40, 0x20)))
+//!     .draw(&mut dither);
+//! ```
+
+use crate::color::{
+    closest_entry, Color, Gray2, Lab, OctColor, PaletteEntry, TriColor, IDEAL_ENTRIES,
+};
+use core::marker::PhantomData;
+use embedded_graphics_core::pixelcolor::{Rgb888, RgbColor};
+use embedded_graphics_core::prelude::*;
+use embedded_graphics_core::primitives::Rectangle;
+
+/// Dithering algorithm used by [`Dither`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DitherAlgorithm {
+    /// Floyd–Steinberg error diffusion
+    FloydSteinberg,
+    /// Atkinson error diffusion, which only diffuses 3/4 of the error and gives more contrast
+    Atkinson,
+    /// Ordered dithering with a 4x4 Bayer matrix
+    Bayer4x4,
+    /// Ordered dithering with a 8x8 Bayer matrix
+    Bayer8x8,
+}
+
+/// Display colors which can be dithered to
+pub trait DitherColor: PixelColor + 'static {
+    /// Colors of the display with how they look
+    ///
+    /// Must not be empty, [`Dither::new`] doesn't compile otherwise.
+    const PALETTE: &'static [PaletteEntry<Self>];
+}
+
+/// [`DitherColor::PALETTE`] split into its first entry and the rest, checked when compiling
+struct DefaultPalette<COLOR>(PhantomData<COLOR>);
+
+impl<COLOR: DitherColor> DefaultPalette<COLOR> {
+    const SPLIT: (&'static PaletteEntry<COLOR>, &'static [PaletteEntry<COLOR>]) =
+        match COLOR::PALETTE {
+            [first, rest @ ..] => (first, rest),
+            [] => panic!("the dithering palette is empty"),
+        };
+}
+
+impl DitherColor for Color {
+    const PALETTE: &'static [PaletteEntry<Self>] = &[
+        PaletteEntry::new(Color::Black, (0x00, 0x00, 0x00), Lab::new(0.0, 0.0, 0.0)),
+        PaletteEntry::new(Color::White, (0xff, 0xff, 0xff), Lab::new(100.0, 0.0, 0.0)),
+    ];
+}
+
+/// The chromatic color is assumed to be red, use [`Dither::with_palette`] for other displays.
+impl DitherColor for TriColor {
+    const PALETTE: &'static [PaletteEntry<Self>] = &[
+        PaletteEntry::new(TriColor::Black, (0x00, 0x00, 0x00), Lab::new(0.0, 0.0, 0.0)),
+        PaletteEntry::new(
+            TriColor::White,
+            (0xff, 0xff, 0xff),
+            Lab::new(100.0, 0.0, 0.0),
+        ),
+        PaletteEntry::new(
+            TriColor::Chromatic,
+            (0xff, 0x00, 0x00),
+            Lab::new(53.23, 80.11, 67.22),
+        ),
+    ];
+}
+
+/// Idealised colors, use [`Dither::with_palette`] with the measured palette of the panel
+/// (e.g. [`epd5in65f::PALETTE`](crate::epd5in65f::PALETTE)) for better results.
+impl DitherColor for OctColor {
+    const PALETTE: &'static [PaletteEntry<Self>] = &IDEAL_ENTRIES;
+}
+
+impl DitherColor for Gray2 {
+    const PALETTE: &'static [PaletteEntry<Self>] = &[
+        PaletteEntry::new(Gray2::Black, (0x00, 0x00, 0x00), Lab::new(0.0, 0.0, 0.0)),
+        PaletteEntry::new(
+            Gray2::DarkGray,
+            (0x55, 0x55, 0x55),
+            Lab::new(36.15, 0.0, 0.0),
+        ),
+        PaletteEntry::new(
+            Gray2::LightGray,
+            (0xaa, 0xaa, 0xaa),
+            Lab::new(69.61, 0.0, 0.0),
+        ),
+        PaletteEntry::new(Gray2::White, (0xff, 0xff, 0xff), Lab::new(100.0, 0.0, 0.0)),
+    ];
+}
+
+/// Neighbours receiving the quantization error: (dx, dy, weight)
+struct Kernel {
+    weights: &'static [(isize, usize, i16)],
+    divisor: i16,
+}
+
+const FLOYD_STEINBERG: Kernel = Kernel {
+    weights: &[(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)],
+    divisor: 16,
+};
+
+const ATKINSON: Kernel = Kernel {
+    weights: &[
+        (1, 0, 1),
+        (2, 0, 1),
+        (-1, 1, 1),
+        (0, 1, 1),
+        (1, 1, 1),
+        (0, 2, 1),
+    ],
+    divisor: 8,
+};
+
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+const BAYER_8X8: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Number of rows of quantization errors kept for error diffusion
+const ERROR_ROWS: usize = 3;
+
+/// Quantization errors are kept in 1/32 of a Lab unit
+const ERROR_SCALE: f32 = 32.0;
+
+/// `DrawTarget` adapter dithering true color pixels onto the palette of the wrapped display
+///
+/// The colors are matched, and the quantization errors diffused, in the CIE Lab color space of
+/// the [`PaletteEntry`]s.
+///
+/// - LINE: maximum width of an area which can be error diffused, this bounds the size of the
+///   line buffers (`LINE * 18` bytes)
+/// - C: color accepted by the adapter, `Rgb888` by default
+///
+/// Error diffusion needs the pixels in raster order, which only `fill_contiguous` (used to
+/// draw images) and `fill_solid` guarantee. Pixels drawn one by one, and areas wider than
+/// `LINE`, are dithered with the 8x8 Bayer matrix instead.
+pub struct Dither<'a, T, const LINE: usize, C = Rgb888>
+where
+    T: DrawTarget,
+{
+    target: &'a mut T,
+    algorithm: DitherAlgorithm,
+    first: &'a PaletteEntry<T::Color>,
+    rest: &'a [PaletteEntry<T::Color>],
+    errors: [[[i16; 3]; LINE]; ERROR_ROWS],
+    _color: PhantomData<C>,
+}
+
+impl<'a, T, const LINE: usize, C> Dither<'a, T, LINE, C>
+where
+    T: DrawTarget,
+    T::Color: DitherColor,
+{
+    /// Creates a dithering adapter using the default palette of the display color
+    pub fn new(target: &'a mut T, algorithm: DitherAlgorithm) -> Self {
+        let (first, rest) = DefaultPalette::<T::Color>::SPLIT;
+        Self::from_parts(target, algorithm, first, rest)
+    }
+}
+
+impl<'a, T, const LINE: usize, C> Dither<'a, T, LINE, C>
+where
+    T: DrawTarget,
+{
+    /// Creates a dithering adapter with a custom palette
+    ///
+    /// Useful if the colors of the display don't match the default palette, e.g. for a
+    /// yellow tricolor display or a calibrated [`Palette`](crate::color::Palette).
+    /// Returns `None` if the palette is empty.
+    pub fn with_palette(
+        target: &'a mut T,
+        algorithm: DitherAlgorithm,
+        palette: &'a [PaletteEntry<T::Color>],
+    ) -> Option<Self> {
+        let (first, rest) = palette.split_first()?;
+        Some(Self::from_parts(target, algorithm, first, rest))
+    }
+
+    fn from_parts(
+        target: &'a mut T,
+        algorithm: DitherAlgorithm,
+        first: &'a PaletteEntry<T::Color>,
+        rest: &'a [PaletteEntry<T::Color>],
+    ) -> Self {
+        Dither {
+            target,
+            algorithm,
+            first,
+            rest,
+            errors: [[[0; 3]; LINE]; ERROR_ROWS],
+            _color: PhantomData,
+        }
+    }
+
+    /// Get the dithering algorithm
+    pub fn algorithm(&self) -> DitherAlgorithm {
+        self.algorithm
+    }
+
+    /// Set the dithering algorithm used for the next drawings
+    pub fn set_algorithm(&mut self, algorithm: DitherAlgorithm) {
+        self.algorithm = algorithm;
+    }
+}
+
+impl<T, const LINE: usize, C> Dimensions for Dither<'_, T, LINE, C>
+where
+    T: DrawTarget,
+{
+    fn bounding_box(&self) -> Rectangle {
+        self.target.bounding_box()
+    }
+}
+
+impl<T, const LINE: usize, C> DrawTarget for Dither<'_, T, LINE, C>
+where
+    T: DrawTarget,
+    C: PixelColor + Into<Rgb888>,
+{
+    type Color = C;
+    type Error = T::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let matrix = match self.algorithm {
+            DitherAlgorithm::Bayer4x4 => OrderedMatrix::Bayer4x4,
+            _ => OrderedMatrix::Bayer8x8,
+        };
+        let (first, rest) = (self.first, self.rest);
+        // the thresholds spread over the lightness between two palette colors
+        let spread = 100.0 / rest.len().max(1) as f32;
+        self.target
+            .draw_iter(pixels.into_iter().map(|Pixel(point, color)| {
+                let value = ordered(matrix, spread, point, lab(color.into()));
+                Pixel(point, closest_entry(first, rest, &value).color)
+            }))
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        let kernel = match self.algorithm {
+            DitherAlgorithm::FloydSteinberg => &FLOYD_STEINBERG,
+            DitherAlgorithm::Atkinson => &ATKINSON,
+            DitherAlgorithm::Bayer4x4 | DitherAlgorithm::Bayer8x8 => {
+                return self.draw_iter(
+                    area.points()
+                        .zip(colors)
+                        .map(|(point, color)| Pixel(point, color)),
+                )
+            }
+        };
+        let width = area.size.width as usize;
+        if width > LINE {
+            return self.draw_iter(
+                area.points()
+                    .zip(colors)
+                    .map(|(point, color)| Pixel(point, color)),
+            );
+        }
+
+        let Self {
+            target,
+            first,
+            rest,
+            errors,
+            ..
+        } = self;
+        *errors = [[[0; 3]; LINE]; ERROR_ROWS];
+
+        let top_left = area.top_left;
+        // the colors of fill_solid never end, the area bounds them
+        let pixels = area.points().zip(colors).map(|(point, color)| {
+            let (x, y) = (
+                (point.x - top_left.x) as usize,
+                (point.y - top_left.y) as usize,
+            );
+            let row = &mut errors[y % ERROR_ROWS][x];
+            let value = lab(color.into());
+            let value = Lab::new(
+                (value.l + f32::from(row[0]) / ERROR_SCALE).clamp(0.0, 100.0),
+                (value.a + f32::from(row[1]) / ERROR_SCALE).clamp(-128.0, 127.0),
+                (value.b + f32::from(row[2]) / ERROR_SCALE).clamp(-128.0, 127.0),
+            );
+            // consumed, so the row is clean once it's reused
+            *row = [0; 3];
+
+            let entry = closest_entry(*first, rest, &value);
+            let error = [
+                (value.l - entry.lab.l) * ERROR_SCALE,
+                (value.a - entry.lab.a) * ERROR_SCALE,
+                (value.b - entry.lab.b) * ERROR_SCALE,
+            ]
+            .map(|channel| channel as i16);
+            for &(dx, dy, weight) in kernel.weights {
+                let nx = x as isize + dx;
+                if nx < 0 || nx as usize >= width {
+                    continue;
+                }
+                let target_error = &mut errors[(y + dy) % ERROR_ROWS][nx as usize];
+                for channel in 0..3 {
+                    target_error[channel] += error[channel] * weight / kernel.divisor;
+                }
+            }
+
+            Pixel(point, entry.color)
+        });
+        target.draw_iter(pixels)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum OrderedMatrix {
+    Bayer4x4,
+    Bayer8x8,
+}
+
+/// Adds the threshold of the matrix, spread over `spread`, to the lightness of the color
+fn ordered(matrix: OrderedMatrix, spread: f32, point: Point, value: Lab) -> Lab {
+    let (x, y) = (
+        point.x.rem_euclid(8) as usize,
+        point.y.rem_euclid(8) as usize,
+    );
+    // the middle of the threshold interval, as a fraction
+    let (threshold, scale) = match matrix {
+        OrderedMatrix::Bayer4x4 => (BAYER_4X4[y % 4][x % 4], 1.0 / 32.0),
+        OrderedMatrix::Bayer8x8 => (BAYER_8X8[y][x], 1.0 / 128.0),
+    };
+    let offset = (f32::from(2 * threshold + 1) * scale - 0.5) * spread;
+    Lab::new((value.l + offset).clamp(0.0, 100.0), value.a, value.b)
+}
+
+fn lab(color: Rgb888) -> Lab {
+    Lab::from_rgb((color.r(), color.g(), color.b()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::{Display, VarDisplay};
+    use embedded_graphics::{
+        pixelcolor::Gray8,
+        primitives::{Primitive, PrimitiveStyle},
+        Drawable,
+    };
+
+    fn count_black(buffer: &[u8]) -> u32 {
+        buffer.iter().map(|byte| byte.count_zeros()).sum()
+    }
+
+    #[test]
+    fn error_diffusion_mid_gray() {
+        for algorithm in [DitherAlgorithm::FloydSteinberg, DitherAlgorithm::Atkinson] {
+            let mut display = Display::<16, 16, false, { 16 * 16 / 8 }, Color>::default();
+            let mut dither = Dither::<_, 16>::new(&mut display, algorithm);
+            Rectangle::new(Point::zero(), Size::new(16, 16))
+                .into_styled(PrimitiveStyle::with_fill(Rgb888::new(0x80, 0x80, 0x80)))
+                .draw(&mut dither)
+                .unwrap();
+
+            // about half of the pixels are black
+            let black = count_black(display.buffer());
+            assert!((100..156).contains(&black), "{:?}: {}", algorithm, black);
+        }
+    }
+
+    #[test]
+    fn ordered_mid_gray() {
+        for algorithm in [DitherAlgorithm::Bayer4x4, DitherAlgorithm::Bayer8x8] {
+            let mut display = Display::<16, 16, false, { 16 * 16 / 8 }, Color>::default();
+            let mut dither = Dither::<_, 16, Gray8>::new(&mut display, algorithm);
+            // half way between black and white in Lab (L* = 50)
+            Rectangle::new(Point::zero(), Size::new(16, 16))
+                .into_styled(PrimitiveStyle::with_fill(Gray8::new(0x77)))
+                .draw(&mut dither)
+                .unwrap();
+
+            assert_eq!(count_black(display.buffer()), 128, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn palette_colors_stay_untouched() {
+        let mut buffer = [0u8; 8 * 4 / 2];
+        let mut display = VarDisplay::<OctColor>::new(8, 4, &mut buffer, false).unwrap();
+        let mut dither = Dither::<_, 8>::new(&mut display, DitherAlgorithm::FloydSteinberg);
+        Rectangle::new(Point::zero(), Size::new(8, 4))
+            .into_styled(PrimitiveStyle::with_fill(Rgb888::new(0x00, 0x00, 0xff)))
+            .draw(&mut dither)
+            .unwrap();
+
+        let blue = OctColor::colors_byte(OctColor::Blue, OctColor::Blue);
+        assert!(display.buffer().iter().all(|byte| *byte == blue));
+    }
+
+    #[test]
+    fn custom_palette() {
+        let mut display = Display::<8, 8, false, { 8 * 8 / 8 }, Color>::default();
+        assert!(
+            Dither::<_, 8>::with_palette(&mut display, DitherAlgorithm::Atkinson, &[]).is_none()
+        );
+
+        // a display showing only black
+        let black = [PaletteEntry::from_rgb(Color::Black, (0, 0, 0))];
+        let mut dither =
+            Dither::<_, 8>::with_palette(&mut display, DitherAlgorithm::Atkinson, &black).unwrap();
+        Rectangle::new(Point::zero(), Size::new(8, 8))
+            .into_styled(PrimitiveStyle::with_fill(Rgb888::WHITE))
+            .draw(&mut dither)
+            .unwrap();
+        assert_eq!(count_black(display.buffer()), 64);
+    }
+
+    #[test]
+    fn wider_than_line_buffer() {
+        let draw = |algorithm| {
+            let mut display = Display::<32, 8, false, { 32 * 8 / 8 }, Color>::default();
+            let mut dither = Dither::<_, 8>::new(&mut display, algorithm);
+            Rectangle::new(Point::zero(), Size::new(32, 8))
+                .into_styled(PrimitiveStyle::with_fill(Rgb888::new(0x77, 0x77, 0x77)))
+                .draw(&mut dither)
+                .unwrap();
+            display
+        };
+
+        // falls back to bayer 8x8
+        let display = draw(DitherAlgorithm::Atkinson);
+        assert_eq!(display.buffer(), draw(DitherAlgorithm::Bayer8x8).buffer());
+        assert_eq!(count_black(display.buffer()), 128);
+    }
+}
@@ -1,12 +1,17 @@
+use crate::config::{EpdConfig, DEFAULT_POWER_ON_DELAY_US, DEFAULT_SPI_CHUNK_SIZE};
+use crate::error::Error;
 use crate::traits::Command;
 use core::marker::PhantomData;
-use embedded_hal::{delay::*, digital::*, spi::SpiDevice};
+use embedded_hal::{delay::*, digital::Error as _, digital::*, spi::SpiDevice};
+
+/// Number of bytes `data_x_times` sends per transfer, from a block on the stack
+pub(crate) const REPEAT_BLOCK_LEN: usize = 256;
 
 /// The Connection Interface of all (?) Waveshare EPD-Devices
 ///
 /// SINGLE_BYTE_WRITE defines if a data block is written bytewise
 /// or blockwise to the spi device
-pub(crate) struct DisplayInterface<SPI, BUSY, DC, RST, DELAY, const SINGLE_BYTE_WRITE: bool> {
+pub(crate) struct DisplayInterface<SPI, BUSY, DC, RST, DELAY, PWR, const SINGLE_BYTE_WRITE: bool> {
     /// SPI
     _spi: PhantomData<SPI>,
     /// DELAY
@@ -17,41 +22,76 @@ pub(crate) struct DisplayInterface<SPI, BUSY, DC, RST, DELAY, const SINGLE_BYTE_
     dc: DC,
     /// Pin for Resetting
     rst: RST,
+    /// Pin switching the power rail of the panel, high when powered
+    power: Option<PWR>,
+    /// whether the power rail was switched on
+    powered: bool,
+    /// number of us to wait after switching the power rail on
+    power_on_delay_us: u32,
     /// number of ms the idle loop should sleep on
     delay_us: u32,
+    /// maximum number of us to wait for the device to be idle, None waits forever
+    busy_timeout_us: Option<u32>,
+    /// number of us the reset pin is held low, None uses the value of the driver
+    reset_pulse_us: Option<u32>,
+    /// number of us to wait after a reset
+    reset_settle_us: u32,
+    /// maximum number of bytes per spi transfer, 0 for no limit
+    chunk_size: usize,
 }
 
-impl<SPI, BUSY, DC, RST, DELAY, const SINGLE_BYTE_WRITE: bool>
-    DisplayInterface<SPI, BUSY, DC, RST, DELAY, SINGLE_BYTE_WRITE>
+impl<SPI, BUSY, DC, RST, DELAY, PWR, const SINGLE_BYTE_WRITE: bool>
+    DisplayInterface<SPI, BUSY, DC, RST, DELAY, PWR, SINGLE_BYTE_WRITE>
 where
     SPI: SpiDevice,
     BUSY: InputPin,
     DC: OutputPin,
     RST: OutputPin,
     DELAY: DelayNs,
+    PWR: OutputPin,
 {
     /// Creates a new `DisplayInterface` struct
     ///
-    /// If no delay is given, a default delay of 10ms is used.
-    pub fn new(busy: BUSY, dc: DC, rst: RST, delay_us: Option<u32>) -> Self {
-        // default delay of 10ms
-        let delay_us = delay_us.unwrap_or(10_000);
+    /// The settings missing from `config` default to a busy poll of 10ms, a reset settle time
+    /// of 200ms, [`DEFAULT_SPI_CHUNK_SIZE`] bytes per transfer and a power on delay of
+    /// [`DEFAULT_POWER_ON_DELAY_US`].
+    pub fn new(busy: BUSY, dc: DC, rst: RST, config: EpdConfig<PWR>) -> Self {
         DisplayInterface {
             _spi: PhantomData,
             _delay: PhantomData,
             busy,
             dc,
             rst,
-            delay_us,
+            power: config.power,
+            powered: false,
+            power_on_delay_us: config
+                .power_on_delay_us
+                .unwrap_or(DEFAULT_POWER_ON_DELAY_US),
+            delay_us: config.busy_poll_us.unwrap_or(10_000),
+            busy_timeout_us: config.busy_timeout_us,
+            reset_pulse_us: config.reset_pulse_us,
+            reset_settle_us: config.reset_settle_us.unwrap_or(200_000),
+            chunk_size: config.spi_chunk_size.unwrap_or(DEFAULT_SPI_CHUNK_SIZE),
         }
     }
 
+    /// Sets the maximum number of us to wait for the device to be idle
+    ///
+    /// None waits forever
+    pub(crate) fn set_busy_timeout(&mut self, busy_timeout_us: Option<u32>) {
+        self.busy_timeout_us = busy_timeout_us;
+    }
+
     /// Basic function for sending [Commands](Command).
     ///
     /// Enables direct interaction with the device with the help of [data()](DisplayInterface::data())
-    pub(crate) fn cmd<T: Command>(&mut self, spi: &mut SPI, command: T) -> Result<(), SPI::Error> {
+    pub(crate) fn cmd<T: Command>(
+        &mut self,
+        spi: &mut SPI,
+        command: T,
+    ) -> Result<(), Error<SPI::Error>> {
         // low for commands
-        let _ = self.dc.set_low();
+        self.dc.set_low().map_err(|e| Error::Pin(e.kind()))?;
 
         // Transfer the command over spi
         self.write(spi, &[command.address()])
@@ -60,9 +100,9 @@ where
     /// Basic function for sending an array of u8-values of data over spi
     ///
     /// Enables direct interaction with the device with the help of [command()](Epd4in2::command())
-    pub(crate) fn data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
+    pub(crate) fn data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), Error<SPI::Error>> {
         // high for data
-        let _ = self.dc.set_high();
+        self.dc.set_high().map_err(|e| Error::Pin(e.kind()))?;
 
         if SINGLE_BYTE_WRITE {
             for val in data.iter().copied() {
@@ -84,42 +124,59 @@ where
         spi: &mut SPI,
         command: T,
         data: &[u8],
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         self.cmd(spi, command)?;
         self.data(spi, data)
     }
 
     /// Basic function for sending the same byte of data (one u8) multiple times over spi
     ///
+    /// Unless SINGLE_BYTE_WRITE is set, the bytes are sent in blocks of [`REPEAT_BLOCK_LEN`].
+    ///
     /// Enables direct interaction with the device with the help of [command()](ConnectionInterface::command())
     pub(crate) fn data_x_times(
         &mut self,
         spi: &mut SPI,
         val: u8,
         repetitions: u32,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
         // high for data
-        let _ = self.dc.set_high();
-        // Transfer data (u8) over spi
-        for _ in 0..repetitions {
-            self.write(spi, &[val])?;
+        self.dc.set_high().map_err(|e| Error::Pin(e.kind()))?;
+        if SINGLE_BYTE_WRITE {
+            // Transfer data (u8) over spi
+            for _ in 0..repetitions {
+                self.write(spi, &[val])?;
+            }
+            return Ok(());
+        }
+        let block = [val; REPEAT_BLOCK_LEN];
+        let mut remaining = repetitions as usize;
+        while remaining > 0 {
+            let len = remaining.min(REPEAT_BLOCK_LEN);
+            self.write(spi, &block[..len])?;
+            remaining -= len;
         }
         Ok(())
     }
 
+    /// Basic function for reading the answer to a [Command](Command)
+    ///
+    /// The controller answers on DIN, see [reading from the controller](crate#reading-from-the-controller).
+    pub(crate) fn read(&mut self, spi: &mut SPI, data: &mut [u8]) -> Result<(), Error<SPI::Error>> {
+        // high for data
+        self.dc.set_high().map_err(|e| Error::Pin(e.kind()))?;
+        spi.read(data).map_err(Error::Spi)
+    }
+
     // spi write helper/abstraction function
-    fn write(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
-        // transfer spi data
-        // Be careful!! Linux has a default limit of 4096 bytes per spi transfer
-        // see https://raspberrypi.stackexchange.com/questions/65595/spi-transfer-fails-with-buffer-size-greater-than-4096
-        if cfg!(target_os = "linux") {
-            for data_chunk in data.chunks(4096) {
-                spi.write(data_chunk)?;
-            }
-            Ok(())
-        } else {
-            spi.write(data)
+    fn write(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        if self.chunk_size == 0 {
+            return spi.write(data).map_err(Error::Spi);
+        }
+        for data_chunk in data.chunks(self.chunk_size) {
+            spi.write(data_chunk).map_err(Error::Spi)?;
         }
+        Ok(())
     }
 
     /// Waits until device isn't busy anymore (busy == HIGH)
@@ -134,18 +191,29 @@ where
     ///  - FALSE for epd2in9, epd1in54 (for all Display Type A ones?)
     ///
     /// Most likely there was a mistake with the 2in9 busy connection
-    pub(crate) fn wait_until_idle(&mut self, delay: &mut DELAY, is_busy_low: bool) {
-        while self.is_busy(is_busy_low) {
-            // This has been removed and added many time :
-            // - it is faster to not have it
-            // - it is complicated to pass the delay everywhere all the time
-            // - busy waiting can consume more power that delaying
-            // - delay waiting enables task switching on realtime OS
-            // -> keep it and leave the decision to the user
-            if self.delay_us > 0 {
-                delay.delay_us(self.delay_us);
-            }
+    ///
+    /// Returns [`Error::BusyTimeout`] if the device is still busy after the configured timeout
+    pub(crate) fn wait_until_idle(
+        &mut self,
+        delay: &mut DELAY,
+        is_busy_low: bool,
+    ) -> Result<(), Error<SPI::Error>> {
+        let mut waited_us = 0;
+        while self.is_busy(is_busy_low)? {
+            self.idle_delay(delay, &mut waited_us)?;
         }
+        Ok(())
+    }
+
+    /// Same as `is_busy` for device needing a command to probe Busy pin
+    pub(crate) fn is_busy_with_cmd<T: Command>(
+        &mut self,
+        spi: &mut SPI,
+        is_busy_low: bool,
+        status_command: T,
+    ) -> Result<bool, Error<SPI::Error>> {
+        self.cmd(spi, status_command)?;
+        self.is_busy(is_busy_low)
     }
 
     /// Same as `wait_until_idle` for device needing a command to probe Busy pin
@@ -155,17 +223,42 @@ where
         delay: &mut DELAY,
         is_busy_low: bool,
         status_command: T,
-    ) -> Result<(), SPI::Error> {
+    ) -> Result<(), Error<SPI::Error>> {
+        let mut waited_us = 0;
         self.cmd(spi, status_command)?;
-        if self.delay_us > 0 {
-            delay.delay_us(self.delay_us);
-        }
-        while self.is_busy(is_busy_low) {
+        self.idle_delay(delay, &mut waited_us)?;
+        while self.is_busy(is_busy_low)? {
             self.cmd(spi, status_command)?;
-            if self.delay_us > 0 {
-                delay.delay_us(self.delay_us);
-            }
+            self.idle_delay(delay, &mut waited_us)?;
+        }
+        Ok(())
+    }
+
+    /// Sleeps between two busy probes and keeps track of the time spent waiting
+    ///
+    /// Returns [`Error::BusyTimeout`] once `waited_us` exceeds the configured timeout.
+    /// When a timeout is configured, busy waiting (`delay_us == 0`) still sleeps 1us per probe
+    /// so the time spent waiting can be accounted for.
+    fn idle_delay(
+        &mut self,
+        delay: &mut DELAY,
+        waited_us: &mut u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        let step = match self.busy_timeout_us {
+            Some(timeout) if *waited_us >= timeout => return Err(Error::BusyTimeout),
+            Some(_) => self.delay_us.max(1),
+            None => self.delay_us,
+        };
+        // This has been removed and added many time :
+        // - it is faster to not have it
+        // - it is complicated to pass the delay everywhere all the time
+        // - busy waiting can consume more power that delaying
+        // - delay waiting enables task switching on realtime OS
+        // -> keep it and leave the decision to the user
+        if step > 0 {
+            delay.delay_us(step);
         }
+        *waited_us = waited_us.saturating_add(step);
         Ok(())
     }
 
@@ -182,9 +275,13 @@ where
     ///
     /// Most likely there was a mistake with the 2in9 busy connection
     /// //TODO: use the #cfg feature to make this compile the right way for the certain types
-    pub(crate) fn is_busy(&mut self, is_busy_low: bool) -> bool {
-        (is_busy_low && self.busy.is_low().unwrap_or(false))
-            || (!is_busy_low && self.busy.is_high().unwrap_or(false))
+    pub(crate) fn is_busy(&mut self, is_busy_low: bool) -> Result<bool, Error<SPI::Error>> {
+        if is_busy_low {
+            self.busy.is_low()
+        } else {
+            self.busy.is_high()
+        }
+        .map_err(|e| Error::Pin(e.kind()))
     }
 
     /// Resets the device.
@@ -194,15 +291,285 @@ where
     /// The timing of keeping the reset pin low seems to be important and different per device.
     /// Most displays seem to require keeping it low for 10ms, but the 7in5_v2 only seems to reset
     /// properly with 2ms
-    pub(crate) fn reset(&mut self, delay: &mut DELAY, initial_delay: u32, duration: u32) {
-        let _ = self.rst.set_high();
+    ///
+    /// A power rail that is off is switched on first.
+    pub(crate) fn reset(
+        &mut self,
+        delay: &mut DELAY,
+        initial_delay: u32,
+        duration: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.power_up(delay)?;
+        self.rst.set_high().map_err(|e| Error::Pin(e.kind()))?;
         delay.delay_us(initial_delay);
 
-        let _ = self.rst.set_low();
-        delay.delay_us(duration);
-        let _ = self.rst.set_high();
-        //TODO: the upstream libraries always sleep for 200ms here
-        // 10ms works fine with just for the 7in5_v2 but this needs to be validated for other devices
-        delay.delay_us(200_000);
+        self.rst.set_low().map_err(|e| Error::Pin(e.kind()))?;
+        delay.delay_us(self.reset_pulse_us.unwrap_or(duration));
+        self.rst.set_high().map_err(|e| Error::Pin(e.kind()))?;
+        // the upstream libraries always sleep for 200ms here, 10ms works fine for the 7in5_v2
+        delay.delay_us(self.reset_settle_us);
+        Ok(())
+    }
+
+    /// Switches the power rail of the panel with the power pin
+    ///
+    /// The dc and reset pins are driven low before switching it off, so the panel isn't powered
+    /// through them. Returns [`Error::Unsupported`] without a power pin.
+    pub(crate) fn switch_power(
+        &mut self,
+        delay: &mut DELAY,
+        on: bool,
+    ) -> Result<(), Error<SPI::Error>> {
+        if self.power.is_none() {
+            return Err(Error::Unsupported);
+        }
+        if on {
+            return self.power_up(delay);
+        }
+        self.dc.set_low().map_err(|e| Error::Pin(e.kind()))?;
+        self.rst.set_low().map_err(|e| Error::Pin(e.kind()))?;
+        if let Some(power) = &mut self.power {
+            power.set_low().map_err(|e| Error::Pin(e.kind()))?;
+        }
+        self.powered = false;
+        Ok(())
+    }
+
+    /// Switches the power rail on and waits for the panel to settle, unless it is already on
+    fn power_up(&mut self, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        match &mut self.power {
+            Some(power) if !self.powered => {
+                power.set_high().map_err(|e| Error::Pin(e.kind()))?;
+                delay.delay_us(self.power_on_delay_us);
+                self.powered = true;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, NoopDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction as PinTransaction};
+    use embedded_hal_mock::eh1::spi::{Mock as SpiMock, Transaction as SpiTransaction};
+    use embedded_hal_mock::eh1::MockError;
+
+    #[test]
+    fn wait_until_idle_times_out() {
+        // busy pin stuck low (is_busy_low): 0, 4, 8 and 12us are probed
+        let mut busy = PinMock::new(&[
+            PinTransaction::get(State::Low),
+            PinTransaction::get(State::Low),
+            PinTransaction::get(State::Low),
+            PinTransaction::get(State::Low),
+        ]);
+        let mut dc = PinMock::new(&[]);
+        let mut rst = PinMock::new(&[]);
+        let mut spi = SpiMock::<u8>::new(&[]);
+
+        let mut interface: DisplayInterface<SpiMock<u8>, _, _, _, NoopDelay, _, false> =
+            DisplayInterface::new(
+                busy.clone(),
+                dc.clone(),
+                rst.clone(),
+                EpdConfig::new().busy_poll_us(4),
+            );
+        interface.set_busy_timeout(Some(10));
+
+        let result = interface.wait_until_idle(&mut NoopDelay::new(), true);
+        assert!(matches!(result, Err(Error::BusyTimeout)));
+
+        busy.done();
+        dc.done();
+        rst.done();
+        spi.done();
+    }
+
+    #[test]
+    fn cmd_propagates_dc_error() {
+        let mut busy = PinMock::new(&[]);
+        let mut dc = PinMock::new(&[PinTransaction::set(State::Low)
+            .with_error(MockError::Io(std::io::ErrorKind::NotConnected))]);
+        let mut rst = PinMock::new(&[]);
+        // nothing may be sent over spi if the dc pin couldn't be set
+        let mut spi = SpiMock::<u8>::new(&[]);
+
+        let mut interface: DisplayInterface<SpiMock<u8>, _, _, _, NoopDelay, _, false> =
+            DisplayInterface::new(busy.clone(), dc.clone(), rst.clone(), EpdConfig::new());
+
+        let result = interface.cmd(&mut spi, crate::type_a::command::Command::SwReset);
+        assert!(matches!(result, Err(Error::Pin(ErrorKind::Other))));
+
+        busy.done();
+        dc.done();
+        rst.done();
+        spi.done();
+    }
+
+    #[test]
+    fn data_is_sent_in_chunks() {
+        let mut busy = PinMock::new(&[]);
+        let mut dc = PinMock::new(&[PinTransaction::set(State::High)]);
+        let mut rst = PinMock::new(&[]);
+        let mut spi = SpiMock::<u8>::new(&[
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write_vec(std::vec![1, 2]),
+            SpiTransaction::transaction_end(),
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write_vec(std::vec![3]),
+            SpiTransaction::transaction_end(),
+        ]);
+
+        let mut interface: DisplayInterface<SpiMock<u8>, _, _, _, NoopDelay, _, false> =
+            DisplayInterface::new(
+                busy.clone(),
+                dc.clone(),
+                rst.clone(),
+                EpdConfig::new().spi_chunk_size(2),
+            );
+        interface.data(&mut spi, &[1, 2, 3]).unwrap();
+
+        busy.done();
+        dc.done();
+        rst.done();
+        spi.done();
+    }
+
+    #[test]
+    fn data_x_times_sends_blocks() {
+        let mut busy = PinMock::new(&[]);
+        let mut dc = PinMock::new(&[PinTransaction::set(State::High)]);
+        let mut rst = PinMock::new(&[]);
+        let mut spi = SpiMock::<u8>::new(&[
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write_vec(std::vec![0xAA; REPEAT_BLOCK_LEN]),
+            SpiTransaction::transaction_end(),
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write_vec(std::vec![0xAA; 44]),
+            SpiTransaction::transaction_end(),
+        ]);
+
+        let mut interface: DisplayInterface<SpiMock<u8>, _, _, _, NoopDelay, _, false> =
+            DisplayInterface::new(
+                busy.clone(),
+                dc.clone(),
+                rst.clone(),
+                EpdConfig::new().spi_chunk_size(0),
+            );
+        interface
+            .data_x_times(&mut spi, 0xAA, REPEAT_BLOCK_LEN as u32 + 44)
+            .unwrap();
+
+        busy.done();
+        dc.done();
+        rst.done();
+        spi.done();
+    }
+
+    #[test]
+    fn reset_uses_the_configured_timings() {
+        let mut busy = PinMock::new(&[]);
+        let mut dc = PinMock::new(&[]);
+        let mut rst = PinMock::new(&[
+            PinTransaction::set(State::High),
+            PinTransaction::set(State::Low),
+            PinTransaction::set(State::High),
+        ]);
+        let mut delay = CheckedDelay::new(&[
+            DelayTransaction::delay_us(10),
+            DelayTransaction::delay_us(20_000),
+            DelayTransaction::delay_us(1_000),
+        ]);
+
+        let config = EpdConfig::new()
+            .reset_pulse_us(20_000)
+            .reset_settle_us(1_000);
+        let mut interface: DisplayInterface<SpiMock<u8>, _, _, _, CheckedDelay, _, false> =
+            DisplayInterface::new(busy.clone(), dc.clone(), rst.clone(), config);
+        interface.reset(&mut delay, 10, 2_000).unwrap();
+
+        busy.done();
+        dc.done();
+        rst.done();
+        delay.done();
+    }
+
+    #[test]
+    fn power_off_drives_the_pins_low() {
+        let mut busy = PinMock::new(&[]);
+        let mut dc = PinMock::new(&[PinTransaction::set(State::Low)]);
+        let mut rst = PinMock::new(&[PinTransaction::set(State::Low)]);
+        let mut power = PinMock::new(&[PinTransaction::set(State::Low)]);
+
+        let config = EpdConfig::new().power_pin(power.clone());
+        let mut interface: DisplayInterface<SpiMock<u8>, _, _, _, NoopDelay, _, false> =
+            DisplayInterface::new(busy.clone(), dc.clone(), rst.clone(), config);
+        interface
+            .switch_power(&mut NoopDelay::new(), false)
+            .unwrap();
+
+        busy.done();
+        dc.done();
+        rst.done();
+        power.done();
+    }
+
+    #[test]
+    fn reset_switches_the_power_on_once() {
+        let mut busy = PinMock::new(&[]);
+        let mut dc = PinMock::new(&[]);
+        let reset = [
+            PinTransaction::set(State::High),
+            PinTransaction::set(State::Low),
+            PinTransaction::set(State::High),
+        ];
+        let mut rst = PinMock::new(&[&reset[..], &reset[..]].concat());
+        let mut power = PinMock::new(&[PinTransaction::set(State::High)]);
+        let mut delay = CheckedDelay::new(&[
+            DelayTransaction::delay_us(5_000),
+            DelayTransaction::delay_us(0),
+            DelayTransaction::delay_us(0),
+            DelayTransaction::delay_us(0),
+            DelayTransaction::delay_us(0),
+            DelayTransaction::delay_us(0),
+            DelayTransaction::delay_us(0),
+        ]);
+
+        let config = EpdConfig::new()
+            .reset_pulse_us(0)
+            .reset_settle_us(0)
+            .power_on_delay_us(5_000)
+            .power_pin(power.clone());
+        let mut interface: DisplayInterface<SpiMock<u8>, _, _, _, CheckedDelay, _, false> =
+            DisplayInterface::new(busy.clone(), dc.clone(), rst.clone(), config);
+        interface.reset(&mut delay, 0, 0).unwrap();
+        interface.reset(&mut delay, 0, 0).unwrap();
+
+        busy.done();
+        dc.done();
+        rst.done();
+        power.done();
+        delay.done();
+    }
+
+    #[test]
+    fn switch_power_needs_a_pin() {
+        let mut busy = PinMock::new(&[]);
+        let mut dc = PinMock::new(&[]);
+        let mut rst = PinMock::new(&[]);
+
+        let mut interface: DisplayInterface<SpiMock<u8>, _, _, _, NoopDelay, _, false> =
+            DisplayInterface::new(busy.clone(), dc.clone(), rst.clone(), EpdConfig::new());
+        let result = interface.switch_power(&mut NoopDelay::new(), false);
+        assert!(matches!(result, Err(Error::Unsupported)));
+
+        busy.done();
+        dc.done();
+        rst.done();
     }
 }
@@ -0,0 +1,348 @@
+use crate::config::{EpdConfig, DEFAULT_POWER_ON_DELAY_US, DEFAULT_SPI_CHUNK_SIZE};
+use crate::error::Error;
+use crate::interface::REPEAT_BLOCK_LEN;
+use crate::traits::Command;
+use core::future::{poll_fn, Future};
+use core::marker::PhantomData;
+use core::pin::pin;
+use core::task::Poll;
+use embedded_hal::digital::{Error as _, InputPin, OutputPin};
+use embedded_hal_async::{delay::DelayNs, digital::Wait, spi::SpiDevice};
+
+/// The async Connection Interface of all (?) Waveshare EPD-Devices
+///
+/// Same as [`DisplayInterface`](crate::interface::DisplayInterface), except that
+/// spi transfers and delays are awaited and the busy pin is awaited on an edge
+/// instead of being polled.
+///
+/// SINGLE_BYTE_WRITE defines if a data block is written bytewise
+/// or blockwise to the spi device
+pub(crate) struct DisplayInterfaceAsync<
+    SPI,
+    BUSY,
+    DC,
+    RST,
+    DELAY,
+    PWR,
+    const SINGLE_BYTE_WRITE: bool,
+> {
+    /// SPI
+    _spi: PhantomData<SPI>,
+    /// DELAY
+    _delay: PhantomData<DELAY>,
+    /// Low for busy, Wait until display is ready!
+    busy: BUSY,
+    /// Data/Command Control Pin (High for data, Low for command)
+    dc: DC,
+    /// Pin for Resetting
+    rst: RST,
+    /// Pin switching the power rail of the panel, high when powered
+    power: Option<PWR>,
+    /// whether the power rail was switched on
+    powered: bool,
+    /// number of us to wait after switching the power rail on
+    power_on_delay_us: u32,
+    /// number of us to sleep between two status probes in `wait_until_idle_with_cmd`
+    delay_us: u32,
+    /// maximum number of us to wait for the device to be idle, None waits forever
+    busy_timeout_us: Option<u32>,
+    /// number of us the reset pin is held low, None uses the value of the driver
+    reset_pulse_us: Option<u32>,
+    /// number of us to wait after a reset
+    reset_settle_us: u32,
+    /// maximum number of bytes per spi transfer, 0 for no limit
+    chunk_size: usize,
+}
+
+// the async feature needs Rust 1.75+ anyway (async fn in traits), see Cargo.toml
+#[clippy::msrv = "1.75"]
+impl<SPI, BUSY, DC, RST, DELAY, PWR, const SINGLE_BYTE_WRITE: bool>
+    DisplayInterfaceAsync<SPI, BUSY, DC, RST, DELAY, PWR, SINGLE_BYTE_WRITE>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin + Wait,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    /// Creates a new `DisplayInterfaceAsync` struct
+    ///
+    /// The settings missing from `config` default to a busy poll of 10ms, a reset settle time
+    /// of 200ms, [`DEFAULT_SPI_CHUNK_SIZE`] bytes per transfer and a power on delay of
+    /// [`DEFAULT_POWER_ON_DELAY_US`].
+    pub fn new(busy: BUSY, dc: DC, rst: RST, config: EpdConfig<PWR>) -> Self {
+        DisplayInterfaceAsync {
+            _spi: PhantomData,
+            _delay: PhantomData,
+            busy,
+            dc,
+            rst,
+            power: config.power,
+            powered: false,
+            power_on_delay_us: config
+                .power_on_delay_us
+                .unwrap_or(DEFAULT_POWER_ON_DELAY_US),
+            delay_us: config.busy_poll_us.unwrap_or(10_000),
+            busy_timeout_us: config.busy_timeout_us,
+            reset_pulse_us: config.reset_pulse_us,
+            reset_settle_us: config.reset_settle_us.unwrap_or(200_000),
+            chunk_size: config.spi_chunk_size.unwrap_or(DEFAULT_SPI_CHUNK_SIZE),
+        }
+    }
+
+    /// Sets the maximum number of us to wait for the device to be idle
+    ///
+    /// None waits forever
+    pub(crate) fn set_busy_timeout(&mut self, busy_timeout_us: Option<u32>) {
+        self.busy_timeout_us = busy_timeout_us;
+    }
+
+    /// Basic function for sending [Commands](Command).
+    pub(crate) async fn cmd<T: Command>(
+        &mut self,
+        spi: &mut SPI,
+        command: T,
+    ) -> Result<(), Error<SPI::Error>> {
+        // low for commands
+        self.dc.set_low().map_err(|e| Error::Pin(e.kind()))?;
+
+        // Transfer the command over spi
+        self.write(spi, &[command.address()]).await
+    }
+
+    /// Basic function for sending an array of u8-values of data over spi
+    pub(crate) async fn data(
+        &mut self,
+        spi: &mut SPI,
+        data: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        // high for data
+        self.dc.set_high().map_err(|e| Error::Pin(e.kind()))?;
+
+        if SINGLE_BYTE_WRITE {
+            for val in data.iter().copied() {
+                // Transfer data one u8 at a time over spi
+                self.write(spi, &[val]).await?;
+            }
+        } else {
+            self.write(spi, data).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Basic function for sending [Commands](Command) and the data belonging to it.
+    pub(crate) async fn cmd_with_data<T: Command>(
+        &mut self,
+        spi: &mut SPI,
+        command: T,
+        data: &[u8],
+    ) -> Result<(), Error<SPI::Error>> {
+        self.cmd(spi, command).await?;
+        self.data(spi, data).await
+    }
+
+    /// Basic function for sending the same byte of data (one u8) multiple times over spi
+    pub(crate) async fn data_x_times(
+        &mut self,
+        spi: &mut SPI,
+        val: u8,
+        repetitions: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        // high for data
+        self.dc.set_high().map_err(|e| Error::Pin(e.kind()))?;
+        if SINGLE_BYTE_WRITE {
+            // Transfer data (u8) over spi
+            for _ in 0..repetitions {
+                self.write(spi, &[val]).await?;
+            }
+            return Ok(());
+        }
+        let block = [val; REPEAT_BLOCK_LEN];
+        let mut remaining = repetitions as usize;
+        while remaining > 0 {
+            let len = remaining.min(REPEAT_BLOCK_LEN);
+            self.write(spi, &block[..len]).await?;
+            remaining -= len;
+        }
+        Ok(())
+    }
+
+    // spi write helper/abstraction function
+    async fn write(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        if self.chunk_size == 0 {
+            return spi.write(data).await.map_err(Error::Spi);
+        }
+        for data_chunk in data.chunks(self.chunk_size) {
+            spi.write(data_chunk).await.map_err(Error::Spi)?;
+        }
+        Ok(())
+    }
+
+    /// Waits until device isn't busy anymore
+    ///
+    /// Instead of polling, this awaits the edge on the busy pin, so the executor
+    /// is free to run other tasks in the meantime.
+    ///
+    /// See [`DisplayInterface::wait_until_idle`](crate::interface::DisplayInterface::wait_until_idle)
+    /// for the meaning of `is_busy_low`.
+    ///
+    /// Returns [`Error::BusyTimeout`] if the device is still busy after the configured timeout
+    pub(crate) async fn wait_until_idle(
+        &mut self,
+        delay: &mut DELAY,
+        is_busy_low: bool,
+    ) -> Result<(), Error<SPI::Error>> {
+        let busy = &mut self.busy;
+        // wait_for_* resolves immediately if the pin already is at the requested level
+        let mut idle = pin!(async move {
+            if is_busy_low {
+                busy.wait_for_high().await
+            } else {
+                busy.wait_for_low().await
+            }
+        });
+
+        let result = match self.busy_timeout_us {
+            None => Some(idle.await),
+            Some(timeout) => {
+                let mut timeout = pin!(delay.delay_us(timeout));
+                // first one of idle and timeout to complete
+                poll_fn(|cx| {
+                    if let Poll::Ready(result) = idle.as_mut().poll(cx) {
+                        Poll::Ready(Some(result))
+                    } else if timeout.as_mut().poll(cx).is_ready() {
+                        Poll::Ready(None)
+                    } else {
+                        Poll::Pending
+                    }
+                })
+                .await
+            }
+        };
+        match result {
+            Some(result) => result.map_err(|e| Error::Pin(e.kind())),
+            None => Err(Error::BusyTimeout),
+        }
+    }
+
+    /// Same as `wait_until_idle` for device needing a command to probe Busy pin
+    pub(crate) async fn wait_until_idle_with_cmd<T: Command>(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        is_busy_low: bool,
+        status_command: T,
+    ) -> Result<(), Error<SPI::Error>> {
+        let mut waited_us = 0;
+        self.cmd(spi, status_command).await?;
+        self.idle_delay(delay, &mut waited_us).await?;
+        while self.is_busy(is_busy_low)? {
+            self.cmd(spi, status_command).await?;
+            self.idle_delay(delay, &mut waited_us).await?;
+        }
+        Ok(())
+    }
+
+    /// Same as `is_busy` for device needing a command to probe Busy pin
+    pub(crate) async fn is_busy_with_cmd<T: Command>(
+        &mut self,
+        spi: &mut SPI,
+        is_busy_low: bool,
+        status_command: T,
+    ) -> Result<bool, Error<SPI::Error>> {
+        self.cmd(spi, status_command).await?;
+        self.is_busy(is_busy_low)
+    }
+
+    /// Sleeps between two busy probes and keeps track of the time spent waiting
+    ///
+    /// See [`DisplayInterface::idle_delay`](crate::interface::DisplayInterface::idle_delay)
+    async fn idle_delay(
+        &mut self,
+        delay: &mut DELAY,
+        waited_us: &mut u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        let step = match self.busy_timeout_us {
+            Some(timeout) if *waited_us >= timeout => return Err(Error::BusyTimeout),
+            Some(_) => self.delay_us.max(1),
+            None => self.delay_us,
+        };
+        if step > 0 {
+            delay.delay_us(step).await;
+        }
+        *waited_us = waited_us.saturating_add(step);
+        Ok(())
+    }
+
+    /// Checks if device is still busy
+    ///
+    /// See [`DisplayInterface::is_busy`](crate::interface::DisplayInterface::is_busy)
+    pub(crate) fn is_busy(&mut self, is_busy_low: bool) -> Result<bool, Error<SPI::Error>> {
+        if is_busy_low {
+            self.busy.is_low()
+        } else {
+            self.busy.is_high()
+        }
+        .map_err(|e| Error::Pin(e.kind()))
+    }
+
+    /// Resets the device.
+    ///
+    /// See [`DisplayInterface::reset`](crate::interface::DisplayInterface::reset)
+    pub(crate) async fn reset(
+        &mut self,
+        delay: &mut DELAY,
+        initial_delay: u32,
+        duration: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.power_up(delay).await?;
+        self.rst.set_high().map_err(|e| Error::Pin(e.kind()))?;
+        delay.delay_us(initial_delay).await;
+
+        self.rst.set_low().map_err(|e| Error::Pin(e.kind()))?;
+        delay
+            .delay_us(self.reset_pulse_us.unwrap_or(duration))
+            .await;
+        self.rst.set_high().map_err(|e| Error::Pin(e.kind()))?;
+        // the upstream libraries always sleep for 200ms here, 10ms works fine for the 7in5_v2
+        delay.delay_us(self.reset_settle_us).await;
+        Ok(())
+    }
+
+    /// Switches the power rail of the panel with the power pin, see
+    /// [`DisplayInterface::switch_power`](crate::interface::DisplayInterface::switch_power)
+    pub(crate) async fn switch_power(
+        &mut self,
+        delay: &mut DELAY,
+        on: bool,
+    ) -> Result<(), Error<SPI::Error>> {
+        if self.power.is_none() {
+            return Err(Error::Unsupported);
+        }
+        if on {
+            return self.power_up(delay).await;
+        }
+        self.dc.set_low().map_err(|e| Error::Pin(e.kind()))?;
+        self.rst.set_low().map_err(|e| Error::Pin(e.kind()))?;
+        if let Some(power) = &mut self.power {
+            power.set_low().map_err(|e| Error::Pin(e.kind()))?;
+        }
+        self.powered = false;
+        Ok(())
+    }
+
+    /// Switches the power rail on and waits for the panel to settle, unless it is already on
+    async fn power_up(&mut self, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        match &mut self.power {
+            Some(power) if !self.powered => {
+                power.set_high().map_err(|e| Error::Pin(e.kind()))?;
+                delay.delay_us(self.power_on_delay_us).await;
+                self.powered = true;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
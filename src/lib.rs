@@ -2,9 +2,11 @@
 //!
 //! - Built using [`embedded-hal`] traits.
 //! - Graphics support is added through [`embedded-graphics`]
+//! - Async support (`async` feature) is added through [`embedded-hal-async`]
 //!
 //! [`embedded-graphics`]: https://docs.rs/embedded-graphics/
 //! [`embedded-hal`]: https://docs.rs/embedded-hal
+//! [`embedded-hal-async`]: https://docs.rs/embedded-hal-async
 //!
 
 //!
@@ -12,7 +14,7 @@
 //!
 //!```rust, no_run
 //!# use embedded_hal_mock::eh1::*;
-//!# fn main() -> Result<(), embedded_hal::spi::ErrorKind> {
+//!# fn main() -> Result<(), epd_waveshare::Error<embedded_hal::spi::ErrorKind>> {
 //!use embedded_graphics::{
 //!    pixelcolor::BinaryColor::On as Black, prelude::*, primitives::{Line, PrimitiveStyle},
 //!};
@@ -56,25 +58,63 @@
 //!
 //! ### SPI
 //!
-//! MISO is not connected/available. SPI_MODE_0 is used (CPHL = 0, CPOL = 0) with 8 bits per word, MSB first.
+//! MISO is not connected/available, except for reading the temperature sensor (see below and
+//! `read_temperature` of the drivers having it). SPI_MODE_0 is used (CPHL = 0, CPOL = 0) with 8 bits per word, MSB first.
 //!
 //! Maximum speed tested by myself was 8Mhz but more should be possible (Ben Krasnow used 18Mhz with his implemenation)
 //!
+//! ### Reading from the controller
+//!
+//! The controllers answer on DIN, their only data line, which the drivers read with
+//! `SpiDevice::read`. That needs a 3-wire (half-duplex) SPI bus, or MISO wired to DIN and MOSI
+//! driving DIN through a series resistor of a few kΩ. Don't wire DIN to both MOSI and MISO
+//! without the resistor: the controller and MOSI would drive the line against each other.
+//!
 #![no_std]
 #![deny(missing_docs)]
 
+#[cfg(feature = "emulator")]
+extern crate std;
+
 #[cfg(feature = "graphics")]
 pub mod graphics;
 
+#[cfg(feature = "emulator")]
+pub mod emulator;
+
 mod traits;
 
+mod error;
+pub use error::Error;
+
+#[cfg(feature = "async")]
+mod traits_async;
+
 pub mod color;
 
 pub mod rect;
 
+pub mod ghosting;
+
+pub mod waveform;
+
+pub mod power;
+
+pub mod config;
+
+#[cfg(feature = "any_epd")]
+pub mod any;
+
 /// Interface for the physical connection between display and the controlling device
 mod interface;
 
+/// Validation of the windows of partial updates
+mod window;
+
+/// Async interface for the physical connection between display and the controlling device
+#[cfg(feature = "async")]
+mod interface_async;
+
 pub mod epd1in02;
 pub mod epd1in54;
 pub mod epd1in54_v2;
@@ -110,10 +150,15 @@ pub(crate) mod type_a;
 /// Includes everything important besides the chosen Display
 pub mod prelude {
     pub use crate::color::{Color, OctColor, TriColor};
+    pub use crate::config::EpdConfig;
     pub use crate::traits::{
-        QuickRefresh, RefreshLut, WaveshareDisplay, WaveshareThreeColorDisplay,
+        BusyPolling, Capabilities, ColorKind, DeepSleepMode, QuickRefresh, RefreshLut,
+        WaveshareDisplay, WaveshareDisplayNew, WaveshareThreeColorDisplay,
     };
 
+    #[cfg(feature = "async")]
+    pub use crate::traits_async::{WaveshareDisplayAsync, WaveshareDisplayAsyncNew};
+
     pub use crate::SPI_MODE;
 
     #[cfg(feature = "graphics")]
@@ -0,0 +1,457 @@
+//! Power states of a display checked at compile time
+//!
+//! Once asleep, the controllers ignore everything but a hardware reset, so frames sent to a
+//! sleeping display are silently lost. [`Awake`] wraps a driver and offers the drawing functions
+//! of [`WaveshareDisplay`], [`Awake::sleep`] consumes it and returns an [`Asleep`] display,
+//! which only offers [`Asleep::wake_up`]:
+//!
+//! ```rust, no_run
+//! # use embedded_hal_mock::eh1::*;
+//! # fn main() -> Result<(), epd_waveshare::Error<embedded_hal::spi::ErrorKind>> {
+//! use epd_waveshare::{epd2in13_v2::*, power::Awake, prelude::*};
+//! # let mut spi = spi::Mock::new(&[]);
+//! # let pin = || digital::Mock::new(&[]);
+//! # let mut delay = delay::NoopDelay::new();
+//! # let (busy, dc, rst) = (pin(), pin(), pin());
+//! # let buffer = [0u8; 8 / 8 * 8];
+//! let epd = Awake::new(Epd2in13::new(&mut spi, busy, dc, rst, &mut delay, None)?);
+//!
+//! let epd = epd.sleep(&mut spi, &mut delay)?;
+//! // epd.update_frame(&mut spi, &buffer, &mut delay)?; doesn't compile
+//! let retained = epd.deep_sleep_mode().retains_ram();
+//!
+//! let mut epd = epd.wake_up(&mut spi, &mut delay)?;
+//! if retained {
+//!     epd.update_partial_frame(&mut spi, &mut delay, &buffer, 0, 0, 8, 8)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! With a switch on the power rail of the panel, whose pin is given to the driver with
+//! [`EpdConfig::power_pin`](crate::config::EpdConfig::power_pin), [`Awake::power_off`] returns a
+//! [`PoweredOff`] display and [`PoweredOff::power_on`] switches it on again.
+//!
+//! A transition that fails returns the driver in a [`TransitionError`], its state is unknown
+//! and waking it up again is the safest way back.
+
+use core::convert::Infallible;
+use core::fmt;
+
+use crate::error::Error;
+use crate::traits::{Capabilities, DeepSleepMode, RefreshLut, WaveshareDisplay};
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{ErrorType, InputPin, OutputPin},
+    spi::SpiDevice,
+};
+
+/// Power pin of a driver without a switch on the power rail of its panel
+///
+/// The default of the `PWR` parameter of the drivers and of [`EpdConfig`](crate::config::EpdConfig),
+/// setting it does nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NoPowerPin;
+
+impl ErrorType for NoPowerPin {
+    type Error = Infallible;
+}
+
+impl OutputPin for NoPowerPin {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+/// Driver of a display that is awake, see the [module documentation](self)
+pub struct Awake<EPD> {
+    epd: EPD,
+}
+
+/// Driver of a display in deep sleep, see the [module documentation](self)
+pub struct Asleep<EPD> {
+    epd: EPD,
+    mode: DeepSleepMode,
+}
+
+/// Driver of a display switched off with its power pin, see the [module documentation](self)
+pub struct PoweredOff<EPD> {
+    epd: EPD,
+}
+
+/// Error of a power state transition, along with the driver
+pub struct TransitionError<EPD, SpiE> {
+    /// The driver, in an unknown state
+    pub epd: EPD,
+    /// What went wrong
+    pub error: Error<SpiE>,
+}
+
+// the drivers don't implement Debug
+impl<EPD, SpiE: fmt::Debug> fmt::Debug for TransitionError<EPD, SpiE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransitionError")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<EPD, SpiE> From<TransitionError<EPD, SpiE>> for Error<SpiE> {
+    fn from(error: TransitionError<EPD, SpiE>) -> Self {
+        error.error
+    }
+}
+
+impl<EPD> Awake<EPD> {
+    /// Wraps a driver that is awake, e.g. right after creating it
+    pub fn new(epd: EPD) -> Self {
+        Awake { epd }
+    }
+
+    /// The wrapped driver
+    pub fn epd(&self) -> &EPD {
+        &self.epd
+    }
+
+    /// The wrapped driver, to use the functions specific to it
+    ///
+    /// Putting the display to sleep through it defeats the purpose of the wrapper.
+    pub fn epd_mut(&mut self) -> &mut EPD {
+        &mut self.epd
+    }
+
+    /// Returns the wrapped driver
+    pub fn into_inner(self) -> EPD {
+        self.epd
+    }
+
+    /// Puts the display to sleep, see [`WaveshareDisplay::sleep`]
+    pub fn sleep<SPI, BUSY, DC, RST, DELAY>(
+        mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<Asleep<EPD>, TransitionError<EPD, SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        let mode = self.epd.deep_sleep_mode();
+        match self.epd.sleep(spi, delay) {
+            Ok(()) => Ok(Asleep {
+                epd: self.epd,
+                mode,
+            }),
+            Err(error) => Err(TransitionError {
+                epd: self.epd,
+                error,
+            }),
+        }
+    }
+
+    /// Switches the display off, see [`WaveshareDisplay::power_off`]
+    pub fn power_off<SPI, BUSY, DC, RST, DELAY>(
+        mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<PoweredOff<EPD>, TransitionError<EPD, SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        match self.epd.power_off(spi, delay) {
+            Ok(()) => Ok(PoweredOff { epd: self.epd }),
+            Err(error) => Err(TransitionError {
+                epd: self.epd,
+                error,
+            }),
+        }
+    }
+
+    /// See [`WaveshareDisplay::capabilities`]
+    pub fn capabilities<SPI, BUSY, DC, RST, DELAY>(&self) -> Capabilities
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        self.epd.capabilities()
+    }
+
+    /// See [`WaveshareDisplay::width`]
+    pub fn width<SPI, BUSY, DC, RST, DELAY>(&self) -> u32
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        self.epd.width()
+    }
+
+    /// See [`WaveshareDisplay::height`]
+    pub fn height<SPI, BUSY, DC, RST, DELAY>(&self) -> u32
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        self.epd.height()
+    }
+
+    /// See [`WaveshareDisplay::update_frame`]
+    pub fn update_frame<SPI, BUSY, DC, RST, DELAY>(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        self.epd.update_frame(spi, buffer, delay)
+    }
+
+    /// See [`WaveshareDisplay::update_partial_frame`]
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_partial_frame<SPI, BUSY, DC, RST, DELAY>(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        self.epd
+            .update_partial_frame(spi, delay, buffer, x, y, width, height)
+    }
+
+    /// See [`WaveshareDisplay::display_frame`]
+    pub fn display_frame<SPI, BUSY, DC, RST, DELAY>(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        self.epd.display_frame(spi, delay)
+    }
+
+    /// See [`WaveshareDisplay::begin_display_frame`]
+    pub fn begin_display_frame<SPI, BUSY, DC, RST, DELAY>(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        self.epd.begin_display_frame(spi, delay)
+    }
+
+    /// See [`WaveshareDisplay::update_and_display_frame`]
+    pub fn update_and_display_frame<SPI, BUSY, DC, RST, DELAY>(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        self.epd.update_and_display_frame(spi, buffer, delay)
+    }
+
+    /// See [`WaveshareDisplay::clear_frame`]
+    pub fn clear_frame<SPI, BUSY, DC, RST, DELAY>(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        self.epd.clear_frame(spi, delay)
+    }
+
+    /// See [`WaveshareDisplay::set_lut`]
+    pub fn set_lut<SPI, BUSY, DC, RST, DELAY>(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        refresh_rate: Option<RefreshLut>,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        self.epd.set_lut(spi, delay, refresh_rate)
+    }
+
+    /// See [`WaveshareDisplay::wait_until_idle`]
+    pub fn wait_until_idle<SPI, BUSY, DC, RST, DELAY>(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        self.epd.wait_until_idle(spi, delay)
+    }
+
+    /// See [`WaveshareDisplay::is_busy`]
+    pub fn is_busy<SPI, BUSY, DC, RST, DELAY>(
+        &mut self,
+        spi: &mut SPI,
+    ) -> Result<bool, Error<SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        self.epd.is_busy(spi)
+    }
+}
+
+impl<EPD> Asleep<EPD> {
+    /// The wrapped driver
+    pub fn epd(&self) -> &EPD {
+        &self.epd
+    }
+
+    /// Returns the wrapped driver
+    pub fn into_inner(self) -> EPD {
+        self.epd
+    }
+
+    /// Mode the display sleeps in, whether the RAM still holds the last frame
+    pub fn deep_sleep_mode(&self) -> DeepSleepMode {
+        self.mode
+    }
+
+    /// Wakes the display up and initialises it again, see [`WaveshareDisplay::wake_up`]
+    pub fn wake_up<SPI, BUSY, DC, RST, DELAY>(
+        mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<Awake<EPD>, TransitionError<EPD, SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        match self.epd.wake_up(spi, delay) {
+            Ok(()) => Ok(Awake { epd: self.epd }),
+            Err(error) => Err(TransitionError {
+                epd: self.epd,
+                error,
+            }),
+        }
+    }
+}
+
+impl<EPD> PoweredOff<EPD> {
+    /// The wrapped driver
+    pub fn epd(&self) -> &EPD {
+        &self.epd
+    }
+
+    /// Returns the wrapped driver
+    pub fn into_inner(self) -> EPD {
+        self.epd
+    }
+
+    /// Switches the display on and initialises it again, see [`WaveshareDisplay::power_on`]
+    pub fn power_on<SPI, BUSY, DC, RST, DELAY>(
+        mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<Awake<EPD>, TransitionError<EPD, SPI::Error>>
+    where
+        SPI: SpiDevice,
+        BUSY: InputPin,
+        DC: OutputPin,
+        RST: OutputPin,
+        DELAY: DelayNs,
+        EPD: WaveshareDisplay<SPI, BUSY, DC, RST, DELAY>,
+    {
+        match self.epd.power_on(spi, delay) {
+            Ok(()) => Ok(Awake { epd: self.epd }),
+            Err(error) => Err(TransitionError {
+                epd: self.epd,
+                error,
+            }),
+        }
+    }
+}
@@ -27,6 +27,14 @@ impl Rect {
         let h = cmp::min(self.y + self.h, other.y + other.h).saturating_sub(y);
         Rect { x, y, w, h }
     }
+    /// Compute the smallest rectangle containing both rectangles
+    pub fn union(&self, other: Rect) -> Rect {
+        let x = cmp::min(self.x, other.x);
+        let y = cmp::min(self.y, other.y);
+        let w = cmp::max(self.x + self.w, other.x + other.w) - x;
+        let h = cmp::max(self.y + self.h, other.y + other.h) - y;
+        Rect { x, y, w, h }
+    }
     /// Move rectangle by (-dx,-dy)
     pub fn sub_offset(&self, dx: u32, dy: u32) -> Rect {
         Rect {
@@ -71,6 +79,13 @@ fn test_intersect() {
     ));
 }
 
+#[test]
+fn test_union() {
+    let r1 = Rect::new(0, 2, 10, 10);
+    let r2 = Rect::new(6, 0, 10, 4);
+    assert_eq!(r1.union(r2), Rect::new(0, 0, 16, 12));
+}
+
 #[test]
 fn sub_offset() {
     let r1 = Rect::new(10, 10, 10, 10);
@@ -1,4 +1,6 @@
-use core::marker::Sized;
+use crate::config::EpdConfig;
+use crate::error::Error;
+use crate::power::NoPowerPin;
 use embedded_hal::{delay::*, digital::*, spi::SpiDevice};
 
 /// All commands need to have this trait which gives the address of the command
@@ -21,6 +23,92 @@ pub enum RefreshLut {
     PartialRefresh,
 }
 
+/// Colors a driver draws, see [`WaveshareDisplay::DisplayColor`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorKind {
+    /// Black and white, [`Color`](crate::color::Color)
+    BlackWhite,
+    /// Black, white and red or yellow, [`TriColor`](crate::color::TriColor)
+    TriColor,
+    /// Up to 7 colors, [`OctColor`](crate::color::OctColor)
+    Octal,
+}
+
+/// How a driver finds out that the display is idle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusyPolling {
+    /// Reads the busy pin
+    Pin,
+    /// Sends a status command before every read of the busy pin, a busy wait therefore needs
+    /// the spi bus
+    Command,
+}
+
+/// Deep sleep modes of the controllers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeepSleepMode {
+    /// Sleeps and keeps access to RAM and controller
+    Normal,
+    /// Sleeps without access to RAM/controller but keeps RAM content
+    Mode1,
+    /// Same as [`Mode1`](DeepSleepMode::Mode1) but RAM content is not kept
+    Mode2,
+}
+
+impl DeepSleepMode {
+    /// Whether the RAM still holds the frame after waking up
+    pub fn retains_ram(self) -> bool {
+        self != DeepSleepMode::Mode2
+    }
+
+    /// Parameter of the deep sleep command of the SSD16xx controllers
+    pub(crate) fn ssd16xx_value(self) -> u8 {
+        match self {
+            DeepSleepMode::Normal => 0x00,
+            DeepSleepMode::Mode1 => 0x01,
+            DeepSleepMode::Mode2 => 0x03,
+        }
+    }
+}
+
+/// What a driver supports
+///
+/// Lets generic code choose a refresh strategy without knowing the display, see
+/// [`WaveshareDisplay::CAPABILITIES`].
+///
+///```rust
+///use epd_waveshare::{epd2in9::Epd2in9, prelude::*};
+///# use embedded_hal_mock::eh1::*;
+///# type Epd = Epd2in9<spi::Mock<u8>, digital::Mock, digital::Mock, digital::Mock, delay::NoopDelay>;
+///
+///let capabilities = Epd::CAPABILITIES;
+///assert!(capabilities.partial_update);
+///assert!(capabilities.supports_lut(RefreshLut::Quick));
+///assert_eq!(capabilities.color, ColorKind::BlackWhite);
+///```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Colors of the frame buffer
+    pub color: ColorKind,
+    /// [`update_partial_frame`](WaveshareDisplay::update_partial_frame) writes a window instead
+    /// of returning [`Error::Unsupported`]
+    pub partial_update: bool,
+    /// [`RefreshLut`]s changing the refresh, [`set_lut`](WaveshareDisplay::set_lut) ignores
+    /// or refuses the others
+    pub refresh_luts: &'static [RefreshLut],
+    /// The driver implements [`QuickRefresh`]
+    pub quick_refresh: bool,
+    /// How the driver waits for the display
+    pub busy: BusyPolling,
+}
+
+impl Capabilities {
+    /// Whether [`set_lut`](WaveshareDisplay::set_lut) selects `lut`
+    pub fn supports_lut(&self, lut: RefreshLut) -> bool {
+        self.refresh_luts.contains(&lut)
+    }
+}
+
 pub(crate) trait InternalWiAdditions<SPI, BUSY, DC, RST, DELAY>
 where
     SPI: SpiDevice,
@@ -32,14 +120,14 @@ where
     /// This initialises the EPD and powers it up
     ///
     /// This function is already called from
-    ///  - [new()](WaveshareDisplay::new())
+    ///  - [new()](WaveshareDisplayNew::new())
     ///  - [`wake_up`]
     ///
     ///
     /// This function calls [reset](WaveshareDisplay::reset),
     /// so you don't need to call reset your self when trying to wake your device up
     /// after setting it to sleep.
-    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error>;
+    fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>>;
 }
 
 /// Functions to interact with three color panels
@@ -61,7 +149,7 @@ where
         delay: &mut DELAY,
         black: &[u8],
         chromatic: &[u8],
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Update only the black/white data of the display.
     ///
@@ -71,7 +159,7 @@ where
         spi: &mut SPI,
         delay: &mut DELAY,
         black: &[u8],
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Update only the chromatic data of the display.
     ///
@@ -82,7 +170,7 @@ where
         spi: &mut SPI,
         delay: &mut DELAY,
         chromatic: &[u8],
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 }
 
 /// All the functions to interact with the EPDs
@@ -93,7 +181,7 @@ where
 ///
 ///```rust, no_run
 ///# use embedded_hal_mock::eh1::*;
-///# fn main() -> Result<(), embedded_hal::spi::ErrorKind> {
+///# fn main() -> Result<(), epd_waveshare::Error<embedded_hal::spi::ErrorKind>> {
 ///use embedded_graphics::{
 ///    pixelcolor::BinaryColor::On as Black, prelude::*, primitives::{Line, PrimitiveStyle},
 ///};
@@ -157,33 +245,47 @@ where
 {
     /// The Color Type used by the Display
     type DisplayColor;
-    /// Creates a new driver from a SPI peripheral, CS Pin, Busy InputPin, DC
-    ///
-    /// `delay_us` is the number of us the idle loop should sleep on.
-    /// Setting it to 0 implies busy waiting.
-    /// Setting it to None means a default value is used.
-    ///
-    /// This already initialises the device.
-    fn new(
-        spi: &mut SPI,
-        busy: BUSY,
-        dc: DC,
-        rst: RST,
-        delay: &mut DELAY,
-        delay_us: Option<u32>,
-    ) -> Result<Self, SPI::Error>
-    where
-        Self: Sized;
+
+    /// What the driver supports
+    const CAPABILITIES: Capabilities;
 
     /// Let the device enter deep-sleep mode to save power.
     ///
     /// The deep sleep mode returns to standby with a hardware reset.
-    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error>;
+    fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>>;
 
     /// Wakes the device up from sleep
     ///
     /// Also reintialises the device if necessary.
-    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error>;
+    fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>>;
+
+    /// Puts the device to [`sleep`](WaveshareDisplay::sleep) and switches it off with the power
+    /// pin given to the driver, see [`EpdConfig::power_pin`]
+    ///
+    /// The dc and reset pins are driven low before, but CS, SCLK and MOSI are still driven by the
+    /// SPI peripheral and can power the panel through its protection diodes. Release the SPI
+    /// bus or drive these pins low as well before the panel is unpowered for long. The content of
+    /// its RAM is lost.
+    ///
+    /// Returns [`Error::Unsupported`], once the device is asleep, without a power pin.
+    fn power_off(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.sleep(spi, delay)?;
+        self.switch_power(delay, false)
+    }
+
+    /// Switches the device on with its power pin after
+    /// [`power_off`](WaveshareDisplay::power_off) and initialises it again
+    fn power_on(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>> {
+        self.switch_power(delay, true)?;
+        self.wake_up(spi, delay)
+    }
+
+    /// Only switches the power pin given to the driver, for
+    /// [`power_off`](WaveshareDisplay::power_off) and [`power_on`](WaveshareDisplay::power_on)
+    ///
+    /// Switching the power on waits for the panel to settle, see
+    /// [`EpdConfig::power_on_delay_us`]. Returns [`Error::Unsupported`] without a power pin.
+    fn switch_power(&mut self, delay: &mut DELAY, on: bool) -> Result<(), Error<SPI::Error>>;
 
     /// Sets the backgroundcolor for various commands like [clear_frame](WaveshareDisplay::clear_frame)
     fn set_background_color(&mut self, color: Self::DisplayColor);
@@ -191,6 +293,26 @@ where
     /// Get current background color
     fn background_color(&self) -> &Self::DisplayColor;
 
+    /// Sets the maximum number of us to wait for the display to be idle
+    ///
+    /// When the busy pin still signals busy after that time, [`Error::BusyTimeout`] is returned
+    /// instead of waiting forever (e.g. on a disconnected panel).
+    /// None (the default) waits forever.
+    fn set_busy_timeout(&mut self, timeout_us: Option<u32>);
+
+    /// What the driver supports, see [`CAPABILITIES`](WaveshareDisplay::CAPABILITIES)
+    fn capabilities(&self) -> Capabilities {
+        Self::CAPABILITIES
+    }
+
+    /// Deep sleep mode [`sleep`](WaveshareDisplay::sleep) enters
+    ///
+    /// Drivers that can't tell return [`DeepSleepMode::Mode2`], the frame has to be written
+    /// again after waking up.
+    fn deep_sleep_mode(&self) -> DeepSleepMode {
+        DeepSleepMode::Mode2
+    }
+
     /// Get the width of the display
     fn width(&self) -> u32;
 
@@ -203,13 +325,17 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Transmits partial data to the SRAM of the EPD
     ///
     /// (x,y) is the top left corner
     ///
     /// BUFFER needs to be of size: width / 8 * height !
+    ///
+    /// `x` and `width` must be multiples of 8, except for a `width` reaching the right edge of the
+    /// display. Windows that don't fit return `Error::InvalidWindow` and buffers of the wrong
+    /// size `Error::InvalidBuffer`.
     #[allow(clippy::too_many_arguments)]
     fn update_partial_frame(
         &mut self,
@@ -220,12 +346,23 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Displays the frame data from SRAM
     ///
     /// This function waits until the device isn`t busy anymore
-    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error>;
+    fn display_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>>;
+
+    /// Starts displaying the frame data from SRAM without waiting for the refresh to finish
+    ///
+    /// A refresh takes several seconds on most displays. Poll [`is_busy`](WaveshareDisplay::is_busy)
+    /// until it returns `false` (or call [`wait_until_idle`](WaveshareDisplay::wait_until_idle))
+    /// before sending anything else to the display.
+    fn begin_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Provide a combined update&display and save some time (skipping a busy check in between)
     fn update_and_display_frame(
@@ -233,12 +370,12 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Clears the frame buffer on the EPD with the declared background color
     ///
     /// The background color can be changed with [`WaveshareDisplay::set_background_color`]
-    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error>;
+    fn clear_frame(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>>;
 
     /// Trait for using various Waveforms from different LUTs
     /// E.g. for partial refreshes
@@ -253,12 +390,99 @@ where
         spi: &mut SPI,
         delay: &mut DELAY,
         refresh_rate: Option<RefreshLut>,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Wait until the display has stopped processing data
     ///
     /// You can call this to make sure a frame is displayed before goin further
-    fn wait_until_idle(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), SPI::Error>;
+    fn wait_until_idle(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>;
+
+    /// Checks once if the display is still processing data, without waiting
+    ///
+    /// This is the non-blocking counterpart of [`wait_until_idle`](WaveshareDisplay::wait_until_idle),
+    /// e.g. to poll for the end of a refresh started with
+    /// [`begin_display_frame`](WaveshareDisplay::begin_display_frame).
+    fn is_busy(&mut self, spi: &mut SPI) -> Result<bool, Error<SPI::Error>>;
+}
+
+/// Constructors of the drivers
+///
+/// They are apart from [`WaveshareDisplay`] so that generic code can take any driver without
+/// being able to build one, e.g. `AnyEpd`, which needs the model of the display as well.
+///
+/// `PWR` is the type of the pin switching the power rail of the panel, see
+/// [`EpdConfig::power_pin`], the drivers have a [`NoPowerPin`] by default.
+pub trait WaveshareDisplayNew<SPI, BUSY, DC, RST, DELAY, PWR = NoPowerPin>:
+    WaveshareDisplay<SPI, BUSY, DC, RST, DELAY> + Sized
+where
+    SPI: SpiDevice,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    /// Creates a new driver from a SPI peripheral, CS Pin, Busy InputPin, DC
+    ///
+    /// `delay_us` is the number of us the idle loop should sleep on.
+    /// Setting it to 0 implies busy waiting.
+    /// Setting it to None means a default value is used.
+    ///
+    /// This already initialises the device.
+    fn new(
+        spi: &mut SPI,
+        busy: BUSY,
+        dc: DC,
+        rst: RST,
+        delay: &mut DELAY,
+        delay_us: Option<u32>,
+    ) -> Result<Self, Error<SPI::Error>>
+    where
+        Self: WaveshareDisplayNew<SPI, BUSY, DC, RST, DELAY, NoPowerPin>,
+    {
+        let config = EpdConfig {
+            busy_poll_us: delay_us,
+            ..EpdConfig::new()
+        };
+        <Self as WaveshareDisplayNew<SPI, BUSY, DC, RST, DELAY, NoPowerPin>>::with_config(
+            spi, busy, dc, rst, delay, config,
+        )
+    }
+
+    /// Creates a new driver like [`new`](WaveshareDisplayNew::new), with the timings and settings
+    /// of `config`
+    ///
+    /// Returns [`Error::Unsupported`] for settings the driver can't apply.
+    fn with_config(
+        spi: &mut SPI,
+        busy: BUSY,
+        dc: DC,
+        rst: RST,
+        delay: &mut DELAY,
+        config: EpdConfig<PWR>,
+    ) -> Result<Self, Error<SPI::Error>>;
+
+    /// Creates a new driver like [`new`](WaveshareDisplayNew::new), switching the panel on with
+    /// its `power` pin first, see [`EpdConfig::power_pin`]
+    fn new_with_power(
+        spi: &mut SPI,
+        busy: BUSY,
+        dc: DC,
+        rst: RST,
+        power: PWR,
+        delay: &mut DELAY,
+        delay_us: Option<u32>,
+    ) -> Result<Self, Error<SPI::Error>> {
+        let config = EpdConfig {
+            busy_poll_us: delay_us,
+            ..EpdConfig::new()
+        };
+        Self::with_config(spi, busy, dc, rst, delay, config.power_pin(power))
+    }
 }
 
 /// Allows quick refresh support for displays that support it; lets you send both
@@ -272,7 +496,7 @@ where
 /// Example:
 ///```rust, no_run
 ///# use embedded_hal_mock::eh1::*;
-///# fn main() -> Result<(), embedded_hal::spi::ErrorKind> {
+///# fn main() -> Result<(), epd_waveshare::Error<embedded_hal::spi::ErrorKind>> {
 ///# use embedded_graphics::{
 ///#   pixelcolor::BinaryColor::On as Black, prelude::*, primitives::{Line, PrimitiveStyle},
 ///# };
@@ -320,7 +544,7 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Updates the new frame.
     fn update_new_frame(
@@ -328,10 +552,14 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Displays the new frame
-    fn display_new_frame(&mut self, spi: &mut SPI, _delay: &mut DELAY) -> Result<(), SPI::Error>;
+    fn display_new_frame(
+        &mut self,
+        spi: &mut SPI,
+        _delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Updates and displays the new frame.
     fn update_and_display_new_frame(
@@ -339,7 +567,7 @@ where
         spi: &mut SPI,
         buffer: &[u8],
         delay: &mut DELAY,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Updates the old frame for a portion of the display.
     #[allow(clippy::too_many_arguments)]
@@ -352,7 +580,7 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Updates the new frame for a portion of the display.
     #[allow(clippy::too_many_arguments)]
@@ -365,7 +593,7 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 
     /// Clears the partial frame buffer on the EPD with the declared background color
     /// The background color can be changed with [`WaveshareDisplay::set_background_color`]
@@ -377,5 +605,5 @@ where
         y: u32,
         width: u32,
         height: u32,
-    ) -> Result<(), SPI::Error>;
+    ) -> Result<(), Error<SPI::Error>>;
 }
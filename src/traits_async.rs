@@ -0,0 +1,288 @@
+use crate::config::EpdConfig;
+use crate::error::Error;
+use crate::power::NoPowerPin;
+use crate::traits::{Capabilities, DeepSleepMode, RefreshLut};
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal_async::{delay::DelayNs, digital::Wait, spi::SpiDevice};
+
+/// Async counterpart of [`InternalWiAdditions`](crate::traits::InternalWiAdditions)
+pub(crate) trait InternalWiAdditionsAsync<SPI, BUSY, DC, RST, DELAY>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin + Wait,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+{
+    /// This initialises the EPD and powers it up
+    ///
+    /// This function is already called from
+    ///  - [new()](WaveshareDisplayAsyncNew::new())
+    ///  - [`wake_up`](WaveshareDisplayAsync::wake_up())
+    async fn init(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>>;
+}
+
+/// All the functions to interact with the EPDs, built on `embedded-hal-async`
+///
+/// This is the async counterpart of [`WaveshareDisplay`](crate::traits::WaveshareDisplay):
+/// spi transfers and delays are awaited, and the busy pin is awaited on an edge
+/// (see [`Wait`]) instead of being polled, so long refreshes don't block the executor.
+///
+/// Only available with the `async` feature.
+///
+/// # Example
+///
+///```rust, ignore
+///use epd_waveshare::{epd2in9_v2::*, prelude::*};
+///
+///// Setup EPD
+///let mut epd = Epd2in9Async::new(&mut spi, busy_in, dc, rst, &mut delay, None).await?;
+///
+///// Use display graphics from embedded-graphics
+///let mut display = Display2in9::default();
+///
+///// Display updated frame
+///epd.update_and_display_frame(&mut spi, display.buffer(), &mut delay).await?;
+///
+///// Set the EPD to sleep
+///epd.sleep(&mut spi, &mut delay).await?;
+///```
+#[allow(async_fn_in_trait)]
+pub trait WaveshareDisplayAsync<SPI, BUSY, DC, RST, DELAY>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin + Wait,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+{
+    /// The Color Type used by the Display
+    type DisplayColor;
+
+    /// What the driver supports
+    const CAPABILITIES: Capabilities;
+
+    /// Let the device enter deep-sleep mode to save power.
+    ///
+    /// The deep sleep mode returns to standby with a hardware reset.
+    async fn sleep(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>>;
+
+    /// Wakes the device up from sleep
+    ///
+    /// Also reintialises the device if necessary.
+    async fn wake_up(&mut self, spi: &mut SPI, delay: &mut DELAY) -> Result<(), Error<SPI::Error>>;
+
+    /// Puts the device to sleep and switches it off with its power pin, see
+    /// [`WaveshareDisplay::power_off`](crate::traits::WaveshareDisplay::power_off)
+    async fn power_off(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.sleep(spi, delay).await?;
+        self.switch_power(delay, false).await
+    }
+
+    /// Switches the device on with its power pin and initialises it again, see
+    /// [`WaveshareDisplay::power_on`](crate::traits::WaveshareDisplay::power_on)
+    async fn power_on(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.switch_power(delay, true).await?;
+        self.wake_up(spi, delay).await
+    }
+
+    /// Only switches the power pin given to the driver, see
+    /// [`WaveshareDisplay::switch_power`](crate::traits::WaveshareDisplay::switch_power)
+    async fn switch_power(&mut self, delay: &mut DELAY, on: bool) -> Result<(), Error<SPI::Error>>;
+
+    /// Sets the backgroundcolor for various commands like [clear_frame](WaveshareDisplayAsync::clear_frame)
+    fn set_background_color(&mut self, color: Self::DisplayColor);
+
+    /// Get current background color
+    fn background_color(&self) -> &Self::DisplayColor;
+
+    /// Sets the maximum number of us to wait for the display to be idle
+    ///
+    /// See [`WaveshareDisplay::set_busy_timeout`](crate::traits::WaveshareDisplay::set_busy_timeout)
+    fn set_busy_timeout(&mut self, timeout_us: Option<u32>);
+
+    /// What the driver supports, see [`CAPABILITIES`](WaveshareDisplayAsync::CAPABILITIES)
+    fn capabilities(&self) -> Capabilities {
+        Self::CAPABILITIES
+    }
+
+    /// Deep sleep mode [`sleep`](WaveshareDisplayAsync::sleep) enters, see
+    /// [`WaveshareDisplay::deep_sleep_mode`](crate::traits::WaveshareDisplay::deep_sleep_mode)
+    fn deep_sleep_mode(&self) -> DeepSleepMode {
+        DeepSleepMode::Mode2
+    }
+
+    /// Get the width of the display
+    fn width(&self) -> u32;
+
+    /// Get the height of the display
+    fn height(&self) -> u32;
+
+    /// Transmit a full frame to the SRAM of the EPD
+    async fn update_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>;
+
+    /// Transmits partial data to the SRAM of the EPD
+    ///
+    /// (x,y) is the top left corner
+    ///
+    /// BUFFER needs to be of size: width / 8 * height !
+    ///
+    /// `x` and `width` must be multiples of 8, except for a `width` reaching the right edge of the
+    /// display. Windows that don't fit return `Error::InvalidWindow` and buffers of the wrong
+    /// size `Error::InvalidBuffer`.
+    #[allow(clippy::too_many_arguments)]
+    async fn update_partial_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        buffer: &[u8],
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Error<SPI::Error>>;
+
+    /// Displays the frame data from SRAM
+    ///
+    /// This function waits until the device isn`t busy anymore
+    async fn display_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>;
+
+    /// Starts displaying the frame data from SRAM without waiting for the refresh to finish
+    ///
+    /// See [`WaveshareDisplay::begin_display_frame`](crate::traits::WaveshareDisplay::begin_display_frame)
+    async fn begin_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>;
+
+    /// Provide a combined update&display and save some time (skipping a busy check in between)
+    async fn update_and_display_frame(
+        &mut self,
+        spi: &mut SPI,
+        buffer: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>;
+
+    /// Clears the frame buffer on the EPD with the declared background color
+    ///
+    /// The background color can be changed with [`WaveshareDisplayAsync::set_background_color`]
+    async fn clear_frame(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>;
+
+    /// Trait for using various Waveforms from different LUTs
+    /// E.g. for partial refreshes
+    ///
+    /// See [`WaveshareDisplay::set_lut`](crate::traits::WaveshareDisplay::set_lut)
+    async fn set_lut(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+        refresh_rate: Option<RefreshLut>,
+    ) -> Result<(), Error<SPI::Error>>;
+
+    /// Wait until the display has stopped processing data
+    ///
+    /// You can call this to make sure a frame is displayed before goin further
+    async fn wait_until_idle(
+        &mut self,
+        spi: &mut SPI,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error>>;
+
+    /// Checks once if the display is still processing data, without waiting
+    ///
+    /// See [`WaveshareDisplay::is_busy`](crate::traits::WaveshareDisplay::is_busy)
+    async fn is_busy(&mut self, spi: &mut SPI) -> Result<bool, Error<SPI::Error>>;
+}
+
+/// Constructors of the async drivers, see
+/// [`WaveshareDisplayNew`](crate::traits::WaveshareDisplayNew)
+#[allow(async_fn_in_trait)]
+pub trait WaveshareDisplayAsyncNew<SPI, BUSY, DC, RST, DELAY, PWR = NoPowerPin>:
+    WaveshareDisplayAsync<SPI, BUSY, DC, RST, DELAY> + Sized
+where
+    SPI: SpiDevice,
+    BUSY: InputPin + Wait,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    /// Creates a new driver from a SPI peripheral, CS Pin, Busy InputPin, DC
+    ///
+    /// `delay_us` is the number of us to sleep between two status probes, for
+    /// the devices that need a command to update the busy pin.
+    /// Setting it to None means a default value is used.
+    ///
+    /// This already initialises the device.
+    async fn new(
+        spi: &mut SPI,
+        busy: BUSY,
+        dc: DC,
+        rst: RST,
+        delay: &mut DELAY,
+        delay_us: Option<u32>,
+    ) -> Result<Self, Error<SPI::Error>>
+    where
+        Self: WaveshareDisplayAsyncNew<SPI, BUSY, DC, RST, DELAY, NoPowerPin>,
+    {
+        let config = EpdConfig {
+            busy_poll_us: delay_us,
+            ..EpdConfig::new()
+        };
+        <Self as WaveshareDisplayAsyncNew<SPI, BUSY, DC, RST, DELAY, NoPowerPin>>::with_config(
+            spi, busy, dc, rst, delay, config,
+        )
+        .await
+    }
+
+    /// Creates a new driver with the timings and settings of `config`, see
+    /// [`WaveshareDisplayNew::with_config`](crate::traits::WaveshareDisplayNew::with_config)
+    async fn with_config(
+        spi: &mut SPI,
+        busy: BUSY,
+        dc: DC,
+        rst: RST,
+        delay: &mut DELAY,
+        config: EpdConfig<PWR>,
+    ) -> Result<Self, Error<SPI::Error>>;
+
+    /// Creates a new driver, switching the panel on with its `power` pin first, see
+    /// [`WaveshareDisplayNew::new_with_power`](crate::traits::WaveshareDisplayNew::new_with_power)
+    async fn new_with_power(
+        spi: &mut SPI,
+        busy: BUSY,
+        dc: DC,
+        rst: RST,
+        power: PWR,
+        delay: &mut DELAY,
+        delay_us: Option<u32>,
+    ) -> Result<Self, Error<SPI::Error>> {
+        let config = EpdConfig {
+            busy_poll_us: delay_us,
+            ..EpdConfig::new()
+        };
+        Self::with_config(spi, busy, dc, rst, delay, config.power_pin(power)).await
+    }
+}
@@ -43,7 +43,9 @@ pub(crate) enum Command {
 
     TemperatureSensorSelection = 0x18,
 
-    TemperatureSensorControl = 0x1A,
+    TemperatureSensorWrite = 0x1A,
+
+    TemperatureSensorRead = 0x1B,
 
     MasterActivation = 0x20,
 
@@ -1,2 +1,121 @@
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{InputPin, OutputPin},
+    spi::SpiDevice,
+};
+
+use crate::color::Color;
+use crate::error::Error;
+use crate::interface::DisplayInterface;
+use crate::waveform::Ssd16xxWaveform;
+
+use self::command::Command;
+
 pub(crate) mod command;
 pub(crate) mod constants;
+
+/// Border bits of [`Command::BorderWaveformControl`] on the SSD1608/IL3820: the border follows
+/// the LUT of black (GS0 to GS0) or white (GS1 to GS1) pixels
+pub(crate) fn il3820_border_bits(color: Color) -> u8 {
+    match color {
+        Color::Black => 0x00,
+        Color::White => 0x03,
+    }
+}
+
+/// Border bits of [`Command::BorderWaveformControl`] on the SSD1680/SSD1681: the border follows
+/// the LUT 0 of black or the LUT 1 of white pixels
+pub(crate) fn ssd1680_border_bits(color: Color) -> u8 {
+    match color {
+        Color::Black => 0x04,
+        Color::White => 0x05,
+    }
+}
+
+/// Writes a custom waveform
+///
+/// `lut_len` is the size of the LUT register, `voltages` whether the controller takes
+/// voltages along the LUT.
+pub(crate) fn write_waveform<SPI, BUSY, DC, RST, DELAY, PWR, const SINGLE_BYTE_WRITE: bool>(
+    interface: &mut DisplayInterface<SPI, BUSY, DC, RST, DELAY, PWR, SINGLE_BYTE_WRITE>,
+    spi: &mut SPI,
+    waveform: &Ssd16xxWaveform<'_>,
+    lut_len: usize,
+    voltages: bool,
+) -> Result<(), Error<SPI::Error>>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    if waveform.lut.len() != lut_len || (waveform.voltages.is_some() && !voltages) {
+        return Err(Error::InvalidWaveform);
+    }
+    interface.cmd_with_data(spi, Command::WriteLutRegister, waveform.lut)?;
+    if let Some(voltages) = waveform.voltages {
+        interface.cmd_with_data(spi, Command::WriteLutRegisterEnd, &[voltages.end_option])?;
+        interface.cmd_with_data(spi, Command::GateDrivingVoltage, &[voltages.gate])?;
+        interface.cmd_with_data(spi, Command::SourceDrivingVoltage, &voltages.source)?;
+        interface.cmd_with_data(spi, Command::WriteVcomRegister, &[voltages.vcom])?;
+    }
+    Ok(())
+}
+
+/// Makes the SSD1680/SSD1681 choose its waveforms for `celsius`, e.g. measured by an external
+/// sensor, or for the reading of its internal sensor with `None`
+pub(crate) fn write_temperature<SPI, BUSY, DC, RST, DELAY, PWR, const SINGLE_BYTE_WRITE: bool>(
+    interface: &mut DisplayInterface<SPI, BUSY, DC, RST, DELAY, PWR, SINGLE_BYTE_WRITE>,
+    spi: &mut SPI,
+    celsius: Option<i8>,
+) -> Result<(), Error<SPI::Error>>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    match celsius {
+        Some(celsius) => {
+            // with the external sensor selected the register keeps the written value
+            interface.cmd_with_data(spi, Command::TemperatureSensorSelection, &[0x48])?;
+            // 12 bits, whole °C in the first byte
+            interface.cmd_with_data(spi, Command::TemperatureSensorWrite, &[celsius as u8, 0x00])
+        }
+        None => interface.cmd_with_data(spi, Command::TemperatureSensorSelection, &[0x80]),
+    }
+}
+
+/// Measures the temperature with the internal sensor of the SSD1680/SSD1681 and reads it back,
+/// in whole °C
+///
+/// See [`DisplayInterface::read`] for the wiring the read needs.
+pub(crate) fn read_temperature<SPI, BUSY, DC, RST, DELAY, PWR, const SINGLE_BYTE_WRITE: bool>(
+    interface: &mut DisplayInterface<SPI, BUSY, DC, RST, DELAY, PWR, SINGLE_BYTE_WRITE>,
+    spi: &mut SPI,
+    delay: &mut DELAY,
+) -> Result<i8, Error<SPI::Error>>
+where
+    SPI: SpiDevice,
+    BUSY: InputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DELAY: DelayNs,
+    PWR: OutputPin,
+{
+    interface.wait_until_idle(delay, false)?;
+    interface.cmd_with_data(spi, Command::TemperatureSensorSelection, &[0x80])?;
+    // enable the clock, load the temperature and disable the clock again
+    interface.cmd_with_data(spi, Command::DisplayUpdateControl2, &[0xA1])?;
+    interface.cmd(spi, Command::MasterActivation)?;
+    interface.wait_until_idle(delay, false)?;
+    interface.cmd(spi, Command::TemperatureSensorRead)?;
+    // integer part first, then the fraction in the upper nibble
+    let mut data = [0; 2];
+    interface.read(spi, &mut data)?;
+    Ok(data[0] as i8)
+}
@@ -0,0 +1,183 @@
+//! Custom waveforms for the drivers with a LUT register
+//!
+//! The built-in waveforms are chosen through [`RefreshLut`](crate::prelude::RefreshLut), these
+//! types describe others, e.g. tuned for cold environments. They are written by the
+//! `set_custom_lut` function of the drivers and stay until the next
+//! [`set_lut`](crate::prelude::WaveshareDisplay::set_lut), wake up or refresh selecting a
+//! built-in one.
+//!
+//! - [`Ssd16xxWaveform`]: 1.54", 1.54" V2, 2.13" V2, 2.7" V2, 2.9", 2.9" V2 and 3.7"
+//! - [`Uc81xxWaveform`]: 2.7", 2.7" (B), 2.9" (D) and 4.2"
+//! - [`Uc8175Waveform`]: 1.02" only
+//!
+//! Waveforms tuned for different temperatures are grouped into [`TemperatureBands`], the
+//! SSD16xx drivers pick the matching one with `set_lut_for_temperature`.
+
+/// Voltages the SSD1680/SSD1681 controllers take along their waveform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ssd16xxVoltages {
+    /// End option (EOPT, command 0x3F)
+    pub end_option: u8,
+    /// Gate voltage (command 0x03)
+    pub gate: u8,
+    /// VSH1, VSH2 and VSL source voltages (command 0x04)
+    pub source: [u8; 3],
+    /// VCOM voltage (command 0x2C)
+    pub vcom: u8,
+}
+
+/// Waveform of the SSD16xx controllers
+///
+/// The length of the LUT depends on the controller: 30 bytes for the 1.54" and 2.9", 70 bytes
+/// for the 2.13" V2, 105 bytes for the 3.7" and 153 bytes for the 1.54" V2, 2.7" V2 and 2.9" V2.
+/// Only the latter take [`Ssd16xxVoltages`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ssd16xxWaveform<'a> {
+    /// Content of the LUT register (command 0x32)
+    pub lut: &'a [u8],
+    /// Voltages set after the LUT, the current ones are kept when `None`
+    pub voltages: Option<Ssd16xxVoltages>,
+}
+
+impl<'a> Ssd16xxWaveform<'a> {
+    /// Splits a table in the layout of the Waveshare demos: 153 bytes of LUT followed by the
+    /// end option, gate, source and VCOM voltages
+    pub fn from_table(table: &'a [u8; 159]) -> Self {
+        Ssd16xxWaveform {
+            lut: &table[..153],
+            voltages: Some(Ssd16xxVoltages {
+                end_option: table[153],
+                gate: table[154],
+                source: [table[155], table[156], table[157]],
+                vcom: table[158],
+            }),
+        }
+    }
+}
+
+/// Waveform of the UC81xx controllers
+///
+/// Each LUT is written to its own register, see the datasheet of the controller for the
+/// meaning of the bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Uc81xxWaveform {
+    /// VCOM LUT (command 0x20)
+    pub vcom: [u8; 44],
+    /// White to white LUT (command 0x21)
+    pub ww: [u8; 42],
+    /// Black to white LUT (command 0x22)
+    pub bw: [u8; 42],
+    /// White to black LUT (command 0x23)
+    pub wb: [u8; 42],
+    /// Black to black LUT (command 0x24)
+    pub bb: [u8; 42],
+}
+
+/// Waveform of the UC8175 controller
+///
+/// It only has a LUT for the pixels turning white and one for those turning black.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Uc8175Waveform {
+    /// White LUT (command 0x23)
+    pub white: [u8; 42],
+    /// Black LUT (command 0x24)
+    pub black: [u8; 42],
+}
+
+/// Waveform used up to a temperature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemperatureBand<W> {
+    /// Highest temperature of the band, in °C
+    pub up_to: i8,
+    /// Waveform used in the band
+    pub waveform: W,
+}
+
+/// Waveforms for consecutive temperature ranges
+///
+/// The bands are sorted by ascending [`up_to`](TemperatureBand::up_to), the last one is also
+/// used above its temperature.
+///
+/// ```rust
+/// use epd_waveshare::waveform::{Ssd16xxWaveform, TemperatureBand, TemperatureBands};
+///
+/// const COLD: [u8; 30] = [0x22; 30];
+/// const WARM: [u8; 30] = [0x11; 30];
+/// const BANDS: TemperatureBands<'static, Ssd16xxWaveform<'static>> = TemperatureBands::new(&[
+///     TemperatureBand {
+///         up_to: 5,
+///         waveform: Ssd16xxWaveform { lut: &COLD, voltages: None },
+///     },
+///     TemperatureBand {
+///         up_to: 50,
+///         waveform: Ssd16xxWaveform { lut: &WARM, voltages: None },
+///     },
+/// ]);
+///
+/// assert_eq!(BANDS.select(-10).unwrap().lut, &COLD);
+/// assert_eq!(BANDS.select(20).unwrap().lut, &WARM);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemperatureBands<'a, W> {
+    bands: &'a [TemperatureBand<W>],
+}
+
+impl<'a, W> TemperatureBands<'a, W> {
+    /// Groups bands sorted by ascending temperature
+    pub const fn new(bands: &'a [TemperatureBand<W>]) -> Self {
+        TemperatureBands { bands }
+    }
+
+    /// Waveform of the first band reaching `celsius`, `None` if there are no bands
+    pub fn select(&self, celsius: i8) -> Option<&'a W> {
+        self.bands
+            .iter()
+            .find(|band| celsius <= band.up_to)
+            .or_else(|| self.bands.last())
+            .map(|band| &band.waveform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_table() {
+        let mut table = [0u8; 159];
+        table[152] = 1;
+        table[153..].copy_from_slice(&[0x22, 0x17, 0x41, 0xA8, 0x32, 0x50]);
+        let waveform = Ssd16xxWaveform::from_table(&table);
+        assert_eq!(waveform.lut.len(), 153);
+        assert_eq!(waveform.lut[152], 1);
+        assert_eq!(
+            waveform.voltages,
+            Some(Ssd16xxVoltages {
+                end_option: 0x22,
+                gate: 0x17,
+                source: [0x41, 0xA8, 0x32],
+                vcom: 0x50,
+            })
+        );
+    }
+
+    #[test]
+    fn select_band() {
+        let bands = [
+            TemperatureBand {
+                up_to: 0,
+                waveform: 'a',
+            },
+            TemperatureBand {
+                up_to: 25,
+                waveform: 'b',
+            },
+        ];
+        let bands = TemperatureBands::new(&bands);
+        assert_eq!(bands.select(-40), Some(&'a'));
+        assert_eq!(bands.select(0), Some(&'a'));
+        assert_eq!(bands.select(1), Some(&'b'));
+        assert_eq!(bands.select(60), Some(&'b'));
+        assert_eq!(TemperatureBands::<char>::new(&[]).select(20), None);
+    }
+}
@@ -0,0 +1,73 @@
+use crate::buffer_len;
+use crate::error::Error;
+
+/// Checks that a partial window lies within a `display_width` x `display_height` display
+///
+/// The controllers address their RAM by bytes horizontally: `x` must be a multiple of 8, and so
+/// must `width` unless the window reaches the right edge of the display.
+pub(crate) fn check_window<SpiE>(
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    display_width: u32,
+    display_height: u32,
+) -> Result<(), Error<SpiE>> {
+    let fits = |start: u32, len: u32, max: u32| {
+        len > 0 && start.checked_add(len).map_or(false, |end| end <= max)
+    };
+    if !fits(x, width, display_width) || !fits(y, height, display_height) {
+        return Err(Error::InvalidWindow);
+    }
+    if x % 8 != 0 || (width % 8 != 0 && x + width != display_width) {
+        return Err(Error::InvalidWindow);
+    }
+    Ok(())
+}
+
+/// Checks that `buffer` holds `planes` planes of a `width` x `height` window, with every line
+/// padded to a whole byte
+pub(crate) fn check_window_buffer<SpiE>(
+    buffer: &[u8],
+    width: u32,
+    height: u32,
+    planes: usize,
+) -> Result<(), Error<SpiE>> {
+    if buffer.len() != planes * buffer_len(width as usize, height as usize) {
+        return Err(Error::InvalidBuffer);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window() {
+        assert_eq!(check_window::<()>(8, 0, 16, 4, 122, 250), Ok(()));
+        // the last byte of a line may be partially used
+        assert_eq!(check_window::<()>(112, 0, 10, 4, 122, 250), Ok(()));
+        assert_eq!(
+            check_window::<()>(112, 0, 9, 4, 122, 250),
+            Err(Error::InvalidWindow)
+        );
+        assert_eq!(
+            check_window::<()>(0, 0, 0, 4, 122, 250),
+            Err(Error::InvalidWindow)
+        );
+        assert_eq!(
+            check_window::<()>(0, u32::MAX, 8, 4, 122, 250),
+            Err(Error::InvalidWindow)
+        );
+    }
+
+    #[test]
+    fn window_buffer() {
+        assert_eq!(check_window_buffer::<()>(&[0; 8], 10, 2, 2), Ok(()));
+        assert_eq!(
+            check_window_buffer::<()>(&[0; 4], 10, 2, 2),
+            Err(Error::InvalidBuffer)
+        );
+    }
+}